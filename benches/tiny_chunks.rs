@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use seq_data_file::{NoMagicNoHeader, SeqDataWriter};
+
+const CHUNKS: u64 = 1_000_000;
+const CHUNK: &[u8] = &[0x42; 20];
+
+fn append_tiny_chunks(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("seq-data-file-bench-{}.sdf", std::process::id()));
+    let mut group = c.benchmark_group("append tiny chunks");
+    group.throughput(Throughput::Elements(CHUNKS));
+    group.sample_size(10);
+    for buffering in [None, Some(64 * 1024)] {
+        let name = match buffering {
+            None => "unbuffered",
+            Some(_) => "buffered",
+        };
+        group.bench_function(BenchmarkId::new(name, CHUNKS), |b| {
+            b.iter(|| run_appends(&path, buffering))
+        });
+    }
+    group.finish();
+}
+
+fn run_appends(path: &PathBuf, buffering: Option<usize>) {
+    let _ = std::fs::remove_file(path);
+    let mut sdf = SeqDataWriter::<NoMagicNoHeader>::create(path, &[]).unwrap();
+    if let Some(capacity) = buffering {
+        sdf = sdf.with_buffering(capacity).unwrap();
+    }
+    for _ in 0..CHUNKS {
+        sdf.append(CHUNK).unwrap();
+    }
+    sdf.flush().unwrap();
+    drop(sdf);
+    std::fs::remove_file(path).unwrap();
+}
+
+criterion_group!(benches, append_tiny_chunks);
+criterion_main!(benches);
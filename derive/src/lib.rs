@@ -0,0 +1,57 @@
+//! Derive macro for the `SeqDataFormat` trait of `seq-data-file`
+//!
+//! This is re-exported by `seq-data-file` with the `derive` feature
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Expr, Meta};
+
+/// Derive `SeqDataFormat`, taking the magic from `#[magic = b"..."]` and the size of
+/// the header from `#[header_size = N]`
+///
+/// Both attributes are optional, for no magic and no header. The other constants
+/// of the format keep their default value.
+#[proc_macro_derive(SeqDataFormat, attributes(magic, header_size))]
+pub fn derive_seq_data_format(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut magic = None;
+    let mut header_size = None;
+    for attr in &input.attrs {
+        let slot = if attr.path().is_ident("magic") {
+            &mut magic
+        } else if attr.path().is_ident("header_size") {
+            &mut header_size
+        } else {
+            continue;
+        };
+        let value = match &attr.meta {
+            Meta::NameValue(nv) => nv.value.clone(),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "expected an attribute of the form `#[name = value]`",
+                ))
+            }
+        };
+        if slot.replace(value).is_some() {
+            return Err(syn::Error::new_spanned(attr, "duplicate attribute"));
+        }
+    }
+
+    let magic = magic.unwrap_or_else(|| syn::parse_quote!(b""));
+    let header_size: Expr = header_size.unwrap_or_else(|| syn::parse_quote!(0));
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::seq_data_file::SeqDataFormat for #name #ty_generics #where_clause {
+            const MAGIC: &'static [u8] = #magic;
+            const HEADER_SIZE: usize = #header_size;
+        }
+    })
+}
@@ -38,7 +38,7 @@ fn main() {
 fn run_writer_reader<H: SeqDataFormat>(sdf_file: &Path) {
     {
         let header = vec![0x90; H::HEADER_SIZE];
-        let mut sdf = SeqDataWriter::<H>::create(&sdf_file, &header).unwrap();
+        let mut sdf = SeqDataWriter::<H>::create(sdf_file, &header).unwrap();
         sdf.append(DATA1).unwrap();
         sdf.append(DATA2).unwrap();
         sdf.append(DATA3).unwrap();
@@ -46,7 +46,7 @@ fn run_writer_reader<H: SeqDataFormat>(sdf_file: &Path) {
 
     let mut pos = Vec::new();
     {
-        let (mut sdf, _header) = SeqDataReader::<H>::open(&sdf_file).unwrap();
+        let (mut sdf, _header) = SeqDataReader::<H>::open(sdf_file).unwrap();
         let (p1, r1) = sdf.next().unwrap().unwrap();
         let (p2, r2) = sdf.next().unwrap().unwrap();
         let (p3, r3) = sdf.next().unwrap().unwrap();
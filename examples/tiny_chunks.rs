@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use seq_data_file::{NoMagicNoHeader, SeqDataReader, SeqDataWriter};
+
+const CHUNKS: usize = 1_000_000;
+const CHUNK: &[u8] = &[0x42; 20];
+
+fn main() {
+    let sdf_file = PathBuf::from("tiny.sdf");
+
+    if sdf_file.exists() {
+        panic!("file {} already exists", &sdf_file.display())
+    }
+
+    let unbuffered = run_appends(&sdf_file, None);
+    std::fs::remove_file(&sdf_file).unwrap();
+    let buffered = run_appends(&sdf_file, Some(64 * 1024));
+    std::fs::remove_file(&sdf_file).unwrap();
+
+    println!(
+        "{} appends of {} bytes: unbuffered {:?}, buffered {:?}",
+        CHUNKS,
+        CHUNK.len(),
+        unbuffered,
+        buffered
+    );
+}
+
+fn run_appends(sdf_file: &Path, buffering: Option<usize>) -> std::time::Duration {
+    let start = Instant::now();
+    {
+        let mut sdf = SeqDataWriter::<NoMagicNoHeader>::create(sdf_file, &[]).unwrap();
+        if let Some(capacity) = buffering {
+            sdf = sdf.with_buffering(capacity).unwrap();
+        }
+        for _ in 0..CHUNKS {
+            sdf.append(CHUNK).unwrap();
+        }
+        sdf.flush().unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    let (mut sdf, _header) = SeqDataReader::<NoMagicNoHeader>::open(sdf_file).unwrap();
+    let mut count = 0;
+    while let Some(chunk) = sdf.next() {
+        let (pos, data) = chunk.unwrap();
+        assert_eq!(pos, (count * (4 + CHUNK.len())) as u64);
+        assert_eq!(data, CHUNK);
+        count += 1;
+    }
+    assert_eq!(count, CHUNKS);
+
+    elapsed
+}
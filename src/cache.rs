@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::format::SeqDataFormat;
+use crate::{Result, SeqDataReaderSeek};
+
+/// Seq Data Reader with seek, keeping recently accessed chunks in memory
+///
+/// The cache is bounded in number of entries, and optionally in number of bytes,
+/// and the least recently used chunks are evicted first.
+pub struct CachedSeekReader<Format: SeqDataFormat> {
+    reader: SeqDataReaderSeek<Format>,
+    // offset -> (last access, data)
+    entries: HashMap<u64, (u64, Vec<u8>)>,
+    // last access -> offset
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: Option<usize>,
+}
+
+impl<Format: SeqDataFormat> CachedSeekReader<Format> {
+    /// Wrap a seek reader with a cache holding at most `max_entries` chunks
+    pub fn new(reader: SeqDataReaderSeek<Format>, max_entries: usize) -> Self {
+        Self {
+            reader,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            max_entries,
+            max_bytes: None,
+        }
+    }
+
+    /// Also limit the cache to `max_bytes` bytes of chunk data
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self.evict();
+        self
+    }
+
+    /// Return the block at the offset specified, from the cache if present
+    ///
+    /// See `SeqDataReaderSeek::next_at` for the caveats on the offset
+    pub fn next_at(&mut self, pos: u64) -> Result<Vec<u8>> {
+        self.tick += 1;
+        if let Some((last_access, data)) = self.entries.get_mut(&pos) {
+            self.recency.remove(last_access);
+            self.recency.insert(self.tick, pos);
+            *last_access = self.tick;
+            return Ok(data.clone());
+        }
+
+        let data = self.reader.next_at(pos)?;
+        self.bytes += data.len();
+        self.recency.insert(self.tick, pos);
+        self.entries.insert(pos, (self.tick, data.clone()));
+        self.evict();
+        Ok(data)
+    }
+
+    /// Number of chunks currently cached
+    pub fn cached_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drop all the cached chunks
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.bytes = 0;
+    }
+
+    /// Return the underlying seek reader
+    pub fn into_inner(self) -> SeqDataReaderSeek<Format> {
+        self.reader
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries
+            || self.max_bytes.is_some_and(|max| self.bytes > max)
+        {
+            let Some((_, pos)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((_, data)) = self.entries.remove(&pos) {
+                self.bytes -= data.len();
+            }
+        }
+    }
+}
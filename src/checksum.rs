@@ -0,0 +1,112 @@
+/// Checksum algorithm computed over the metadata and data of each chunk
+///
+/// A format uses it by setting `SeqDataFormat::CHECKSUM` to
+/// `Some(ChecksumAlgorithm::of::<C>())`.
+pub trait ChunkChecksum: Default {
+    /// Checksum value, which need to be `WIDTH` bytes long
+    type Output: AsRef<[u8]>;
+    /// Size in bytes of the checksum written after each chunk
+    const WIDTH: usize;
+
+    /// Feed some data to the checksum
+    fn update(&mut self, data: &[u8]);
+
+    /// Return the checksum of all the data fed
+    fn finalize(self) -> Self::Output;
+}
+
+/// Checksum algorithm configured for a format, created from a `ChunkChecksum`
+#[derive(Clone, Copy)]
+pub struct ChecksumAlgorithm {
+    width: usize,
+    compute: fn(&[&[u8]], &mut [u8]),
+}
+
+impl ChecksumAlgorithm {
+    /// Checksum algorithm implemented by `C`
+    pub const fn of<C: ChunkChecksum>() -> Self {
+        ChecksumAlgorithm {
+            width: C::WIDTH,
+            compute: compute::<C>,
+        }
+    }
+
+    /// Size in bytes of the checksum written after each chunk
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Compute the checksum of the concatenation of `parts` into `out`, of `width` bytes
+    pub(crate) fn compute(&self, parts: &[&[u8]], out: &mut [u8]) {
+        (self.compute)(parts, out)
+    }
+}
+
+fn compute<C: ChunkChecksum>(parts: &[&[u8]], out: &mut [u8]) {
+    let mut checksum = C::default();
+    for part in parts {
+        checksum.update(part);
+    }
+    let value = checksum.finalize();
+    assert_eq!(
+        value.as_ref().len(),
+        C::WIDTH,
+        "checksum output doesn't match its width"
+    );
+    out.copy_from_slice(value.as_ref());
+}
+
+/// CRC32 (IEEE) checksum, written little endian
+#[derive(Default)]
+pub struct Crc32(crc32fast::Hasher);
+
+impl ChunkChecksum for Crc32 {
+    type Output = [u8; 4];
+    const WIDTH: usize = 4;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.0.finalize().to_le_bytes()
+    }
+}
+
+/// CRC32C (Castagnoli) checksum, written little endian
+#[cfg(feature = "crc32c")]
+#[derive(Default)]
+pub struct Crc32c(u32);
+
+#[cfg(feature = "crc32c")]
+impl ChunkChecksum for Crc32c {
+    type Output = [u8; 4];
+    const WIDTH: usize = 4;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0 = crc32c::crc32c_append(self.0, data)
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.0.to_le_bytes()
+    }
+}
+
+/// XXH64 checksum with a seed of 0, written little endian
+#[cfg(feature = "xxhash")]
+#[derive(Default)]
+pub struct XxHash64(xxhash_rust::xxh64::Xxh64);
+
+#[cfg(feature = "xxhash")]
+impl ChunkChecksum for XxHash64 {
+    type Output = [u8; 8];
+    const WIDTH: usize = 8;
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn finalize(self) -> Self::Output {
+        self.0.digest().to_le_bytes()
+    }
+}
@@ -0,0 +1,216 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::format::SeqDataFormat;
+use crate::logging::{debug, on_error};
+use crate::{
+    check_header_size, read_magic_and_header, validate_header, write_magic_and_header, Result,
+    SeqDataError,
+};
+
+const LENGTH_SIZE: u64 = 8;
+
+/// Writer for files with the lengths of all the chunks stored in a single block
+///
+/// The magic and header are the same as a normal SeqData, but the chunks are written
+/// without length prefix, and their lengths are written together after them by `close`:
+///
+/// ```text
+/// ┌─────┬──────┬─────┬─────┬─────┬──────┬──────┬─────┬─────┐
+/// │MAGIC│HEADER│data1│data2│.....│ len1 │ len2 │.....│count│
+/// └─────┴──────┴─────┴─────┴─────┴──────┴──────┴─────┴─────┘
+/// ```
+///
+/// where all the lengths and the count are little endian u64. The lengths are kept in
+/// memory until the writer is closed, and a file not closed is not readable.
+/// The other settings of the format (trailer magic, checksum, ...) are not used.
+pub struct ColumnarWriter<Format: SeqDataFormat> {
+    file: BufWriter<File>,
+    lengths: Vec<u64>,
+    phantom: PhantomData<Format>,
+}
+
+impl<Format: SeqDataFormat> ColumnarWriter<Format> {
+    /// Create a new file at the location specified
+    ///
+    /// If the file already exists, this call will fail
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub fn create<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<Self> {
+        check_header_size::<Format>(header)?;
+        validate_header::<Format>(header)?;
+
+        debug!(path = %path.as_ref().display(), "create columnar");
+        let file = on_error(
+            OpenOptions::new().write(true).create_new(true).open(path),
+            "create",
+        )?;
+        let mut file = BufWriter::new(file);
+        write_magic_and_header::<Format, _>(&mut file, header)?;
+        Ok(Self {
+            file,
+            lengths: Vec::new(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Append a new data chunk, returning its index
+    pub fn append(&mut self, data: &[u8]) -> Result<u64> {
+        self.file.write_all(data)?;
+        self.lengths.push(data.len() as u64);
+        Ok(self.lengths.len() as u64 - 1)
+    }
+
+    /// Finish writing, writing the lengths of the chunks and their count
+    pub fn close(mut self) -> Result<()> {
+        for len in &self.lengths {
+            self.file.write_all(&len.to_le_bytes())?;
+        }
+        self.file
+            .write_all(&(self.lengths.len() as u64).to_le_bytes())?;
+        Ok(self.file.flush()?)
+    }
+}
+
+/// Reader for files written by `ColumnarWriter`
+///
+/// The lengths of all the chunks are read in a single read when opening the file, so
+/// they are available upfront with `lengths`, e.g. to plan the reads.
+pub struct ColumnarReader<Format: SeqDataFormat> {
+    buf_reader: BufReader<File>,
+    start: u64,
+    lengths: Vec<u64>,
+    // offset of each chunk from `start`
+    offsets: Vec<u64>,
+    index: usize,
+    phantom: PhantomData<Format>,
+}
+
+impl<Format: SeqDataFormat> ColumnarReader<Format> {
+    /// Open a file written by `ColumnarWriter` for reading, returning its header
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open columnar for reading");
+        let mut file = on_error(File::open(path), "open")?;
+
+        let phantom = PhantomData;
+        let header = read_magic_and_header(phantom, &mut file)?;
+        let start = file.stream_position()?;
+        let total_len = file.seek(std::io::SeekFrom::End(0))?;
+        let lengths = on_error(read_lengths(&mut file, start, total_len), "header")?;
+
+        let mut offsets = Vec::with_capacity(lengths.len());
+        let mut offset = 0;
+        for len in &lengths {
+            offsets.push(offset);
+            offset += len;
+        }
+        file.seek(std::io::SeekFrom::Start(start))?;
+        let buf_reader = BufReader::with_capacity(1024 * 1024, file);
+        Ok((
+            Self {
+                buf_reader,
+                start,
+                lengths,
+                offsets,
+                index: 0,
+                phantom,
+            },
+            header,
+        ))
+    }
+
+    /// Lengths of all the chunks, in order
+    pub fn lengths(&self) -> &[u64] {
+        &self.lengths
+    }
+
+    /// Number of chunks in the file
+    pub fn count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Return the next chunk along with its index if it exists, or None if
+    /// reached the end of file.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(usize, Vec<u8>)>> {
+        let len = *self.lengths.get(self.index)?;
+        let mut data = vec![0; len as usize];
+        match self.buf_reader.read_exact(&mut data) {
+            Err(e) => Some(Err(e.into())),
+            Ok(()) => {
+                let index = self.index;
+                self.index += 1;
+                Some(Ok((index, data)))
+            }
+        }
+    }
+
+    /// Return the chunk at the index specified
+    ///
+    /// Sequential reading with `next` continues from the chunk following it
+    pub fn get(&mut self, index: usize) -> Result<Vec<u8>> {
+        let Some(offset) = self.offsets.get(index) else {
+            return Err(SeqDataError::IndexOutOfRange {
+                index: index as u64,
+                count: self.lengths.len() as u64,
+            });
+        };
+        self.buf_reader
+            .seek(std::io::SeekFrom::Start(self.start + offset))?;
+        self.index = index;
+        self.next().unwrap().map(|(_, data)| data)
+    }
+}
+
+// read the count at the end of the file, then the block of lengths before it, and check
+// that the lengths add up to the data between `start` and the block
+fn read_lengths(file: &mut File, start: u64, total_len: u64) -> Result<Vec<u64>> {
+    if total_len < start + LENGTH_SIZE {
+        return Err(SeqDataError::FileTooShort {
+            len: total_len,
+            min: start + LENGTH_SIZE,
+        });
+    }
+    let mut count = [0; LENGTH_SIZE as usize];
+    file.seek(std::io::SeekFrom::Start(total_len - LENGTH_SIZE))?;
+    file.read_exact(&mut count)?;
+    let count = u64::from_le_bytes(count);
+
+    let available = total_len - start - LENGTH_SIZE;
+    let block_len = count
+        .checked_mul(LENGTH_SIZE)
+        .filter(|block_len| *block_len <= available)
+        .ok_or_else(|| {
+            invalid_lengths(format!(
+                "{} chunks declared but only {} bytes available",
+                count, available
+            ))
+        })?;
+    let mut block = vec![0; block_len as usize];
+    file.seek(std::io::SeekFrom::Start(
+        total_len - LENGTH_SIZE - block_len,
+    ))?;
+    file.read_exact(&mut block)?;
+    let lengths = block
+        .chunks_exact(LENGTH_SIZE as usize)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect::<Vec<_>>();
+
+    let data_len = available - block_len;
+    let total = lengths
+        .iter()
+        .try_fold(0u64, |total, len| total.checked_add(*len));
+    if total != Some(data_len) {
+        return Err(invalid_lengths(format!(
+            "lengths of the chunks don't add up to the data length {}",
+            data_len
+        )));
+    }
+    Ok(lengths)
+}
+
+fn invalid_lengths(reason: String) -> SeqDataError {
+    SeqDataError::InvalidIndex(reason)
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::format::SeqDataFormat;
+use crate::{Result, SeqDataError, SeqDataReader, SeqDataWriter};
+
+/// Size in bytes of the key used to encrypt chunks
+pub const KEY_SIZE: usize = 32;
+
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+/// Writer for a SeqData where every chunk is encrypted with ChaCha20-Poly1305
+///
+/// Each chunk is stored as `[nonce][ciphertext][tag]` with a random nonce, and the
+/// length prefix covers all of it. The offset of the chunk is authenticated along
+/// with the data, so that chunks cannot be moved around undetected.
+pub struct EncryptedWriter<Format: SeqDataFormat> {
+    writer: SeqDataWriter<Format>,
+    cipher: ChaCha20Poly1305,
+}
+
+impl<Format: SeqDataFormat> SeqDataWriter<Format> {
+    /// Create a new SeqData File at the location specified, with chunks encrypted
+    /// with `key`
+    ///
+    /// The magic and header are stored in clear, see `SeqDataWriter::create`. Formats
+    /// with `MANIFEST` or `CHUNK_META_SIZE` set are not supported.
+    pub fn create_encrypted<P: AsRef<Path>>(
+        path: P,
+        header: &[u8],
+        key: &[u8; KEY_SIZE],
+    ) -> Result<EncryptedWriter<Format>> {
+        check_format::<Format>()?;
+        let writer = Self::create(path, header)?;
+        Ok(EncryptedWriter {
+            writer,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        })
+    }
+}
+
+impl<Format: SeqDataFormat> EncryptedWriter<Format> {
+    /// Open an encrypted SeqData File at the location specified, see `SeqDataWriter::open`
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        header: &[u8],
+        key: &[u8; KEY_SIZE],
+    ) -> Result<(Self, Vec<u8>)> {
+        check_format::<Format>()?;
+        let (writer, header) = SeqDataWriter::open(path, header)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        Ok((Self { writer, cipher }, header))
+    }
+
+    /// Encrypt and append a new data chunk to this file
+    pub fn append(&mut self, data: &[u8]) -> Result<()> {
+        let offset = self.writer.pos;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = offset.to_le_bytes();
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: data,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| SeqDataError::InvalidInput("chunk too large to be encrypted".into()))?;
+
+        let mut chunk = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        chunk.extend_from_slice(&nonce);
+        chunk.extend_from_slice(&ciphertext);
+        self.writer.append(&chunk)
+    }
+
+    /// Finish writing, see `SeqDataWriter::close`
+    pub fn close(self) -> Result<()> {
+        self.writer.close().map(|_| ())
+    }
+}
+
+/// Reader for a SeqData written by `EncryptedWriter`
+pub struct EncryptedReader<Format: SeqDataFormat> {
+    reader: SeqDataReader<Format>,
+    cipher: ChaCha20Poly1305,
+}
+
+impl<Format: SeqDataFormat> EncryptedReader<Format> {
+    /// Open an encrypted SeqData for reading
+    pub fn open<P: AsRef<Path>>(path: P, key: &[u8; KEY_SIZE]) -> Result<(Self, Vec<u8>)> {
+        check_format::<Format>()?;
+        let (reader, header) = SeqDataReader::open(path)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        Ok((Self { reader, cipher }, header))
+    }
+
+    /// Return the next decrypted block along with the current offset if it exists,
+    /// or None if reached the end of file.
+    ///
+    /// A chunk that fails authentication is reported as `SeqDataError::DecryptionFailed`
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(u64, Vec<u8>)>> {
+        match self.reader.next()? {
+            Err(e) => Some(Err(e)),
+            Ok((offset, chunk)) => {
+                Some(decrypt_chunk(&self.cipher, offset, &chunk).map(|data| (offset, data)))
+            }
+        }
+    }
+}
+
+// the whole chunk is encrypted, so there is no room for a manifest or a chunk metadata
+fn check_format<Format: SeqDataFormat>() -> Result<()> {
+    if Format::MANIFEST {
+        return Err(SeqDataError::Unsupported(
+            "encrypting a format with a manifest is not supported",
+        ));
+    }
+    if Format::CHUNK_META_SIZE != 0 {
+        return Err(SeqDataError::Unsupported(
+            "encrypting a format with chunk metadata is not supported",
+        ));
+    }
+    Ok(())
+}
+
+fn decrypt_chunk(cipher: &ChaCha20Poly1305, offset: u64, chunk: &[u8]) -> Result<Vec<u8>> {
+    if chunk.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(SeqDataError::DecryptionFailed { offset });
+    }
+    let (nonce, ciphertext) = chunk.split_at(NONCE_SIZE);
+    let aad = offset.to_le_bytes();
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| SeqDataError::DecryptionFailed { offset })
+}
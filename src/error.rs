@@ -0,0 +1,350 @@
+use std::fmt;
+
+/// Error returned when a chunk could not be appended in full
+///
+/// The writer tries to truncate the file back to the offset the chunk was supposed
+/// to start at, so that no torn chunk is left behind. `rolled_back` reports whether
+/// this truncation succeeded.
+#[derive(Debug)]
+pub struct PartialAppendError {
+    /// Offset at which the chunk was being appended
+    pub offset: u64,
+    /// Number of bytes (length prefix included) that landed before the failure
+    pub written: u64,
+    /// Whether the file has been truncated back to `offset`
+    pub rolled_back: bool,
+    /// The error that interrupted the append
+    pub error: std::io::Error,
+}
+
+impl fmt::Display for PartialAppendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "append at offset {} failed after {} bytes written ({}): {}",
+            self.offset,
+            self.written,
+            if self.rolled_back {
+                "rolled back"
+            } else {
+                "not rolled back"
+            },
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for PartialAppendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<PartialAppendError> for std::io::Error {
+    fn from(e: PartialAppendError) -> Self {
+        std::io::Error::new(e.error.kind(), e)
+    }
+}
+
+/// Result of the functions of this crate
+pub type Result<T, E = SeqDataError> = std::result::Result<T, E>;
+
+/// Error returned by the functions of this crate
+///
+/// The failures that callers may want to tell apart have their own variant, any other
+/// I/O failure is returned as `Io`. It converts to and from `std::io::Error`, so `?`
+/// works both ways. The `std::io::Error` returned by the trait implementations (`Read`,
+/// `Write`, `ReadAt`) carries the variant, and `SeqDataError::of` gives it back.
+#[derive(Debug)]
+pub enum SeqDataError {
+    /// The magic at the start of the file doesn't match the format
+    MagicMismatch,
+    /// The trailer magic at the end of the file doesn't match the format, the file might
+    /// not have been closed
+    TrailerMismatch,
+    /// The file is cut short after `len` bytes, before the `min` bytes of its magic,
+    /// header and trailer magic (or of the count of chunks for a columnar file)
+    FileTooShort { len: u64, min: u64 },
+    /// The header doesn't have the size of the format
+    HeaderSize { expected: usize, got: usize },
+    /// The header doesn't have the value expected by the caller
+    HeaderMismatch { expected: Vec<u8>, got: Vec<u8> },
+    /// The header, or its layout in the format, is not valid
+    InvalidHeader(String),
+    /// The chunk metadata doesn't have the size of the format
+    ChunkMetaSize { expected: usize, got: usize },
+    /// A chunk is cut short by the end of file after `available` bytes. `len` is the
+    /// length of the chunk data, or None if the length prefix itself is cut short
+    TruncatedChunk { len: Option<u64>, available: u64 },
+    /// A chunk is bigger than what the length prefix of the format can hold
+    ChunkTooLarge { len: u64, max: u64 },
+    /// Appending `grow` bytes would grow the file to `len` bytes, past the size limit
+    SizeLimitExceeded { grow: u64, len: u64, limit: u64 },
+    /// The checksum of a chunk doesn't match its data
+    ChecksumMismatch,
+    /// A chunk is not followed by the sentinel of the format
+    SentinelMismatch,
+    /// The trailing length of a chunk doesn't match its leading length
+    TrailingLengthMismatch { leading: u64, trailing: u64 },
+    /// The trailing length before `end` is for a chunk of `len` bytes that cannot end there
+    InvalidChunkEnd { len: u64, end: u64 },
+    /// The chunk at `offset` is corrupted, `error` tells how
+    CorruptedChunk {
+        offset: u64,
+        error: Box<SeqDataError>,
+    },
+    /// A record made of continued chunks is cut short by the end of file
+    TruncatedRecord,
+    /// A chunk of `len` bytes is overwritten with `got` bytes
+    ChunkSizeMismatch { len: u64, got: u64 },
+    /// The offset is past the data length `len`
+    OffsetOutOfRange { offset: u64, len: u64 },
+    /// The index of a chunk or record is past the `count` of them
+    IndexOutOfRange { index: u64, count: u64 },
+    /// The range `start..end` is reversed
+    InvalidRange { start: u64, end: u64 },
+    /// The buffers of `capacity` bytes are too small for a block of `len` bytes
+    BufferTooSmall { capacity: u64, len: u64 },
+    /// An argument is not valid
+    InvalidInput(String),
+    /// The operation is not supported by the format, or with the options given
+    Unsupported(&'static str),
+    /// The format has a manifest but it has not been written, or the file doesn't
+    /// have it
+    ManifestMissing,
+    /// The manifest has already been written
+    ManifestAlreadyWritten,
+    /// The index was built for `indexed` bytes of data, past the data length `len`
+    IndexMismatch { indexed: u64, len: u64 },
+    /// The index, or the lengths of the chunks of a columnar file, are not valid
+    InvalidIndex(String),
+    /// The data length `len` is not a multiple of the record size
+    RecordSizeMismatch { len: u64, record_size: u64 },
+    /// The chunk at `offset` failed authentication, or is too small to be encrypted
+    DecryptionFailed { offset: u64 },
+    /// A value couldn't be encoded to, or decoded from, a chunk
+    Encoding(Box<dyn std::error::Error + Send + Sync>),
+    /// The writer task stopped, and doesn't take appends anymore
+    WriterStopped,
+    /// A range request at `offset` returned `got` bytes instead of `expected`
+    RangeResponseLength {
+        offset: u64,
+        expected: u64,
+        got: u64,
+    },
+    /// Any other I/O error
+    Io(std::io::Error),
+}
+
+impl SeqDataError {
+    /// Return the `SeqDataError` carried by an error returned by this crate, if any
+    pub fn of(e: &std::io::Error) -> Option<&SeqDataError> {
+        e.get_ref()?.downcast_ref()
+    }
+
+    /// Return the kind of the `std::io::Error` this converts to
+    pub fn kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            SeqDataError::TruncatedChunk { .. }
+            | SeqDataError::FileTooShort { .. }
+            | SeqDataError::TruncatedRecord => ErrorKind::UnexpectedEof,
+            SeqDataError::MagicMismatch
+            | SeqDataError::TrailerMismatch
+            | SeqDataError::HeaderSize { .. }
+            | SeqDataError::HeaderMismatch { .. }
+            | SeqDataError::InvalidHeader(_)
+            | SeqDataError::ChecksumMismatch
+            | SeqDataError::SentinelMismatch
+            | SeqDataError::TrailingLengthMismatch { .. }
+            | SeqDataError::InvalidChunkEnd { .. }
+            | SeqDataError::CorruptedChunk { .. }
+            | SeqDataError::ManifestMissing
+            | SeqDataError::IndexMismatch { .. }
+            | SeqDataError::InvalidIndex(_)
+            | SeqDataError::RecordSizeMismatch { .. }
+            | SeqDataError::DecryptionFailed { .. }
+            | SeqDataError::Encoding(_)
+            | SeqDataError::RangeResponseLength { .. } => ErrorKind::InvalidData,
+            SeqDataError::ChunkMetaSize { .. }
+            | SeqDataError::ChunkTooLarge { .. }
+            | SeqDataError::ChunkSizeMismatch { .. }
+            | SeqDataError::OffsetOutOfRange { .. }
+            | SeqDataError::IndexOutOfRange { .. }
+            | SeqDataError::InvalidRange { .. }
+            | SeqDataError::BufferTooSmall { .. }
+            | SeqDataError::InvalidInput(_)
+            | SeqDataError::ManifestAlreadyWritten => ErrorKind::InvalidInput,
+            SeqDataError::SizeLimitExceeded { .. } => ErrorKind::FileTooLarge,
+            SeqDataError::Unsupported(_) => ErrorKind::Unsupported,
+            SeqDataError::WriterStopped => ErrorKind::BrokenPipe,
+            SeqDataError::Io(e) => e.kind(),
+        }
+    }
+}
+
+impl fmt::Display for SeqDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeqDataError::MagicMismatch => write!(f, "magic do not match expected value"),
+            SeqDataError::TrailerMismatch => write!(
+                f,
+                "trailer magic do not match expected value, file might not have been closed"
+            ),
+            SeqDataError::FileTooShort { len: 0, .. } => {
+                write!(f, "file is empty, its creation might have been interrupted")
+            }
+            SeqDataError::FileTooShort { len, min } => write!(
+                f,
+                "file not contains enough bytes, expecting at least {} but got {}",
+                min, len
+            ),
+            SeqDataError::HeaderSize { expected, got } => write!(
+                f,
+                "header has invalid size, expecting {} but got {}",
+                expected, got
+            ),
+            SeqDataError::HeaderMismatch { expected, got } => write!(
+                f,
+                "header do not match expected value, expecting {:02x?} but got {:02x?}",
+                expected, got
+            ),
+            SeqDataError::InvalidHeader(reason) => write!(f, "invalid header: {}", reason),
+            SeqDataError::ChunkMetaSize { expected, got } => write!(
+                f,
+                "chunk meta has invalid size, expecting {} but got {}",
+                expected, got
+            ),
+            SeqDataError::TruncatedChunk {
+                len: None,
+                available,
+            } => write!(
+                f,
+                "chunk length cut short by the end of file after {} bytes",
+                available
+            ),
+            SeqDataError::TruncatedChunk {
+                len: Some(len),
+                available,
+            } => write!(
+                f,
+                "chunk of {} bytes cut short by the end of file after {} bytes",
+                len, available
+            ),
+            SeqDataError::ChunkTooLarge { len, max } => write!(
+                f,
+                "chunk of {} bytes doesn't fit the length prefix, maximum {}",
+                len, max
+            ),
+            SeqDataError::SizeLimitExceeded { grow, len, limit } => write!(
+                f,
+                "appending {} bytes would grow the file to {} bytes past the limit of {}",
+                grow, len, limit
+            ),
+            SeqDataError::ChecksumMismatch => write!(f, "chunk checksum doesn't match its data"),
+            SeqDataError::SentinelMismatch => write!(f, "chunk is not followed by the sentinel"),
+            SeqDataError::TrailingLengthMismatch { leading, trailing } => write!(
+                f,
+                "chunk trailing length {} doesn't match leading length {}",
+                trailing, leading
+            ),
+            SeqDataError::InvalidChunkEnd { len, end } => {
+                write!(f, "chunk of {} bytes cannot end at {}", len, end)
+            }
+            SeqDataError::CorruptedChunk { offset, error } => {
+                write!(f, "corrupted chunk at offset {}: {}", offset, error)
+            }
+            SeqDataError::TruncatedRecord => write!(f, "record continues past the end of file"),
+            SeqDataError::ChunkSizeMismatch { len, got } => write!(
+                f,
+                "chunk has a size of {} but trying to overwrite with {} bytes",
+                len, got
+            ),
+            SeqDataError::OffsetOutOfRange { offset, len } => write!(
+                f,
+                "trying to access data at {} but data length {}",
+                offset, len
+            ),
+            SeqDataError::IndexOutOfRange { index, count } => write!(
+                f,
+                "trying to access chunk {} but only {} chunks",
+                index, count
+            ),
+            SeqDataError::InvalidRange { start, end } => {
+                write!(f, "invalid range {}..{}", start, end)
+            }
+            SeqDataError::BufferTooSmall { capacity, len } => write!(
+                f,
+                "buffers of {} bytes cannot hold block of {} bytes",
+                capacity, len
+            ),
+            SeqDataError::InvalidInput(reason) => write!(f, "invalid input: {}", reason),
+            SeqDataError::Unsupported(reason) => write!(f, "{}", reason),
+            SeqDataError::ManifestMissing => {
+                write!(f, "manifest missing, it need to be the first chunk")
+            }
+            SeqDataError::ManifestAlreadyWritten => write!(f, "manifest already written"),
+            SeqDataError::IndexMismatch { indexed, len } => write!(
+                f,
+                "index for data length {} but data length {}",
+                indexed, len
+            ),
+            SeqDataError::InvalidIndex(reason) => write!(f, "invalid index: {}", reason),
+            SeqDataError::RecordSizeMismatch { len, record_size } => write!(
+                f,
+                "data length {} is not a multiple of the record size {}",
+                len, record_size
+            ),
+            SeqDataError::DecryptionFailed { offset } => {
+                write!(f, "encrypted chunk at {} failed authentication", offset)
+            }
+            SeqDataError::Encoding(e) => write!(f, "encoding error: {}", e),
+            SeqDataError::WriterStopped => write!(f, "writer stopped"),
+            SeqDataError::RangeResponseLength {
+                offset,
+                expected,
+                got,
+            } => write!(
+                f,
+                "range request at {} returned {} bytes instead of {}",
+                offset, got, expected
+            ),
+            SeqDataError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SeqDataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SeqDataError::Io(e) => Some(e),
+            SeqDataError::CorruptedChunk { error, .. } => Some(error),
+            SeqDataError::Encoding(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<PartialAppendError> for SeqDataError {
+    fn from(e: PartialAppendError) -> Self {
+        SeqDataError::Io(e.into())
+    }
+}
+
+impl From<std::io::Error> for SeqDataError {
+    fn from(e: std::io::Error) -> Self {
+        if SeqDataError::of(&e).is_none() {
+            return SeqDataError::Io(e);
+        }
+        *e.into_inner().unwrap().downcast().unwrap()
+    }
+}
+
+impl From<SeqDataError> for std::io::Error {
+    fn from(e: SeqDataError) -> Self {
+        match e {
+            SeqDataError::Io(e) => e,
+            e => std::io::Error::new(e.kind(), e),
+        }
+    }
+}
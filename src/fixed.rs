@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::format::SeqDataFormat;
+use crate::logging::on_error;
+use crate::{get_file_length, read_magic_and_header, Result, SeqDataError};
+
+/// Reader for files made of records of `N` bytes, without length prefix
+///
+/// The magic and header are the same as a normal SeqData, but the data is
+/// a plain array of records of a size known from the format.
+pub struct FixedRecordReader<Format: SeqDataFormat, const N: usize> {
+    buf_reader: BufReader<File>,
+    start: u64,
+    index: u64,
+    count: u64,
+    phantom: PhantomData<Format>,
+}
+
+impl<Format: SeqDataFormat, const N: usize> FixedRecordReader<Format, N> {
+    /// Open a file of fixed size records for reading
+    ///
+    /// The data length need to be a multiple of `N`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        if N == 0 {
+            return Err(SeqDataError::InvalidInput("record size cannot be 0".into()));
+        }
+        let mut file = on_error(File::open(path), "open")?;
+
+        let phantom = PhantomData;
+        let len = get_file_length(phantom, &mut file)?;
+        let header = read_magic_and_header(phantom, &mut file)?;
+        if len % N as u64 != 0 {
+            return on_error(
+                Err(SeqDataError::RecordSizeMismatch {
+                    len,
+                    record_size: N as u64,
+                }),
+                "open",
+            );
+        }
+
+        let start = file.stream_position()?;
+        let buf_reader = BufReader::with_capacity(1024 * 1024, file);
+        Ok((
+            Self {
+                buf_reader,
+                start,
+                index: 0,
+                count: len / N as u64,
+                phantom,
+            },
+            header,
+        ))
+    }
+
+    /// Number of records in the file
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the next record along with its index if it exists, or None if
+    /// reached the end of file.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(u64, [u8; N])>> {
+        if self.index >= self.count {
+            return None;
+        }
+        let mut record = [0u8; N];
+        match self.buf_reader.read_exact(&mut record) {
+            Err(e) => Some(on_error(Err(e.into()), "next")),
+            Ok(()) => {
+                let index = self.index;
+                self.index += 1;
+                Some(Ok((index, record)))
+            }
+        }
+    }
+
+    /// Return the record at the index specified
+    ///
+    /// Sequential reading with `next` continues from the record following it
+    pub fn get(&mut self, index: u64) -> Result<[u8; N]> {
+        if index >= self.count {
+            return Err(SeqDataError::IndexOutOfRange {
+                index,
+                count: self.count,
+            });
+        }
+        let seek = self.start + index * N as u64;
+        self.buf_reader.seek(std::io::SeekFrom::Start(seek))?;
+        self.index = index;
+        self.next().unwrap().map(|(_, record)| record)
+    }
+}
@@ -1,9 +1,143 @@
+use crate::checksum::ChecksumAlgorithm;
+
+/// Width of the little endian length prefix of each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixWidth {
+    /// 2 bytes, for chunks of at most 64 KiB
+    U16,
+    /// 4 bytes, for chunks of at most 4 GiB
+    U32,
+    /// 8 bytes
+    U64,
+}
+
+impl PrefixWidth {
+    /// Size in bytes of the length prefix
+    pub const fn size(self) -> usize {
+        match self {
+            PrefixWidth::U16 => 2,
+            PrefixWidth::U32 => 4,
+            PrefixWidth::U64 => 8,
+        }
+    }
+}
+
 /// Format configuration for SeqData
 pub trait SeqDataFormat {
     /// Magic bytes. can be empty
     const MAGIC: &'static [u8];
     /// The size of the header in bytes
     const HEADER_SIZE: usize;
+    /// Whether the data starts with a variable size manifest chunk
+    ///
+    /// When set, the manifest has to be written with `SeqDataWriter::write_manifest`
+    /// before any other chunk, and is returned by `SeqDataReader::manifest`
+    const MANIFEST: bool = false;
+    /// Width of the length prefix of each chunk, which bounds the size of the chunks
+    ///
+    /// Appending a chunk too big for the width fails. The trailing length, if any, has
+    /// the same width.
+    const PREFIX_WIDTH: PrefixWidth = PrefixWidth::U32;
+    /// Whether the length of each chunk is also written after its data
+    ///
+    /// This allows finding a chunk from the offset where it ends, with
+    /// `SeqDataReaderSeek::read_ending_at`
+    const TRAILING_LENGTH: bool = false;
+    /// Bytes written after each chunk (after the trailing length if any). can be empty
+    ///
+    /// Readers check the sentinel after each chunk, and `SeqDataReader::next_skipping_errors`
+    /// scans for it to resynchronize after corrupted data
+    const SENTINEL: &'static [u8] = &[];
+    /// Size of the fixed metadata stored between the length prefix and the data of each chunk
+    ///
+    /// When set, chunks are written with `SeqDataWriter::append_with_meta`, and the length
+    /// prefix only covers the data. `SeqDataReader::next_with_meta` returns the metadata
+    /// and the data separately.
+    const CHUNK_META_SIZE: usize = 0;
+    /// Magic bytes at the end of the file. can be empty
+    ///
+    /// When set, the trailer magic is written by `SeqDataWriter::close`, and checked
+    /// when opening the file, so a writer need to be closed for its file to be readable
+    const TRAILER_MAGIC: &'static [u8] = &[];
+    /// Whether the top bit of the length prefix flags a chunk continued by the next one
+    ///
+    /// This allows records bigger than the maximum chunk size, written with
+    /// `SeqDataWriter::append_large` and read with `SeqDataReader::next_record`, at the
+    /// cost of halving the maximum chunk size
+    const CONTINUATION: bool = false;
+    /// Offset in the header of a byte flagging the file as not properly closed
+    ///
+    /// When set, `SeqDataWriter` marks the file dirty when creating or opening it, and
+    /// clean on `close`, so that `SeqDataReader::was_clean` detects a writer that
+    /// didn't shut down properly. The value given for this byte in the header is ignored.
+    ///
+    /// The async `nonblocking::SeqDataWriter` doesn't maintain the flag, and fails to
+    /// create or open a file of such a format with an `Unsupported` error.
+    const DIRTY_FLAG: Option<usize> = None;
+    /// Offset in the header of a u32 LE holding the number of zero bytes padding the
+    /// end of the file
+    ///
+    /// When set, `SeqDataWriter::close_padded` pads the file to a multiple of a block size,
+    /// for writes to block devices or with `O_DIRECT`, and records the padding there.
+    /// Readers subtract the padding from the file length, so it is never read as a chunk.
+    /// These bytes need to be zero in the header given when creating the file.
+    const PADDING_LENGTH: Option<usize> = None;
+    /// Checksum written after the data of each chunk, and verified when reading it
+    ///
+    /// The checksum covers the chunk metadata and data, and is followed by the trailing
+    /// length and sentinel if any. e.g. `Some(ChecksumAlgorithm::of::<Crc32>())`
+    ///
+    /// The length prefix doesn't count the checksum. A chunk not matching its checksum
+    /// is returned by the readers as a `SeqDataError::CorruptedChunk` giving its offset.
+    const CHECKSUM: Option<ChecksumAlgorithm> = None;
+    /// Data of the sync marker chunks, that readers can resynchronize on after corrupted
+    /// data. can be empty, to not use markers
+    ///
+    /// A marker is written by `SeqDataWriter::append_sync_marker` as a regular chunk: the
+    /// length prefix, zeroed metadata, these bytes as data, then the checksum, trailing
+    /// length and sentinel if any. `SeqDataReader::resync_to_marker` scans for this exact
+    /// encoding. Markers are returned by the readers like any other chunk, so chunks with
+    /// the same data are taken for markers.
+    const SYNC_MARKER: &'static [u8] = &[];
+    /// Whether a u64 LE count of the chunks is reserved right after the header
+    ///
+    /// The count is maintained by `SeqDataWriter`, which writes it in place on `flush`
+    /// and `close`, and is returned by `SeqDataReader::declared_count` without scanning
+    /// the chunks. The manifest chunk is not counted. The count is not part of the data,
+    /// so chunk offsets start after it.
+    ///
+    /// The async `nonblocking::SeqDataWriter` doesn't maintain the count, and fails to
+    /// create or open a file of such a format with an `Unsupported` error.
+    const CHUNK_COUNT: bool = false;
+
+    /// Check the invariants of a header, beyond its size
+    ///
+    /// This is called on the header given when creating a file, and on the header
+    /// read when opening one. The error is reported as `SeqDataError::InvalidHeader`.
+    fn validate_header(header: &[u8]) -> Result<(), String> {
+        let _ = header;
+        Ok(())
+    }
+}
+
+/// Object safe version of `SeqDataFormat`, to handle formats only known at runtime
+///
+/// This is implemented for every type implementing `SeqDataFormat`
+pub trait DynSeqDataFormat {
+    /// Magic bytes. can be empty
+    fn magic(&self) -> &'static [u8];
+    /// The size of the header in bytes
+    fn header_size(&self) -> usize;
+}
+
+impl<F: SeqDataFormat> DynSeqDataFormat for F {
+    fn magic(&self) -> &'static [u8] {
+        F::MAGIC
+    }
+
+    fn header_size(&self) -> usize {
+        F::HEADER_SIZE
+    }
 }
 
 pub struct NoMagicNoHeader;
@@ -0,0 +1,70 @@
+use crate::format::SeqDataFormat;
+use crate::{ReadAt, Result, SeqDataError, SeqDataReaderSeek};
+
+/// HTTP client able to fetch byte ranges of a remote file
+///
+/// This is implemented by the user with the HTTP library of their choice,
+/// usually as a GET with a `Range: bytes=start-end` header.
+pub trait HttpRangeClient {
+    /// Return the total length of the remote file
+    fn content_length(&self, url: &str) -> std::io::Result<u64>;
+    /// Return the `len` bytes of the remote file starting at `start`
+    ///
+    /// The range requested is always within the file, and a response of another
+    /// length is an error.
+    fn get_range(&self, url: &str, start: u64, len: u64) -> std::io::Result<Vec<u8>>;
+}
+
+/// Remote file fetched with HTTP range requests, as a `ReadAt` backend
+pub struct HttpBackend<C: HttpRangeClient> {
+    client: C,
+    url: String,
+}
+
+impl<C: HttpRangeClient> HttpBackend<C> {
+    pub fn new(client: C, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+        }
+    }
+
+    /// Return the client used for the range requests
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+}
+
+impl<C: HttpRangeClient> ReadAt for HttpBackend<C> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let data = self.client.get_range(&self.url, offset, buf.len() as u64)?;
+        if data.len() != buf.len() {
+            return Err(SeqDataError::RangeResponseLength {
+                offset,
+                expected: buf.len() as u64,
+                got: data.len() as u64,
+            }
+            .into());
+        }
+        buf.copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        self.client.content_length(&self.url)
+    }
+}
+
+/// Seq Data Reader with seek, over a remote file fetched with HTTP range requests
+///
+/// Opening costs a request for the length, one for the magic and header, and one
+/// for the trailer magic if the format has one. Each `next_at` costs two range
+/// requests, one for the length prefix and one for the chunk.
+pub type RemoteSeekReader<C, Format> = SeqDataReaderSeek<Format, HttpBackend<C>>;
+
+impl<C: HttpRangeClient, Format: SeqDataFormat> SeqDataReaderSeek<Format, HttpBackend<C>> {
+    /// Open the SeqData at `url`, returning the reader and the header
+    pub fn open(client: C, url: &str) -> Result<(Self, Vec<u8>)> {
+        Self::from_backend(HttpBackend::new(client, url))
+    }
+}
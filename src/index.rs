@@ -0,0 +1,219 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::format::SeqDataFormat;
+use crate::logging::{debug, on_error};
+use crate::{Result, SeqDataError, SeqDataReader, SeqDataReaderSeek};
+
+const INDEX_MAGIC: &[u8] = b"SDIX";
+const INDEX_VERSION: u8 = 1;
+const INDEX_HEADER_SIZE: usize = INDEX_MAGIC.len() + 1 + 8 + 8;
+
+/// Offsets of all the chunks of a SeqData
+///
+/// The serialized form is independent of the platform:
+///
+/// ```text
+/// ┌─────┬───────┬────────┬─────┬───────┬───────┬───────┐
+/// │SDIX │version│data len│count│offset1│offset2│.......│
+/// └─────┴───────┴────────┴─────┴───────┴───────┴───────┘
+/// ```
+///
+/// where version is a single byte, and all the other integers are little endian u64
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    data_len: u64,
+    offsets: Vec<u64>,
+}
+
+impl Index {
+    /// Create an index from the offsets of a data of `data_len` bytes
+    pub fn new(data_len: u64, offsets: Vec<u64>) -> Self {
+        Self { data_len, offsets }
+    }
+
+    /// Build the index of the SeqData at the location specified, by scanning all the chunks
+    pub fn build<Format: SeqDataFormat>(path: impl AsRef<Path>) -> Result<Self> {
+        let (mut reader, _) = SeqDataReader::<Format>::open(path)?;
+        let offsets = reader.offsets().collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(reader.len(), offsets))
+    }
+
+    /// Location of the index file kept alongside the SeqData at the location specified,
+    /// which is the same path with `.idx` appended, e.g. `data.sdf.idx` for `data.sdf`
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut sidecar = OsString::from(path.as_ref());
+        sidecar.push(".idx");
+        PathBuf::from(sidecar)
+    }
+
+    /// Write the index to the file at the location specified, replacing it if it exists
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_bytes())?)
+    }
+
+    /// Read an index written by `save` from the file at the location specified
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Whether the SeqData indexed has grown to `data_len` bytes since being indexed
+    pub fn is_stale(&self, data_len: u64) -> bool {
+        self.data_len != data_len
+    }
+
+    /// Add the offsets of the chunks appended to the SeqData at the location specified
+    /// since it was indexed, returning how many were added
+    ///
+    /// Only the chunks following the data indexed are scanned. The data is expected to
+    /// only grow, and a data shorter than indexed is an error.
+    pub fn update<Format: SeqDataFormat>(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let (mut reader, _) = SeqDataReader::<Format>::open(path)?;
+        if reader.len() < self.data_len {
+            return Err(SeqDataError::IndexMismatch {
+                indexed: self.data_len,
+                len: reader.len(),
+            });
+        }
+        reader.seek_to_offset(self.data_len)?;
+        let offsets = reader.offsets().collect::<Result<Vec<_>>>()?;
+        self.offsets.extend_from_slice(&offsets);
+        self.data_len = reader.len();
+        Ok(offsets.len())
+    }
+
+    /// Length of the data indexed
+    pub fn data_len(&self) -> u64 {
+        self.data_len
+    }
+
+    /// Offsets of the chunks
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Offset of the chunk at the index specified
+    pub fn get(&self, index: usize) -> Option<u64> {
+        self.offsets.get(index).copied()
+    }
+
+    /// Number of chunks indexed
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Serialize the index
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(INDEX_HEADER_SIZE + self.offsets.len() * 8);
+        out.extend_from_slice(INDEX_MAGIC);
+        out.push(INDEX_VERSION);
+        out.extend_from_slice(&self.data_len.to_le_bytes());
+        out.extend_from_slice(&(self.offsets.len() as u64).to_le_bytes());
+        for offset in &self.offsets {
+            out.extend_from_slice(&offset.to_le_bytes());
+        }
+        out
+    }
+
+    /// Deserialize an index serialized with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < INDEX_HEADER_SIZE {
+            return Err(SeqDataError::FileTooShort {
+                len: bytes.len() as u64,
+                min: INDEX_HEADER_SIZE as u64,
+            });
+        }
+        let (magic, rest) = bytes.split_at(INDEX_MAGIC.len());
+        if magic != INDEX_MAGIC {
+            return Err(SeqDataError::MagicMismatch);
+        }
+        let (version, rest) = rest.split_at(1);
+        if version[0] != INDEX_VERSION {
+            return Err(invalid_index(format!(
+                "unsupported index version {}",
+                version[0]
+            )));
+        }
+        let (data_len, rest) = rest.split_at(8);
+        let (count, rest) = rest.split_at(8);
+        let data_len = u64::from_le_bytes(data_len.try_into().unwrap());
+        let count = u64::from_le_bytes(count.try_into().unwrap());
+        if count.checked_mul(8) != Some(rest.len() as u64) {
+            return Err(invalid_index(format!(
+                "index declares {} offsets but contains {} bytes of offsets",
+                count,
+                rest.len()
+            )));
+        }
+        let offsets = rest
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        Ok(Self { data_len, offsets })
+    }
+}
+
+/// Reader accessing the chunks of a SeqData by their index, using the index file kept
+/// alongside it
+///
+/// The index file is at `Index::sidecar_path` of the data, and is written with
+/// `Index::build` then `Index::save`. Chunks appended to the data since are added to the
+/// index in memory when opening, and `index` gives the updated index to save it again.
+pub struct SeqDataIndexedReader<Format: SeqDataFormat> {
+    reader: SeqDataReaderSeek<Format>,
+    index: Index,
+}
+
+impl<Format: SeqDataFormat> SeqDataIndexedReader<Format> {
+    /// Open the SeqData at the location specified along with its index file, returning
+    /// the header
+    ///
+    /// A stale index is updated in memory only, the index file is not written. Save
+    /// `index()` with `Index::save` so that the next open doesn't scan the chunks
+    /// appended since again.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        let path = path.as_ref();
+        let mut index = on_error(Index::load(Index::sidecar_path(path)), "open index")?;
+        let (reader, header) = SeqDataReaderSeek::<Format>::open(path)?;
+        if index.is_stale(reader.append_position()) {
+            debug!(path = %path.display(), indexed = index.data_len(), "update stale index");
+            index.update::<Format>(path)?;
+        }
+        Ok((Self { reader, index }, header))
+    }
+
+    /// Index of the data, including the chunks appended since the index file was written
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// Number of chunks indexed
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Return the block of the chunk at the index specified
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, the block starts with the chunk metadata
+    pub fn get(&mut self, index: usize) -> Result<Vec<u8>> {
+        let Some(offset) = self.index.get(index) else {
+            return Err(SeqDataError::IndexOutOfRange {
+                index: index as u64,
+                count: self.index.len() as u64,
+            });
+        };
+        self.reader.next_at(offset)
+    }
+}
+
+fn invalid_index(reason: String) -> SeqDataError {
+    SeqDataError::InvalidIndex(reason)
+}
@@ -1,4 +1,8 @@
-use std::{fs::OpenOptions, io::Read, path::Path};
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::Path,
+};
 
 /// this is a version of read_exact that returns a None if the stream is empty
 pub fn optional_read_exact<R: Read + ?Sized>(
@@ -30,6 +34,47 @@ pub fn optional_read_exact<R: Read + ?Sized>(
     }
 }
 
+/// read into `buf` until it is full or the end of the stream is reached,
+/// returning the number of bytes read
+pub fn read_up_to<R: Read + ?Sized>(this: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut read_bytes = 0;
+    while read_bytes < buf.len() {
+        match this.read(&mut buf[read_bytes..]) {
+            Ok(0) => break,
+            Ok(n) => read_bytes += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read_bytes)
+}
+
+/// this is a version of write_all that keeps track of the number of bytes written,
+/// even when returning an error
+pub fn write_all_counted<W: Write + ?Sized>(
+    this: &mut W,
+    mut buf: &[u8],
+    written: &mut u64,
+) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match this.write(buf) {
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => {
+                buf = &buf[n..];
+                *written += n as u64;
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
 pub fn truncate_at(path: &Path, len: u64) -> std::io::Result<()> {
     let file = OpenOptions::new()
         .read(false)
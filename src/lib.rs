@@ -1,286 +1,3056 @@
 //! Seq Data is a simple file format that contains multiple chunks of data prefixed by a length
+//!
+//! Paths given to the open and create functions are passed as is to `std::fs`, so UNC
+//! and verbatim (`\\?\` prefixed) paths work on Windows, and paths longer than
+//! `MAX_PATH` are made verbatim by the standard library.
+use std::borrow::Cow;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, Read, Seek, Write};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write};
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::Arc;
 
+mod checksum;
+mod columnar;
+mod error;
+mod fixed;
 mod format;
+mod index;
 mod ioutils;
+mod logging;
+mod read_at;
+mod tools;
 
 #[cfg(feature = "async")]
 pub mod nonblocking;
 
-pub use format::{NoMagicNoHeader, SeqDataFormat};
-use ioutils::optional_read_exact;
+#[cfg(feature = "cache")]
+mod cache;
+
+#[cfg(feature = "crypto")]
+mod crypto;
+
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+
+#[cfg(feature = "testutil")]
+pub mod testutil;
+
+#[cfg(feature = "bincode")]
+mod serialize;
+
+#[cfg(feature = "cache")]
+pub use cache::CachedSeekReader;
+#[cfg(feature = "crc32c")]
+pub use checksum::Crc32c;
+#[cfg(feature = "xxhash")]
+pub use checksum::XxHash64;
+pub use checksum::{ChecksumAlgorithm, ChunkChecksum, Crc32};
+pub use columnar::{ColumnarReader, ColumnarWriter};
+#[cfg(feature = "crypto")]
+pub use crypto::{EncryptedReader, EncryptedWriter, KEY_SIZE};
+pub use error::{PartialAppendError, Result, SeqDataError};
+pub use fixed::FixedRecordReader;
+pub use format::{DynSeqDataFormat, NoMagicNoHeader, PrefixWidth, SeqDataFormat};
+#[cfg(feature = "http")]
+pub use http::{HttpBackend, HttpRangeClient, RemoteSeekReader};
+pub use index::{Index, SeqDataIndexedReader};
 pub use ioutils::truncate_at;
+use ioutils::{optional_read_exact, read_up_to, write_all_counted};
+use logging::{debug, on_error, trace};
+#[cfg(feature = "mmap")]
+pub use mmap::SeqDataReaderMmap;
+pub use read_at::ReadAt;
+#[cfg(feature = "derive")]
+pub use seq_data_file_derive::SeqDataFormat;
+pub use tools::{
+    ChunkMeta, Description, SeqData, Stats, TimingSummary, VerifyReport, VerifyResult,
+};
 
-/// Writer for a new SeqData
-pub struct SeqDataWriter<Format: SeqDataFormat> {
-    file: File,
+/// Offset of a chunk, along with its metadata and its data
+pub type MetaChunk = (u64, Vec<u8>, Vec<u8>);
+
+/// Writer for a new SeqData, framing the chunks into any `Write` sink
+///
+/// This allows streaming SeqData to a socket or any other destination. The methods
+/// that need to seek or truncate the output (reopening an existing file and
+/// `overwrite_at`) are only available on the file based `SeqDataWriter`, and
+/// `try_append` only rolls back on sinks set with `with_truncate`.
+pub struct SeqDataStreamWriter<W: Write, Format: SeqDataFormat> {
+    file: BufWriter<W>,
+    pos: u64,
+    size_limit: Option<u64>,
+    on_close: Option<fn(&mut W) -> Result<()>>,
+    // number of chunks appended, manifest excluded, for formats with `CHUNK_COUNT` set
+    count: u64,
+    // write `count` in place in the file, when the sink allows it
+    on_count: Option<fn(&mut W, u64) -> Result<()>>,
+    // truncate the sink back to a length, to roll back a failed `try_append`
+    on_truncate: Option<fn(&mut W, u64) -> std::io::Result<()>>,
+    // last chunk appended with `append_dedup`, along with the position it ends at
+    last: Option<(u64, Vec<u8>)>,
     phantom: PhantomData<Format>,
 }
 
-impl<Format: SeqDataFormat> SeqDataWriter<Format> {
-    /// Create a new SeqData File at the location specified
+/// Size of the writes coalescing the chunks of `SeqDataWriter::append_repeated`
+pub const REPEAT_WRITE_SIZE: usize = 64 * 1024;
+
+/// Maximum size of the chunks remembered by `SeqDataWriter::append_dedup`
+pub const DEDUP_MAX_LEN: usize = 64 * 1024;
+
+/// Size of the readahead of `SeqDataReader::with_prefetch`
+pub const PREFETCH_SIZE: u64 = 1024 * 1024;
+
+/// Writer for a new SeqData
+pub type SeqDataWriter<Format> = SeqDataStreamWriter<File, Format>;
+
+impl<W: Write, Format: SeqDataFormat> SeqDataStreamWriter<W, Format> {
+    /// Create a new SeqData writing to the sink specified
     ///
-    /// If the file already exists, this call will fail
+    /// The magic and header are written immediately to the sink
     ///
     /// The header need to fits the size of Format::HEADER_SIZE
-    pub fn create<P: AsRef<Path>>(path: P, header: &[u8]) -> std::io::Result<Self> {
-        if Format::HEADER_SIZE != header.len() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "header has invalid size, expecting {} but got {}",
-                    Format::HEADER_SIZE,
-                    header.len()
-                ),
-            ));
-        }
+    pub fn new(mut sink: W, header: &[u8]) -> Result<Self> {
+        check_header_size::<Format>(header)?;
+        validate_header::<Format>(header)?;
+        write_magic_and_header::<Format, _>(&mut sink, header)?;
+        Ok(Self::from_parts(sink, 0))
+    }
 
-        let mut file = OpenOptions::new()
-            .read(false)
-            .write(true)
-            .create_new(true)
-            .append(true)
-            .open(path)?;
-        file.write_all(&Format::MAGIC)?;
-        file.write_all(header)?;
-        Ok(SeqDataWriter {
-            file,
+    fn from_parts(file: W, pos: u64) -> Self {
+        SeqDataStreamWriter {
+            // without buffering, everything is written through
+            file: BufWriter::with_capacity(0, file),
+            pos,
+            size_limit: None,
+            on_close: None,
+            count: 0,
+            on_count: None,
+            on_truncate: None,
+            last: None,
             phantom: PhantomData,
-        })
+        }
     }
 
-    /// Open a SeqData File at the location specified
+    /// Offset at which the next chunk will be appended
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Buffer the appended chunks in memory, writing them out once `capacity` bytes
+    /// are pending or on `flush`
     ///
-    /// If the file already exists, this call will fail
+    /// This coalesces many small appends into fewer writes. Offsets returned by the
+    /// writer account for the buffered chunks, but they are not visible to readers
+    /// of the file until flushed. The buffer is also flushed when the writer is dropped,
+    /// ignoring any error, so `flush` should be called to handle them.
+    pub fn with_buffering(mut self, capacity: usize) -> Result<Self> {
+        self.file.flush()?;
+        let file = self.file.into_inner().map_err(|e| e.into_error())?;
+        self.file = BufWriter::with_capacity(capacity, file);
+        Ok(self)
+    }
+
+    /// Write out any buffered chunk and flush the underlying sink
     ///
-    /// The header need to fits the size of Format::HEADER_SIZE
-    pub fn open<P: AsRef<Path>>(path: P, header: &[u8]) -> std::io::Result<(Self, Vec<u8>)> {
-        if Format::HEADER_SIZE != header.len() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "header has invalid size, expecting {} but got {}",
-                    Format::HEADER_SIZE,
-                    header.len()
-                ),
-            ));
+    /// For formats with `CHUNK_COUNT` set, this also updates the count of chunks in
+    /// the file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        if let Some(on_count) = self.on_count {
+            on_count(self.file.get_mut(), self.count)?;
         }
+        Ok(())
+    }
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(false)
-            .append(true)
-            .open(path)?;
+    /// Return a reference to the underlying sink
+    pub fn get_ref(&self) -> &W {
+        self.file.get_ref()
+    }
 
-        file.seek(std::io::SeekFrom::Start(0))?;
-        let header = read_magic_and_header(PhantomData::<Format>, &mut file)?;
-        file.seek(std::io::SeekFrom::End(0))?;
+    /// Write out any buffered chunk and return the underlying sink
+    pub fn into_inner(self) -> Result<W> {
+        Ok(self.file.into_inner().map_err(|e| e.into_error())?)
+    }
 
-        Ok((
-            SeqDataWriter {
-                file,
-                phantom: PhantomData,
-            },
-            header,
-        ))
+    /// Finish writing, writing the trailer magic if the format has one, and return the
+    /// underlying sink
+    ///
+    /// For formats with `TRAILER_MAGIC` set, a file not closed is not readable
+    pub fn close(mut self) -> Result<W> {
+        self.file.write_all(Format::TRAILER_MAGIC)?;
+        let (on_close, on_count, count) = (self.on_close, self.on_count, self.count);
+        let mut file = self.into_inner()?;
+        if let Some(on_count) = on_count {
+            on_count(&mut file, count)?;
+        }
+        if let Some(on_close) = on_close {
+            on_close(&mut file)?;
+        }
+        Ok(file)
+    }
+
+    /// Limit the size of the file, in bytes including magic and header
+    ///
+    /// Appending a chunk that would make the file grow past this limit fails,
+    /// and leave the file untouched.
+    pub fn with_size_limit(mut self, limit: u64) -> Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Return the current size of the file, in bytes including magic and header
+    pub fn current_len(&self) -> u64 {
+        data_start::<Format>() + self.pos
+    }
+
+    /// Return the length of the data written out to the sink, that readers opening the
+    /// file can see
+    ///
+    /// This is `position` minus the chunks still pending in the buffer set with
+    /// `with_buffering`. This doesn't account for the trailer magic written by `close`.
+    pub fn flushed_len(&self) -> u64 {
+        self.pos - self.file.buffer().len() as u64
+    }
+
+    /// Estimate how many chunks of `avg_chunk_size` bytes can still be appended
+    /// before reaching the size limit, or None if there is no limit set
+    pub fn estimate_remaining_chunks(&self, avg_chunk_size: u64) -> Option<u64> {
+        let limit = self.size_limit?;
+        let remaining = limit.saturating_sub(self.current_len());
+        Some(remaining / chunk_size::<Format>(avg_chunk_size))
+    }
+
+    // `grow` is the size of the chunks to append, framing included
+    fn check_size_limit(&self, grow: u64) -> Result<()> {
+        if let Some(limit) = self.size_limit {
+            let new_len = self.current_len() + grow;
+            if new_len > limit {
+                return Err(SeqDataError::SizeLimitExceeded {
+                    grow,
+                    len: new_len,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the manifest of this file
+    ///
+    /// This is only valid for formats with `MANIFEST` set, and need to be called
+    /// exactly once, before appending any chunk.
+    pub fn write_manifest(&mut self, manifest: &[u8]) -> Result<()> {
+        if !Format::MANIFEST {
+            return Err(SeqDataError::Unsupported("format does not have a manifest"));
+        }
+        if self.pos != 0 {
+            return Err(SeqDataError::ManifestAlreadyWritten);
+        }
+        self.check_size_limit(chunk_size::<Format>(manifest.len() as u64))?;
+        let meta = vec![0; Format::CHUNK_META_SIZE];
+        write_chunk::<Format, _>(&mut self.file, &meta, manifest, false)?;
+        self.pos += chunk_size::<Format>(manifest.len() as u64);
+        Ok(())
+    }
+
+    fn check_append(&self, grow: u64) -> Result<()> {
+        if Format::MANIFEST && self.pos == 0 {
+            return Err(SeqDataError::ManifestMissing);
+        }
+        self.check_size_limit(grow)
     }
 
     /// Append a new data chunk to this file
-    pub fn append(&mut self, data: &[u8]) -> std::io::Result<()> {
-        write_chunk(&mut self.file, data)
+    ///
+    /// The chunk may still be buffered, in this writer or by the OS, when this returns:
+    /// it is written out by `flush`, and only durable after `SeqDataWriter::sync`.
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, `append_with_meta` need to be used instead
+    pub fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.append_with_meta(&[], data)
     }
-}
 
-/// Reader for SeqData
-pub struct SeqDataReader<Format: SeqDataFormat> {
-    buf_reader: BufReader<File>,
-    pos: u64,
-    len: u64,
-    phantom: PhantomData<Format>,
-}
+    /// Append a new data chunk along with its metadata to this file
+    ///
+    /// The metadata need to fits the size of Format::CHUNK_META_SIZE
+    pub fn append_with_meta(&mut self, meta: &[u8], data: &[u8]) -> Result<()> {
+        trace!(offset = self.pos, len = data.len(), "append");
+        let size = chunk_size::<Format>(data.len() as u64);
+        on_error(self.check_append(size), "append")?;
+        on_error(
+            write_chunk::<Format, _>(&mut self.file, meta, data, false),
+            "append",
+        )?;
+        self.pos += size;
+        self.count += 1;
+        Ok(())
+    }
 
-fn read_magic_and_header<Format: SeqDataFormat>(
-    _format: PhantomData<Format>,
-    file: &mut File,
-) -> std::io::Result<Vec<u8>> {
-    // try to read the magic
-    const MAGIC_READ_BUF_SIZE: usize = 16;
-    let mut magic_read_buf = [0u8; MAGIC_READ_BUF_SIZE];
-    let mut magic_slice = Format::MAGIC;
-    while !magic_slice.is_empty() {
-        let sz = Format::MAGIC.len().min(MAGIC_READ_BUF_SIZE);
-        let rd = file.read(&mut magic_read_buf[0..sz])?;
-        if rd == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::UnexpectedEof,
-                "unexpected EOF in magic reading",
+    /// Append a record of any size to this file, split over as many chunks as needed
+    ///
+    /// This is only valid for formats with `CONTINUATION` set. Every chunk but the last
+    /// one has the continuation flag set, and `SeqDataReader::next_record` returns the
+    /// whole record. If the write fails midway, a partial record is left in the file.
+    pub fn append_large(&mut self, data: &[u8]) -> Result<()> {
+        if !Format::CONTINUATION {
+            return Err(SeqDataError::Unsupported(
+                "format does not have continuation flag",
             ));
         }
-        if magic_slice[0..rd] != magic_read_buf[0..rd] {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "magic do not match expected value",
-            ));
+        let max = max_chunk_len::<Format>();
+        let pieces = data.len().div_ceil(max).max(1);
+        let size = chunk_size::<Format>(0) * pieces as u64 + data.len() as u64;
+        trace!(offset = self.pos, len = data.len(), pieces, "append large");
+        on_error(self.check_append(size), "append")?;
+
+        let meta = vec![0; Format::CHUNK_META_SIZE];
+        for i in 0..pieces {
+            let piece = &data[i * max..data.len().min((i + 1) * max)];
+            let more = i + 1 < pieces;
+            on_error(
+                write_chunk::<Format, _>(&mut self.file, &meta, piece, more),
+                "append",
+            )?;
+            self.pos += chunk_size::<Format>(piece.len() as u64);
+            self.count += 1;
         }
-        magic_slice = &magic_slice[rd..];
+        Ok(())
     }
 
-    let mut header = vec![0u8; Format::HEADER_SIZE];
-    file.read_exact(&mut header)?;
-    Ok(header)
-}
+    /// Append a new data chunk to this file, and call `on_written` with its offset
+    ///
+    /// The callback is only called once the chunk has been successfully written,
+    /// but before any implicit sync of the file, which is useful to keep an
+    /// external index up to date with the file.
+    pub fn append_with<F: FnMut(u64)>(&mut self, data: &[u8], mut on_written: F) -> Result<()> {
+        let offset = self.pos;
+        self.append(data)?;
+        on_written(offset);
+        Ok(())
+    }
 
-impl<Format: SeqDataFormat> SeqDataReader<Format> {
-    /// Open a SeqData for reading
-    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<(Self, Vec<u8>)> {
-        let mut file = File::open(path)?;
+    /// Append each element of `chunks` as its own data chunk, returning their offsets
+    ///
+    /// This is equivalent to calling `append` in a loop
+    pub fn append_slice_iter(&mut self, chunks: &[&[u8]]) -> Result<Vec<u64>> {
+        let mut offsets = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            offsets.push(self.pos);
+            self.append(chunk)?;
+        }
+        Ok(offsets)
+    }
 
-        let phantom = PhantomData;
-        let len = get_file_length(phantom, &mut file)?;
-        let header = read_magic_and_header(phantom, &mut file)?;
+    /// Append each element of `chunks` as its own data chunk, with a single write
+    ///
+    /// The chunks read back are the same as calling `append` in a loop, but all the
+    /// chunks are encoded in one buffer before writing it, so an invalid chunk leaves
+    /// the file untouched instead of after the chunks preceding it.
+    pub fn append_many(&mut self, chunks: &[&[u8]]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let size = chunks
+            .iter()
+            .map(|chunk| chunk_size::<Format>(chunk.len() as u64))
+            .sum::<u64>();
+        trace!(offset = self.pos, n = chunks.len(), size, "append many");
+        on_error(self.check_append(size), "append")?;
 
-        let buf_reader = BufReader::with_capacity(1024 * 1024, file);
-        Ok((
-            SeqDataReader {
-                buf_reader,
-                pos: 0,
-                len,
-                phantom,
-            },
-            header,
-        ))
+        let mut batch = Vec::with_capacity(size as usize);
+        for chunk in chunks {
+            on_error(
+                write_chunk::<Format, _>(&mut batch, &[], chunk, false),
+                "append",
+            )?;
+        }
+        on_error(self.file.write_all(&batch), "append")?;
+        self.pos += size;
+        self.count += chunks.len() as u64;
+        Ok(())
     }
 
-    pub fn len(&self) -> u64 {
-        self.len
+    /// Append a new data chunk, unless it is identical to the chunk appended just before
+    ///
+    /// Return the offset of the chunk, or None if the write was skipped. This only
+    /// deduplicates immediate repeats of a chunk appended with `append_dedup`, and not
+    /// arbitrary duplicates: any other chunk appended in between resets the comparison.
+    /// Chunks bigger than `DEDUP_MAX_LEN` are not remembered, so they are always written.
+    pub fn append_dedup(&mut self, data: &[u8]) -> Result<Option<u64>> {
+        if let Some((end, last)) = &self.last {
+            if *end == self.pos && last == data {
+                trace!(offset = self.pos, len = data.len(), "append deduplicated");
+                return Ok(None);
+            }
+        }
+        let offset = self.pos;
+        self.append(data)?;
+        if data.len() <= DEDUP_MAX_LEN {
+            let (end, last) = self.last.get_or_insert_with(|| (0, Vec::new()));
+            *end = self.pos;
+            last.clear();
+            last.extend_from_slice(data);
+        } else {
+            self.last = None;
+        }
+        Ok(Some(offset))
     }
 
-    pub fn position(&self) -> u64 {
-        self.pos
+    /// Append `n` chunks of the same data, returning their offsets
+    ///
+    /// The chunk is framed once, and the copies are coalesced into writes of about
+    /// `REPEAT_WRITE_SIZE` bytes. If a write fails midway, some of the chunks are left
+    /// in the file.
+    pub fn append_repeated(&mut self, data: &[u8], n: usize) -> Result<Vec<u64>> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        trace!(offset = self.pos, len = data.len(), n, "append repeated");
+        let size = chunk_size::<Format>(data.len() as u64);
+        on_error(self.check_append(size.saturating_mul(n as u64)), "append")?;
+
+        let mut chunk = Vec::with_capacity(size as usize);
+        on_error(
+            write_chunk::<Format, _>(&mut chunk, &[], data, false),
+            "append",
+        )?;
+        let per_write = (REPEAT_WRITE_SIZE / chunk.len()).clamp(1, n);
+        let batch = chunk.repeat(per_write);
+
+        let mut offsets = Vec::with_capacity(n);
+        while offsets.len() < n {
+            let count = (n - offsets.len()).min(per_write);
+            on_error(self.file.write_all(&batch[..count * chunk.len()]), "append")?;
+            for _ in 0..count {
+                offsets.push(self.pos);
+                self.pos += size;
+                self.count += 1;
+            }
+        }
+        Ok(offsets)
     }
 
-    /// Return the next block along with the current offset if it exists, or None if
-    /// reached the end of file.
-    pub fn next(&mut self) -> Option<std::io::Result<(u64, Vec<u8>)>> {
-        match read_chunk(&mut self.buf_reader) {
-            None => None,
-            Some(Err(e)) => Some(Err(e)),
-            Some(Ok(buf)) => {
-                let current_pos = self.pos;
-                self.pos += size_of::<PrefixLength>() as u64 + buf.len() as u64;
-                Some(Ok((current_pos, buf)))
+    /// Append a sync marker chunk, returning its offset
+    ///
+    /// This is only valid for formats with `SYNC_MARKER` set. Writing a marker every
+    /// few chunks allows `SeqDataReader::resync_to_marker` to skip corrupted data.
+    pub fn append_sync_marker(&mut self) -> Result<u64> {
+        if Format::SYNC_MARKER.is_empty() {
+            return Err(SeqDataError::Unsupported("format has no sync marker"));
+        }
+        let offset = self.pos;
+        self.append_with_meta(&vec![0; Format::CHUNK_META_SIZE], Format::SYNC_MARKER)?;
+        Ok(offset)
+    }
+
+    /// Append `data` split into as many chunks of at most `max_chunk` bytes as needed,
+    /// returning their offsets
+    ///
+    /// Contrary to `append_large`, nothing in the file marks the chunks as parts of the
+    /// same data: readers need to know where to rejoin them, by concatenation. Empty data
+    /// is written as a single empty chunk. If a write fails midway, some of the chunks
+    /// are left in the file.
+    pub fn append_split(&mut self, data: &[u8], max_chunk: usize) -> Result<Vec<u64>> {
+        if max_chunk == 0 {
+            return Err(SeqDataError::InvalidInput(
+                "maximum chunk size need to be non zero".into(),
+            ));
+        }
+        let max_chunk = max_chunk.min(max_chunk_len::<Format>());
+        let pieces = data.len().div_ceil(max_chunk).max(1);
+        let size = data.len() as u64 + pieces as u64 * chunk_size::<Format>(0);
+        on_error(self.check_append(size), "append")?;
+
+        let mut offsets = Vec::with_capacity(pieces);
+        for i in 0..pieces {
+            let piece = &data[i * max_chunk..data.len().min((i + 1) * max_chunk)];
+            offsets.push(self.pos);
+            self.append(piece)?;
+        }
+        Ok(offsets)
+    }
+
+    /// Set how to truncate the sink back to a length in bytes, including magic and
+    /// header, for `try_append` to roll back a chunk partially written
+    ///
+    /// This is set on the file based `SeqDataWriter`.
+    pub fn with_truncate(mut self, truncate: fn(&mut W, u64) -> std::io::Result<()>) -> Self {
+        self.on_truncate = Some(truncate);
+        self
+    }
+
+    /// Append a new data chunk, returning the offset of the chunk
+    ///
+    /// Contrary to `append`, if the write fails midway (e.g. the disk is full), the sink
+    /// is truncated back to its length before the call with the function set by
+    /// `with_truncate`, so that no partial chunk is left at the end of the file. The
+    /// error reports how many bytes had been written and whether the rollback succeeded,
+    /// which it can't without a truncate function once some bytes are written.
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, `try_append_with_meta` need to be used
+    /// instead
+    pub fn try_append(&mut self, data: &[u8]) -> Result<u64, PartialAppendError> {
+        self.try_append_with_meta(&[], data)
+    }
+
+    /// Same as `try_append` along with the chunk metadata
+    ///
+    /// The metadata need to fits the size of Format::CHUNK_META_SIZE
+    pub fn try_append_with_meta(
+        &mut self,
+        meta: &[u8],
+        data: &[u8],
+    ) -> Result<u64, PartialAppendError> {
+        let offset = self.pos;
+        let checked = check_chunk_meta::<Format>(meta)
+            .and_then(|()| self.check_append(chunk_size::<Format>(data.len() as u64)))
+            .and_then(|()| Ok(self.file.flush()?));
+        if let Err(error) = checked {
+            return Err(PartialAppendError {
+                offset,
+                written: 0,
+                rolled_back: true,
+                error: error.into(),
+            });
+        }
+        let file = self.file.get_mut();
+        let mut written = 0;
+        match write_chunk_counted::<Format, _>(file, meta, data, false, &mut written) {
+            Ok(()) => {
+                self.pos += written;
+                self.count += 1;
+                Ok(offset)
+            }
+            Err(error) => {
+                let end = data_start::<Format>() + offset;
+                let rolled_back = written == 0
+                    || self
+                        .on_truncate
+                        .is_some_and(|truncate| truncate(file, end).is_ok());
+                debug!(offset, written, rolled_back, error = %error, "partial append");
+                Err(PartialAppendError {
+                    offset,
+                    written,
+                    rolled_back,
+                    error: error.into(),
+                })
             }
         }
     }
-}
 
-/// Seq Data Reader with seek
-pub struct SeqDataReaderSeek<Format: SeqDataFormat> {
-    handle: File,
-    phantom: PhantomData<Format>,
-    start: u64,
-    len: u64,
+    /// Spawn a thread appending each message received on `rx` as a data chunk
+    ///
+    /// Once every sender is dropped, the writer is closed and the thread returns the
+    /// number of chunks appended. The thread stops on the first write error and
+    /// returns it, dropping the messages still in the channel.
+    pub fn spawn_consumer(
+        mut self,
+        rx: std::sync::mpsc::Receiver<Vec<u8>>,
+    ) -> std::thread::JoinHandle<Result<u64>>
+    where
+        W: Send + 'static,
+        Format: Send + 'static,
+    {
+        std::thread::spawn(move || {
+            let mut count = 0;
+            for data in rx {
+                self.append(&data)?;
+                count += 1;
+            }
+            self.close()?;
+            Ok(count)
+        })
+    }
 }
 
-impl<Format: SeqDataFormat> SeqDataReaderSeek<Format> {
-    /// Open a new Seq Data seeker
-    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<(Self, Vec<u8>)> {
-        let mut handle = File::open(path)?;
+impl<Format: SeqDataFormat> SeqDataWriter<Format> {
+    /// Create a new SeqData File at the location specified
+    ///
+    /// If the file already exists, this call will fail, unless the file is empty: a
+    /// creation interrupted before the magic and header were written leaves an empty
+    /// file behind, which is initialized again instead of wedging every retry.
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub fn create<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<Self> {
+        check_header_size::<Format>(header)?;
+        validate_header::<Format>(header)?;
 
-        let phantom = PhantomData;
-        let len = get_file_length(phantom, &mut handle)?;
-        let header = read_magic_and_header(phantom, &mut handle)?;
+        let path = path.as_ref();
+        debug!(path = %path.display(), "create");
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                on_error(reopen_empty(path, e), "create")?
+            }
+            r => on_error(r, "create")?,
+        };
+        // don't leave a file with a partial header behind
+        match on_error(
+            Self::new(file, header)
+                .map(Self::track_truncate)
+                .map(Self::track_count)
+                .and_then(Self::track_dirty),
+            "create",
+        ) {
+            Ok(writer) => Ok(writer),
+            Err(e) => {
+                let _ = std::fs::remove_file(path);
+                Err(e)
+            }
+        }
+    }
 
-        let start = handle.seek(std::io::SeekFrom::Current(0))?;
+    /// Open a SeqData File at the location specified
+    ///
+    /// If the file already exists, this call will fail
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub fn open<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<(Self, Vec<u8>)> {
+        check_header_size::<Format>(header)?;
+        Self::open_existing(path)
+    }
 
-        Ok((
-            Self {
-                handle,
-                phantom,
-                len,
-                start,
-            },
-            header,
-        ))
+    /// Open a SeqData File at the location specified, parsing its header with `parse`
+    pub fn open_with_header_parser<P, H, F>(path: P, parse: F) -> Result<(Self, H)>
+    where
+        P: AsRef<Path>,
+        F: Fn(&[u8]) -> std::io::Result<H>,
+    {
+        let (writer, header) = Self::open_existing(path)?;
+        Ok((writer, parse(&header)?))
     }
 
-    /// Return the next block along with the current offset if it exists, or None if
-    /// reached the end of file.
-    pub fn next(&mut self) -> std::io::Result<Vec<u8>> {
-        read_chunk(&mut self.handle).unwrap()
+    fn open_existing<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open for writing");
+        let file = on_error(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(false)
+                .open(path),
+            "open",
+        )?;
+        Self::from_file(file)
     }
 
-    /// Return the next block at the offset specified
+    /// Create a writer appending to an already opened SeqData File, returning its header
     ///
-    /// Note that if the position specified is not a valid boundary,
-    /// then arbitrary invalid stuff might be returns, or some Err
-    /// related to reading data
-    pub fn next_at(&mut self, pos: u64) -> std::io::Result<Vec<u8>> {
-        if pos >= self.len {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "trying to access data at {} but data length {}",
-                    pos, self.len
-                ),
+    /// The file need to be opened for reading and writing, which is checked upfront so that
+    /// a read only handle is reported here instead of by the first append.
+    pub fn from_file(mut file: File) -> Result<(Self, Vec<u8>)> {
+        // a zero sized write still fails on a handle not opened for writing
+        if let Err(e) = file.write(&[]) {
+            return Err(SeqDataError::Io(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("file is not writable: {}", e),
+            )));
+        }
+
+        file.seek(std::io::SeekFrom::Start(0))?;
+        let (header, count) = read_magic_header_and_count(PhantomData::<Format>, &mut file)?;
+        let mut end = file.seek(std::io::SeekFrom::End(0))?;
+        if padding_length::<Format>(&header) != 0 {
+            // the padding is dropped, and written again by close_padded if needed
+            end = strip_padding::<Format>(&header, end)?;
+            file.set_len(end)?;
+            set_padding_length::<Format>(&mut file, 0)?;
+            file.seek(std::io::SeekFrom::Start(end))?;
+        }
+        if !Format::TRAILER_MAGIC.is_empty() {
+            // the trailer magic is written again by close
+            check_trailer_magic::<Format, _>(&mut file, end)?;
+            end -= Format::TRAILER_MAGIC.len() as u64;
+            file.set_len(end)?;
+            file.seek(std::io::SeekFrom::Start(end))?;
+        }
+
+        let mut writer = Self::from_parts(file, end - data_start::<Format>());
+        writer.count = count.unwrap_or(0);
+        let writer = writer.track_truncate().track_count().track_dirty()?;
+        Ok((writer, header))
+    }
+
+    // truncate the file to roll back a failed `try_append`
+    fn track_truncate(self) -> Self {
+        self.with_truncate(|file, len| {
+            file.set_len(len)?;
+            file.seek(std::io::SeekFrom::Start(len))?;
+            Ok(())
+        })
+    }
+
+    // update the count of chunks on flush and close, for formats with `CHUNK_COUNT` set
+    fn track_count(mut self) -> Self {
+        if Format::CHUNK_COUNT {
+            self.on_count = Some(set_chunk_count::<Format>);
+        }
+        self
+    }
+
+    // mark the file dirty until closed, for formats with `DIRTY_FLAG` set
+    fn track_dirty(mut self) -> Result<Self> {
+        if Format::DIRTY_FLAG.is_some() {
+            self.file.flush()?;
+            set_dirty_flag::<Format>(self.file.get_mut(), true)?;
+            self.on_close = Some(|file| set_dirty_flag::<Format>(file, false));
+        }
+        Ok(self)
+    }
+
+    /// Finish writing like `close`, then pad the file with zero bytes to a multiple
+    /// of `block` bytes, for writes to block devices or with `O_DIRECT`
+    ///
+    /// The padding is recorded in the header at `Format::PADDING_LENGTH`, which needs to
+    /// be set, and readers ignore it when opening the file. Reopening the file for
+    /// appending removes the padding.
+    pub fn close_padded(mut self, block: usize) -> Result<File> {
+        if Format::PADDING_LENGTH.is_none() {
+            return Err(SeqDataError::Unsupported(
+                "format has no padding length in its header",
+            ));
+        }
+        if block == 0 {
+            return Err(SeqDataError::InvalidInput(
+                "block size need to be non zero".into(),
             ));
         }
+        let end = self.current_len() + Format::TRAILER_MAGIC.len() as u64;
+        let padding = (block as u64 - end % block as u64) % block as u64;
+        let padding = u32::try_from(padding).map_err(|_| {
+            SeqDataError::InvalidInput("block size too big for the padding length".into())
+        })?;
 
-        let seek = self.start + pos;
-        self.handle.seek(std::io::SeekFrom::Start(seek))?;
-        self.next()
+        self.file.write_all(Format::TRAILER_MAGIC)?;
+        self.file.write_all(&vec![0; padding as usize])?;
+        let (on_close, on_count, count) = (self.on_close, self.on_count, self.count);
+        let mut file = self.into_inner()?;
+        set_padding_length::<Format>(&mut file, padding)?;
+        if let Some(on_count) = on_count {
+            on_count(&mut file, count)?;
+        }
+        if let Some(on_close) = on_close {
+            on_close(&mut file)?;
+        }
+        Ok(file)
     }
-}
 
-type PrefixLength = u32;
+    /// Open the SeqData File at the location specified, creating it if it doesn't exist
+    ///
+    /// When the file is created (or exists but is empty), the magic and `header` are
+    /// written to it, otherwise the existing magic and header are validated.
+    /// In both cases, the header of the file is returned.
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub fn open_append<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<(Self, Vec<u8>)> {
+        check_header_size::<Format>(header)?;
 
-fn read_chunk<R: Read>(file: &mut R) -> Option<std::io::Result<Vec<u8>>> {
-    let mut lenbuf = [0; size_of::<PrefixLength>()];
-    // try to read the length, if the length return a none, we just expect
-    // having reached the end of the stream then
-    match optional_read_exact(file, &mut lenbuf) {
-        None => None,
-        Some(Err(e)) => Some(Err(e)),
-        Some(Ok(())) => {
-            let len = PrefixLength::from_le_bytes(lenbuf);
+        debug!(path = %path.as_ref().display(), "open for appending");
+        let mut file = on_error(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path),
+            "open",
+        )?;
+
+        let end = file.seek(std::io::SeekFrom::End(0))?;
+        if end == 0 {
+            validate_header::<Format>(header)?;
+            write_magic_and_header::<Format, _>(&mut file, header)?;
+            let writer = Self::from_parts(file, 0)
+                .track_truncate()
+                .track_count()
+                .track_dirty()?;
+            return Ok((writer, header.to_vec()));
+        }
+        Self::from_file(file)
+    }
 
-            // create a buffer of the prefix length 'len' and read all data
-            let mut out = vec![0; len as usize];
-            match file.read_exact(&mut out) {
-                Err(e) => Some(Err(e)),
-                Ok(()) => Some(Ok(out)),
+    /// Open a SeqData File at the location specified for appending, after removing the
+    /// chunk cut short at its end if any, as left by a crash during an append
+    ///
+    /// The chunks are read from the start, and the file is truncated after the last
+    /// whole chunk when reading stops on a chunk cut short by the end of file. Any other
+    /// error is returned, leaving the file untouched. Return the header of the file and
+    /// how many bytes were removed.
+    ///
+    /// This is not supported for formats with `TRAILER_MAGIC` set.
+    pub fn open_recover<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>, u64)> {
+        if !Format::TRAILER_MAGIC.is_empty() {
+            return Err(SeqDataError::Unsupported(
+                "recovering a file with a trailer magic is not supported",
+            ));
+        }
+        let path = path.as_ref();
+        let (mut reader, _) = SeqDataReader::<Format>::open(path)?;
+        let mut end = reader.position();
+        let mut count = 0;
+        let mut buf = Vec::new();
+        while let Some(r) = reader.next_into(&mut buf) {
+            match r {
+                Ok(offset) => {
+                    end =
+                        offset + chunk_size::<Format>((buf.len() - Format::CHUNK_META_SIZE) as u64);
+                    count += 1;
+                }
+                Err(e) => match e {
+                    SeqDataError::TruncatedChunk { .. } => break,
+                    _ => return Err(e),
+                },
             }
         }
+
+        let removed = reader.len() - end;
+        if removed > 0 {
+            debug!(path = %path.display(), offset = end, removed, "recover");
+            truncate_at(path, data_start::<Format>() + end)?;
+        }
+        let (mut writer, header) = Self::open_existing(path)?;
+        writer.count = count;
+        Ok((writer, header, removed))
     }
-}
 
-fn write_chunk(file: &mut File, data: &[u8]) -> std::io::Result<()> {
-    let max = PrefixLength::MAX as usize;
-    assert!(data.len() <= max);
-    let len: u32 = data.len() as PrefixLength;
-    let header = len.to_le_bytes();
-    file.write_all(&header)?;
-    file.write_all(data)?;
-    Ok(())
-}
+    /// Overwrite the payload of the chunk at the offset specified
+    ///
+    /// `pos` must be the offset of a chunk, as given by `position` before appending it
+    /// or by a reader. The existing chunk must have exactly the same length as `data`
+    /// and end within the data, otherwise an error is returned and the file is left
+    /// untouched. These checks can't tell an offset in the middle of a chunk whose bytes
+    /// happen to decode as the right length, which then corrupts the file.
+    pub fn overwrite_at(&mut self, pos: u64, data: &[u8]) -> Result<()> {
+        if pos >= self.pos {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: pos,
+                len: self.pos,
+            });
+        }
 
-fn get_file_length<Format: SeqDataFormat>(
-    _phantom: PhantomData<Format>,
-    file: &mut File,
-) -> std::io::Result<u64> {
-    let meta = file.metadata()?;
-    let total_len = meta.len();
+        self.file.flush()?;
+        let file = self.file.get_mut();
+        let start = data_start::<Format>();
+        file.seek(std::io::SeekFrom::Start(start + pos))?;
+        let result = overwrite_chunk::<Format>(file, data, self.pos - pos);
+        file.seek(std::io::SeekFrom::Start(start + self.pos))?;
+        result
+    }
 
-    let minimum_size = Format::MAGIC.len() as u64 + Format::HEADER_SIZE as u64;
-    if total_len < minimum_size {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "file not contains enough bytes for magic and header",
-        ));
+    /// Flush the chunks appended so far and sync them to the disk
+    ///
+    /// The chunks are durable once this returns, e.g. before recording a checkpoint
+    /// referring to them elsewhere. Only the data is synced, with `File::sync_data`.
+    pub fn sync(&mut self) -> Result<()> {
+        self.flush()?;
+        Ok(on_error(self.file.get_ref().sync_data(), "sync")?)
+    }
+
+    /// Append a new data chunk to this file and sync it to the disk, returning the offset
+    /// of the chunk
+    ///
+    /// The chunk is durable once this returns, which is what a write-ahead log needs.
+    /// This costs a `sync_data` for every chunk, which is typically milliseconds on a
+    /// disk and can bound the throughput to a few hundred chunks per second.
+    pub fn append_durable(&mut self, data: &[u8]) -> Result<u64> {
+        let offset = self.pos;
+        self.append(data)?;
+        self.file.flush()?;
+        on_error(self.file.get_ref().sync_data(), "sync")?;
+        Ok(offset)
+    }
+
+    /// Append a new data chunk to this file while holding an exclusive lock on it,
+    /// returning the offset of the chunk
+    ///
+    /// Readers opened with `SeqDataReader::open_locked` hold a shared lock while looking
+    /// up the length of the file, so they see either the whole chunk or none of it. This
+    /// waits for such a reader to release the lock. The locks are advisory, and only
+    /// coordinate with the users of these methods.
+    pub fn append_coordinated(&mut self, data: &[u8]) -> Result<u64> {
+        let offset = self.pos;
+        self.file.flush()?;
+        on_error(self.file.get_ref().lock(), "lock")?;
+        let appended = self.append(data).and_then(|()| Ok(self.file.flush()?));
+        let unlocked = self.file.get_ref().unlock();
+        appended?;
+        unlocked?;
+        Ok(offset)
+    }
+
+    /// Append a new data chunk to this file, and read it back from the file
+    ///
+    /// Return the offset of the chunk and the data read back, which allow to check that
+    /// the chunk landed as expected. This costs an extra flush of the buffered data and
+    /// a read of the chunk, which is likely served from the OS page cache and not the disk
+    /// unless the file has been synced in between.
+    pub fn append_and_read(&mut self, data: &[u8]) -> Result<(u64, Vec<u8>)> {
+        let offset = self.pos;
+        self.append(data)?;
+
+        self.file.flush()?;
+        let file = self.file.get_mut();
+        let start = data_start::<Format>();
+        file.seek(std::io::SeekFrom::Start(start + offset))?;
+        let result = read_chunk_data::<Format, _>(file, self.pos - offset).unwrap_or_else(|| {
+            Err(SeqDataError::TruncatedChunk {
+                len: None,
+                available: 0,
+            })
+        });
+        file.seek(std::io::SeekFrom::Start(start + self.pos))?;
+        Ok((offset, result?))
+    }
+
+    /// Start appending a chunk of `len` bytes whose data is streamed through the
+    /// returned sink, e.g. from a source too big to be buffered
+    ///
+    /// The length prefix is written immediately, so the sink need to be given exactly
+    /// `len` bytes before calling `ChunkSink::finish`. Otherwise `finish` fails and the
+    /// file is truncated back to its length before this call, as it is if the sink is
+    /// dropped without `finish`. For formats with `CHUNK_META_SIZE` set, the metadata
+    /// is zeroed. This is not supported for formats with `CHECKSUM` set.
+    pub fn append_streaming(&mut self, len: u64) -> Result<ChunkSink<'_, Format>> {
+        if Format::CHECKSUM.is_some() {
+            return Err(SeqDataError::Unsupported(
+                "streaming a chunk is not supported with a checksum",
+            ));
+        }
+        if len > max_chunk_len::<Format>() as u64 {
+            return Err(SeqDataError::ChunkTooLarge {
+                len,
+                max: max_chunk_len::<Format>() as u64,
+            });
+        }
+        on_error(self.check_append(chunk_size::<Format>(len)), "append")?;
+        trace!(offset = self.pos, len, "append streaming");
+        let prefix = encode_prefix::<Format>(len as usize, false);
+        // a failure writing the prefix is rolled back when the sink is dropped
+        let sink = ChunkSink {
+            offset: self.pos,
+            len,
+            written: 0,
+            overflow: false,
+            finished: false,
+            writer: self,
+        };
+        sink.writer.file.write_all(&prefix)?;
+        sink.writer
+            .file
+            .write_all(&vec![0; Format::CHUNK_META_SIZE])?;
+        Ok(sink)
+    }
+}
+
+/// Sink for the data of a chunk appended with `SeqDataWriter::append_streaming`
+///
+/// Writing past the length of the chunk fails, and makes `finish` fail.
+pub struct ChunkSink<'a, Format: SeqDataFormat> {
+    writer: &'a mut SeqDataWriter<Format>,
+    offset: u64,
+    len: u64,
+    written: u64,
+    overflow: bool,
+    finished: bool,
+}
+
+impl<Format: SeqDataFormat> ChunkSink<'_, Format> {
+    /// Number of bytes of the chunk data still expected
+    pub fn remaining(&self) -> u64 {
+        self.len - self.written
+    }
+
+    /// Finish the chunk, returning its offset
+    ///
+    /// If the sink wasn't given exactly the length of the chunk, this fails and the
+    /// file is truncated back to its length before the chunk.
+    pub fn finish(mut self) -> Result<u64> {
+        self.finished = true;
+        if self.overflow || self.written != self.len {
+            let message = if self.overflow {
+                format!("more than the {} bytes of the chunk written", self.len)
+            } else {
+                format!(
+                    "chunk of {} bytes but {} bytes written",
+                    self.len, self.written
+                )
+            };
+            let error = SeqDataError::InvalidInput(message);
+            self.rollback()?;
+            return on_error(Err(error), "append");
+        }
+        let prefix = encode_prefix::<Format>(self.len as usize, false);
+        if Format::TRAILING_LENGTH {
+            self.writer.file.write_all(&prefix)?;
+        }
+        self.writer.file.write_all(Format::SENTINEL)?;
+        self.writer.pos += chunk_size::<Format>(self.len);
+        self.writer.count += 1;
+        Ok(self.offset)
+    }
+
+    // truncate the file back to the start of the chunk
+    fn rollback(&mut self) -> Result<()> {
+        self.writer.file.flush()?;
+        let end = data_start::<Format>() + self.offset;
+        let file = self.writer.file.get_mut();
+        file.set_len(end)?;
+        file.seek(std::io::SeekFrom::Start(end))?;
+        Ok(())
+    }
+}
+
+impl<Format: SeqDataFormat> Write for ChunkSink<'_, Format> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() as u64 > self.remaining() {
+            self.overflow = true;
+            return Err(SeqDataError::InvalidInput(format!(
+                "writing {} bytes past the end of the chunk of {} bytes",
+                buf.len() as u64 - self.remaining(),
+                self.len
+            ))
+            .into());
+        }
+        let n = self.writer.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.file.flush()
+    }
+}
+
+impl<Format: SeqDataFormat> Drop for ChunkSink<'_, Format> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // an error leaves the partial chunk at the end of the file
+            let _ = self.rollback();
+        }
+    }
+}
+
+// hint the OS that `len` bytes at an offset of the reader are going to be read
+type Readahead<R> = fn(&R, u64, u64) -> Result<()>;
+
+/// Reader for SeqData
+///
+/// The data is read from `R`, a `File` by default
+pub struct SeqDataReader<Format: SeqDataFormat, R: Read + Seek = File> {
+    buf_reader: BufReader<R>,
+    // bytes of the buffer lent by `next_cow`, to consume before the next read
+    lent: usize,
+    pos: u64,
+    len: u64,
+    // readahead of the data following the chunks, enabled with `with_prefetch`
+    prefetch: Option<Readahead<R>>,
+    // end of the data covered by the last readahead
+    prefetched: u64,
+    manifest: Option<Vec<u8>>,
+    clean: bool,
+    declared_count: Option<u64>,
+    // stop at `len` instead of the end of file
+    bounded: bool,
+    partial: Option<PartialChunk>,
+    // time taken by `next`, when enabled with `with_timing`
+    timing: Option<TimingSummary>,
+    phantom: PhantomData<Format>,
+}
+
+// reader over a single block, returned by `SeqDataReader::chunk_reader`
+struct ChunkReader<'a, Format: SeqDataFormat, R: Read + Seek> {
+    reader: &'a mut SeqDataReader<Format, R>,
+}
+
+impl<Format: SeqDataFormat, R: Read + Seek> Read for ChunkReader<'_, Format, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.reader.read_in_chunk(buf)?)
+    }
+}
+
+impl<Format: SeqDataFormat, R: Read + Seek> Drop for ChunkReader<'_, Format, R> {
+    fn drop(&mut self) {
+        // an error is reported again by the next read on the reader
+        let _ = self.reader.finish_chunk();
+    }
+}
+
+// block being read piecewise with `read_in_chunk`, whose length prefix has been read
+#[derive(Clone, Copy)]
+struct PartialChunk {
+    // length of the chunk data, as in the length prefix
+    len: u64,
+    // bytes of the block (metadata and data) not read yet
+    remaining: u64,
+}
+
+fn read_magic_and_header<Format: SeqDataFormat, R: Read>(
+    format: PhantomData<Format>,
+    file: &mut R,
+) -> Result<Vec<u8>> {
+    read_magic_header_and_count(format, file).map(|(header, _)| header)
+}
+
+/// Read the magic and header, and the count of chunks for formats with `CHUNK_COUNT` set,
+/// leaving `file` at the start of the data
+fn read_magic_header_and_count<Format: SeqDataFormat, R: Read>(
+    format: PhantomData<Format>,
+    file: &mut R,
+) -> Result<(Vec<u8>, Option<u64>)> {
+    on_error(read_magic_and_header_raw(format, file), "header")
+}
+
+fn read_magic_and_header_raw<Format: SeqDataFormat, R: Read>(
+    _format: PhantomData<Format>,
+    file: &mut R,
+) -> Result<(Vec<u8>, Option<u64>)> {
+    // try to read the magic
+    const MAGIC_READ_BUF_SIZE: usize = 16;
+    let mut magic_read_buf = [0u8; MAGIC_READ_BUF_SIZE];
+    let mut magic_slice = Format::MAGIC;
+    while !magic_slice.is_empty() {
+        let sz = magic_slice.len().min(MAGIC_READ_BUF_SIZE);
+        let rd = read_up_to(file, &mut magic_read_buf[0..sz])?;
+        if magic_slice[0..rd] != magic_read_buf[0..rd] {
+            return Err(SeqDataError::MagicMismatch);
+        }
+        if rd < sz {
+            let len = Format::MAGIC.len() - magic_slice.len() + rd;
+            return Err(file_too_short::<Format>(len as u64));
+        }
+        magic_slice = &magic_slice[rd..];
+    }
+
+    let mut header = vec![0u8; Format::HEADER_SIZE];
+    let rd = read_up_to(file, &mut header)?;
+    if rd < header.len() {
+        return Err(file_too_short::<Format>((Format::MAGIC.len() + rd) as u64));
+    }
+    validate_header::<Format>(&header)?;
+
+    let count = if Format::CHUNK_COUNT {
+        let mut count = [0; CHUNK_COUNT_SIZE];
+        let rd = read_up_to(file, &mut count)?;
+        if rd < count.len() {
+            let len = Format::MAGIC.len() + Format::HEADER_SIZE + rd;
+            return Err(file_too_short::<Format>(len as u64));
+        }
+        Some(u64::from_le_bytes(count))
+    } else {
+        None
+    };
+    Ok((header, count))
+}
+
+impl<Format: SeqDataFormat> SeqDataReader<Format> {
+    /// Open a SeqData for reading
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open for reading");
+        let file = on_error(File::open(path), "open")?;
+        Self::from_reader(file)
+    }
+
+    /// Enable readahead of the data following the chunks returned by `next`
+    ///
+    /// The OS is asked to read the next `PREFETCH_SIZE` bytes of data in the background,
+    /// again each time less than half of them are left ahead, so that reading the
+    /// following chunks doesn't wait on the disk. This only helps when the caller does
+    /// meaningful work between calls to `next`, and does nothing on platforms without
+    /// support for it.
+    pub fn with_prefetch(mut self) -> Self {
+        self.prefetch = Some(readahead);
+        self
+    }
+
+    /// Open a SeqData for reading, or return None if the file is missing or empty
+    ///
+    /// A file not written yet is treated as having no chunk instead of as an error,
+    /// and has no magic or header to validate. This is only relaxed for a missing or
+    /// zero byte file: a file too short for its magic and header is still an error.
+    pub fn open_allow_empty<P: AsRef<Path>>(path: P) -> Result<Option<(Self, Vec<u8>)>> {
+        debug!(path = %path.as_ref().display(), "open for reading, allowing empty");
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return on_error(Err(e.into()), "open"),
+        };
+        if file.metadata()?.len() == 0 {
+            return Ok(None);
+        }
+        Self::from_reader(file).map(Some)
+    }
+
+    /// Open a SeqData for reading, holding a shared lock on the file while looking up
+    /// its length
+    ///
+    /// A chunk appended with `SeqDataWriter::append_coordinated` is either entirely part
+    /// of the data of this reader, or not at all: the reader stops at the length of the
+    /// data when opened, ignoring anything appended afterwards.
+    pub fn open_locked<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open locked for reading");
+        let mut file = on_error(File::open(path), "open")?;
+        on_error(file.lock_shared(), "lock")?;
+        let len = get_file_length(PhantomData::<Format>, &mut file);
+        file.unlock()?;
+        let (mut reader, header) = Self::from_reader_len(file, len?)?;
+        reader.bounded = true;
+        Ok((reader, header))
+    }
+
+    /// Open a SeqData for reading, trusting `total_len` as the size of the file in bytes
+    ///
+    /// This saves looking up the size of the file when it is already known, e.g. when
+    /// scanning a directory. The length is only checked against the minimum size of a
+    /// SeqData: an incorrect length makes the reads fail or stop at the wrong place.
+    pub fn open_with_len<P: AsRef<Path>>(path: P, total_len: u64) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), total_len, "open for reading");
+        let mut file = on_error(File::open(path), "open")?;
+        let len = data_length(PhantomData::<Format>, &mut file, total_len)?;
+        Self::from_reader_len(file, len)
+    }
+
+    /// Open a SeqData for reading, parsing its header with `parse`
+    pub fn open_with_header_parser<P, H, F>(path: P, parse: F) -> Result<(Self, H)>
+    where
+        P: AsRef<Path>,
+        F: Fn(&[u8]) -> std::io::Result<H>,
+    {
+        let (reader, header) = Self::open(path)?;
+        Ok((reader, parse(&header)?))
+    }
+
+    /// Open a SeqData for reading, checking that its header is exactly `expected`
+    ///
+    /// This fails with `SeqDataError::HeaderMismatch` on a file written with another header,
+    /// e.g. with an incompatible configuration.
+    pub fn open_expect_header<P: AsRef<Path>>(path: P, expected: &[u8]) -> Result<Self> {
+        let (reader, header) = Self::open(path)?;
+        if header != expected {
+            return on_error(
+                Err(SeqDataError::HeaderMismatch {
+                    expected: expected.to_vec(),
+                    got: header,
+                }),
+                "header",
+            );
+        }
+        Ok(reader)
+    }
+
+    /// Read the header and the first chunk of the SeqData at the location specified,
+    /// or None for the chunk if the file has no chunk
+    ///
+    /// This doesn't construct a full reader and its buffer, and only read what is
+    /// needed from the file. For formats with `MANIFEST` set, the first chunk is the
+    /// manifest.
+    pub fn read_first<P: AsRef<Path>>(path: P) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+        debug!(path = %path.as_ref().display(), "read first chunk");
+        let mut file = on_error(File::open(path), "open")?;
+
+        let phantom = PhantomData::<Format>;
+        let len = get_file_length(phantom, &mut file)?;
+        let header = read_magic_and_header(phantom, &mut file)?;
+        let first = if len == 0 {
+            None
+        } else {
+            read_chunk_data::<Format, _>(&mut file, len).transpose()?
+        };
+        Ok((header, first))
+    }
+}
+
+impl<Format: SeqDataFormat> SeqDataReader<Format, Cursor<Vec<u8>>> {
+    /// Create a reader over a SeqData held in memory, returning the header
+    pub fn from_bytes(data: Vec<u8>) -> Result<(Self, Vec<u8>)> {
+        Self::from_reader(Cursor::new(data))
+    }
+}
+
+impl<Format: SeqDataFormat, R: Read + Seek> SeqDataReader<Format, R> {
+    /// Create a reader over a SeqData spanning the whole of `reader`, returning the header
+    pub fn from_reader(mut reader: R) -> Result<(Self, Vec<u8>)> {
+        let len = get_file_length(PhantomData::<Format>, &mut reader)?;
+        Self::from_reader_len(reader, len)
+    }
+
+    // `reader` is at the start of the file, and `len` is the length of its data
+    fn from_reader_len(mut reader: R, len: u64) -> Result<(Self, Vec<u8>)> {
+        let phantom = PhantomData;
+        let (header, declared_count) = read_magic_header_and_count(phantom, &mut reader)?;
+        debug!(len, "opened for reading");
+
+        let buf_reader = BufReader::with_capacity(1024 * 1024, reader);
+        let mut reader = SeqDataReader {
+            buf_reader,
+            lent: 0,
+            pos: 0,
+            len,
+            prefetch: None,
+            prefetched: 0,
+            manifest: None,
+            clean: Format::DIRTY_FLAG.is_none_or(|offset| header.get(offset) == Some(&0)),
+            declared_count,
+            bounded: data_followed::<Format>(),
+            partial: None,
+            timing: None,
+            phantom,
+        };
+        if Format::MANIFEST {
+            match reader.next() {
+                None => return Err(SeqDataError::ManifestMissing),
+                Some(Err(e)) => return Err(e),
+                Some(Ok((_, mut manifest))) => {
+                    reader.manifest = Some(manifest.split_off(Format::CHUNK_META_SIZE))
+                }
+            }
+        }
+        Ok((reader, header))
+    }
+
+    /// Return the manifest of this file, for formats with `MANIFEST` set
+    pub fn manifest(&self) -> Option<&[u8]> {
+        self.manifest.as_deref()
+    }
+
+    /// Whether the last writer of this file closed it properly
+    ///
+    /// This is always true for formats without `DIRTY_FLAG`
+    pub fn was_clean(&self) -> bool {
+        self.clean
+    }
+
+    /// Number of chunks recorded after the header, for formats with `CHUNK_COUNT` set
+    ///
+    /// This is read when opening the file, without scanning the chunks. The writer
+    /// only updates it on `flush` and `close`, so it can be behind the chunks in the file
+    /// if the writer didn't shut down properly.
+    pub fn declared_count(&self) -> Option<u64> {
+        self.declared_count
+    }
+
+    /// Record the time taken by each call to `next` returning a block, reading it and
+    /// allocating its buffer, for `timing_summary`
+    ///
+    /// Without this, `next` doesn't look at the clock at all.
+    pub fn with_timing(mut self) -> Self {
+        self.timing = Some(TimingSummary::default());
+        self
+    }
+
+    /// Return the time taken by the blocks returned by `next` so far, or None if
+    /// `with_timing` is not enabled
+    pub fn timing_summary(&self) -> Option<TimingSummary> {
+        self.timing
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Offset that the next chunk appended to this file would report
+    ///
+    /// This is the same as `len`, as the length of the data when the file was opened
+    /// is where a writer appends the next chunk.
+    pub fn append_position(&self) -> u64 {
+        self.len
+    }
+
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Move the reader to the chunk offset specified, so that the next call to `next`
+    /// returns the chunk at this offset
+    ///
+    /// The offset need to be a chunk boundary, or `len` to move at the end of the data.
+    /// Any chunk partially read with `start_chunk` is discarded.
+    pub fn seek_to_offset(&mut self, pos: u64) -> Result<()> {
+        if pos > self.len {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: pos,
+                len: self.len,
+            });
+        }
+        // seeking through the BufReader, and not the reader under it, discards the buffer
+        self.buf_reader
+            .seek(std::io::SeekFrom::Start(data_start::<Format>() + pos))?;
+        self.lent = 0;
+        self.partial = None;
+        self.pos = pos;
+        self.prefetched = 0;
+        Ok(())
+    }
+
+    /// Convert a chunk offset, as returned by this reader, to an offset in the file
+    pub fn to_physical(&self, logical: u64) -> u64 {
+        data_start::<Format>() + logical
+    }
+
+    /// Convert an offset in the file to a chunk offset, or None if it is before the data
+    pub fn to_logical(&self, physical: u64) -> Option<u64> {
+        physical.checked_sub(data_start::<Format>())
+    }
+
+    /// Return the next block along with the current offset if it exists, or None if
+    /// reached the end of file.
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, the block starts with the chunk metadata
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(u64, Vec<u8>)>> {
+        let start = self.timing.is_some().then(std::time::Instant::now);
+        let next = self
+            .next_flagged()
+            .map(|r| r.map(|(offset, buf, _)| (offset, buf)));
+        if let (Some(timing), Some(start), Some(Ok(_))) = (&mut self.timing, start, &next) {
+            timing.record(start.elapsed());
+        }
+        next
+    }
+
+    /// Return the next record along with its offset, or None if reached the end of file.
+    ///
+    /// For formats with `CONTINUATION` set, the chunks with the continuation flag set
+    /// are concatenated with the following ones into a single record, as written by
+    /// `SeqDataWriter::append_large`. Otherwise this is the same as `next`.
+    pub fn next_record(&mut self) -> Option<Result<(u64, Vec<u8>)>> {
+        let (offset, mut record, mut more) = match self.next_flagged()? {
+            Ok(r) => r,
+            Err(e) => return Some(Err(e)),
+        };
+        while more {
+            match self.next_flagged() {
+                None => return Some(Err(SeqDataError::TruncatedRecord)),
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok((_, data, m))) => {
+                    record.extend_from_slice(&data);
+                    more = m;
+                }
+            }
+        }
+        Some(Ok((offset, record)))
+    }
+
+    /// Same as `next` but read the block into `buf`, returning its offset, so that the
+    /// allocation of the buffer is reused across calls
+    ///
+    /// `buf` is cleared and resized to the block, and is left untouched when the end of
+    /// file is reached. Its content is unspecified after an error.
+    pub fn next_into(&mut self, buf: &mut Vec<u8>) -> Option<Result<u64>> {
+        self.next_flagged_into(buf)
+            .map(|r| r.map(|(offset, _)| offset))
+    }
+
+    fn next_flagged(&mut self) -> Option<Result<(u64, Vec<u8>, bool)>> {
+        let mut buf = Vec::new();
+        self.next_flagged_into(&mut buf)
+            .map(|r| r.map(|(offset, more)| (offset, buf, more)))
+    }
+
+    fn next_flagged_into(&mut self, buf: &mut Vec<u8>) -> Option<Result<(u64, bool)>> {
+        if let Err(e) = self.finish_chunk() {
+            return Some(Err(e));
+        }
+        if self.at_data_end() {
+            return None;
+        }
+        let available = self.len.saturating_sub(self.pos);
+        match read_chunk::<Format, _>(self.reader(), buf, available)
+            .map(|r| r.and_then(ChunkRead::complete))
+        {
+            None => None,
+            Some(Err(e)) => Some(on_error(Err(corrupted_at(e, self.pos)), "next")),
+            Some(Ok(more)) => {
+                let current_pos = self.pos;
+                self.pos += chunk_size::<Format>((buf.len() - Format::CHUNK_META_SIZE) as u64);
+                self.prefetch();
+                Some(Ok((current_pos, more)))
+            }
+        }
+    }
+
+    /// Same as `next` but borrow the block from the internal buffer when it is
+    /// entirely buffered, instead of allocating it
+    ///
+    /// The borrowed block stays valid until the next call on this reader.
+    pub fn next_cow(&mut self) -> Option<Result<(u64, Cow<'_, [u8]>)>> {
+        let start = self.timing.is_some().then(std::time::Instant::now);
+        if let Err(e) = self.finish_chunk() {
+            return Some(Err(e));
+        }
+        if self.at_data_end() {
+            return None;
+        }
+        if self.reader().buffer().is_empty() {
+            if let Err(e) = self.buf_reader.fill_buf() {
+                return Some(on_error(Err(e.into()), "next"));
+            }
+        }
+        let buffer = self.buf_reader.buffer();
+        let prefix_size = prefix_size::<Format>();
+        if buffer.len() >= prefix_size {
+            let raw = decode_prefix::<Format>(buffer);
+            let (len, _) = split_prefix::<Format>(raw);
+            // a chunk going past the data, e.g. into the trailer magic or the padding,
+            // is left to `next` to report
+            let end = chunk_end::<Format>(self.pos, len, self.len).ok();
+            if let Some(end) = end.filter(|end| end - self.pos <= buffer.len() as u64) {
+                let size = (end - self.pos) as usize;
+                let block_len = match check_trailer_slice::<Format>(&buffer[prefix_size..size]) {
+                    Ok(block_len) => block_len,
+                    Err(e) => return Some(on_error(Err(corrupted_at(e, self.pos)), "next")),
+                };
+                let current_pos = self.pos;
+                self.pos = end;
+                self.lent = size;
+                if let (Some(timing), Some(start)) = (&mut self.timing, start) {
+                    timing.record(start.elapsed());
+                }
+                let block = &self.buf_reader.buffer()[prefix_size..prefix_size + block_len];
+                return Some(Ok((current_pos, Cow::Borrowed(block))));
+            }
+        }
+        self.next()
+            .map(|r| r.map(|(offset, data)| (offset, Cow::Owned(data))))
+    }
+
+    /// Start reading the next block piecewise, returning its offset and its length,
+    /// or None if reached the end of file.
+    ///
+    /// Only the length prefix is read: the block (metadata included) is then read with
+    /// `read_in_chunk`. Any other read skips the rest of the block. The position stays
+    /// at the offset of the block until it has been entirely read.
+    pub fn start_chunk(&mut self) -> Option<Result<(u64, u64)>> {
+        if let Err(e) = self.finish_chunk() {
+            return Some(Err(e));
+        }
+        if self.at_data_end() {
+            return None;
+        }
+        match read_chunk_length::<Format, _>(self.reader()) {
+            None => None,
+            Some(Err(e)) => Some(on_error(Err(e), "next")),
+            Some(Ok(raw)) => {
+                let (len, _) = split_prefix::<Format>(raw);
+                if let Err(e) = chunk_end::<Format>(self.pos, len, self.len) {
+                    return Some(Err(e));
+                }
+                let remaining = Format::CHUNK_META_SIZE as u64 + len;
+                self.partial = Some(PartialChunk { len, remaining });
+                Some(Ok((self.pos, remaining)))
+            }
+        }
+    }
+
+    /// Read some bytes of the block started with `start_chunk` into `buf`, returning
+    /// how many were read
+    ///
+    /// 0 is returned when there is no block being read. Once the whole block has been
+    /// read, the next call checks the framing following it, moves to the next block and
+    /// returns 0. The checksum of formats with `CHECKSUM` set is not verified.
+    pub fn read_in_chunk(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let Some(PartialChunk { len, remaining }) = self.partial else {
+            return Ok(0);
+        };
+        if remaining == 0 {
+            let mut trailer = vec![0; trailer_size::<Format>()];
+            self.reader().read_exact(&mut trailer)?;
+            self.partial = None;
+            self.pos += chunk_size::<Format>(len);
+            check_trailer_framing::<Format>(&trailer[checksum_size::<Format>()..], len as usize)?;
+            return Ok(0);
+        }
+        let n = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.reader().read(&mut buf[..n])?;
+        if n == 0 && !buf.is_empty() {
+            return Err(SeqDataError::TruncatedChunk {
+                len: Some(len),
+                available: Format::CHUNK_META_SIZE as u64 + len - remaining,
+            });
+        }
+        self.partial = Some(PartialChunk {
+            len,
+            remaining: remaining - n as u64,
+        });
+        Ok(n)
+    }
+
+    /// Return the number of bytes left to read in the block started with `start_chunk`,
+    /// or None if there is no block being read
+    pub fn bytes_remaining_in_chunk(&self) -> Option<u64> {
+        self.partial.map(|partial| partial.remaining)
+    }
+
+    /// Return the offset of the next block and a reader over it, or None if reached
+    /// the end of file.
+    ///
+    /// This allows streaming a large block without holding it in memory, and is built
+    /// on `start_chunk` and `read_in_chunk`: the position moves to the next block once
+    /// the reader is entirely read or dropped, the rest of the block being skipped.
+    pub fn chunk_reader(&mut self) -> Option<Result<(u64, impl Read + '_)>> {
+        match self.start_chunk()? {
+            Ok((offset, _)) => Some(Ok((offset, ChunkReader { reader: self }))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    // skip the rest of the block being read piecewise, if any
+    fn finish_chunk(&mut self) -> Result<()> {
+        if let Some(PartialChunk { len, remaining }) = self.partial {
+            let skip = remaining + trailer_size::<Format>() as u64;
+            self.reader().seek_relative(skip as i64)?;
+            self.partial = None;
+            self.pos += chunk_size::<Format>(len);
+        }
+        Ok(())
+    }
+
+    // the underlying reader, once the buffer lent by `next_cow` has been consumed
+    // issue the next readahead when less than half of the previous one is left ahead
+    fn prefetch(&mut self) {
+        let Some(readahead) = self.prefetch else {
+            return;
+        };
+        if self.prefetched >= self.pos.saturating_add(PREFETCH_SIZE / 2).min(self.len) {
+            return;
+        }
+        let end = self.pos.saturating_add(PREFETCH_SIZE).min(self.len);
+        // this is only a hint, any error will be reported by the reads
+        let _ = readahead(
+            self.buf_reader.get_ref(),
+            data_start::<Format>() + self.pos,
+            end - self.pos,
+        );
+        self.prefetched = end;
+    }
+
+    fn reader(&mut self) -> &mut BufReader<R> {
+        self.buf_reader.consume(std::mem::take(&mut self.lent));
+        &mut self.buf_reader
+    }
+
+    /// Same as `next` but return the block in a shared buffer, that can be cheaply
+    /// cloned to hand the same block to multiple consumers
+    pub fn next_shared(&mut self) -> Option<Result<(u64, Arc<[u8]>)>> {
+        self.next()
+            .map(|r| r.map(|(offset, data)| (offset, Arc::from(data))))
+    }
+
+    /// Return the next block along with the current offset, its metadata and its data
+    /// separately, or None if reached the end of file.
+    pub fn next_with_meta(&mut self) -> Option<Result<MetaChunk>> {
+        self.next().map(|r| {
+            r.map(|(offset, mut meta)| {
+                let data = meta.split_off(Format::CHUNK_META_SIZE);
+                (offset, meta, data)
+            })
+        })
+    }
+
+    /// Return the next readable block along with its offset, skipping over unreadable ones
+    ///
+    /// When a block cannot be read, `on_skip` is called with its offset and the error,
+    /// and the reader tries to resynchronize on a following block. Each following offset
+    /// (or for formats with a `SENTINEL`, each offset following an occurrence of the
+    /// sentinel) is tried in turn, and accepted when the length read there fits in the
+    /// file, and the block after it also fits or ends exactly at the end of the data
+    /// (the trailing length and sentinel, if any, need to match too).
+    ///
+    /// This is only an heuristic: the data of a block can contain anything, so an offset
+    /// in the middle of a block can be taken for a valid block and garbage returned. In
+    /// particular, a zeroed region looks like a sequence of empty blocks. The scan also
+    /// does a few small reads per offset tried, so skipping a large corrupted region is slow.
+    ///
+    /// None is returned at the end of file, or when no valid block can be found anymore.
+    pub fn next_skipping_errors<F: FnMut(u64, SeqDataError)>(
+        &mut self,
+        mut on_skip: F,
+    ) -> Option<(u64, Vec<u8>)> {
+        loop {
+            match self.next()? {
+                Ok(r) => return Some(r),
+                Err(e) => {
+                    let offset = self.pos;
+                    on_skip(offset, e);
+                    match self.resync(offset + 1) {
+                        Ok(true) => {}
+                        Ok(false) => return None,
+                        Err(e) => {
+                            on_skip(offset, e);
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // position the reader at the first plausible block starting from `from`,
+    // or at the end of file if there is none
+    fn resync(&mut self, from: u64) -> Result<bool> {
+        let start = data_start::<Format>();
+        // the buffer is discarded by the seek below, including any lent part
+        // or block being read
+        self.lent = 0;
+        self.partial = None;
+        let file = self.buf_reader.get_mut();
+        let mut from = from;
+        while let Some(candidate) = resync_candidate::<Format, _>(file, start, from, self.len)? {
+            if let Some(end) = plausible_chunk_end::<Format, _>(file, start, candidate, self.len)? {
+                if end == self.len
+                    || plausible_chunk_end::<Format, _>(file, start, end, self.len)?.is_some()
+                {
+                    self.buf_reader
+                        .seek(std::io::SeekFrom::Start(start + candidate))?;
+                    self.pos = candidate;
+                    return Ok(true);
+                }
+            }
+            from = candidate + 1 - Format::SENTINEL.len() as u64;
+        }
+        self.buf_reader.seek(std::io::SeekFrom::End(0))?;
+        self.pos = self.len;
+        Ok(false)
+    }
+
+    /// Move to the block following the next sync marker, returning the offset of the
+    /// marker, or None and move to the end of file if there is none
+    ///
+    /// This is only valid for formats with `SYNC_MARKER` set, and typically used after
+    /// `next` returned an error. The scan starts right after the current position,
+    /// so that a marker at the current position is skipped over.
+    pub fn resync_to_marker(&mut self) -> Result<Option<u64>> {
+        if Format::SYNC_MARKER.is_empty() {
+            return Err(SeqDataError::Unsupported("format has no sync marker"));
+        }
+        let mut marker = Vec::new();
+        write_chunk::<Format, _>(
+            &mut marker,
+            &vec![0; Format::CHUNK_META_SIZE],
+            Format::SYNC_MARKER,
+            false,
+        )?;
+        let start = data_start::<Format>();
+        // the buffer is discarded by the seek below, including any lent part
+        // or block being read
+        self.lent = 0;
+        self.partial = None;
+        let file = self.buf_reader.get_mut();
+        match find_pattern(file, start, self.pos + 1, self.len, &marker)? {
+            Some(offset) => {
+                let next = offset + marker.len() as u64;
+                self.buf_reader
+                    .seek(std::io::SeekFrom::Start(start + next))?;
+                self.pos = next;
+                Ok(Some(offset))
+            }
+            None => {
+                self.buf_reader.seek(std::io::SeekFrom::End(0))?;
+                self.pos = self.len;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Check whether a block, as returned by `next`, is a sync marker
+    pub fn is_sync_marker(block: &[u8]) -> bool {
+        !Format::SYNC_MARKER.is_empty()
+            && block.len() == Format::CHUNK_META_SIZE + Format::SYNC_MARKER.len()
+            && block[Format::CHUNK_META_SIZE..] == *Format::SYNC_MARKER
+    }
+
+    /// Skip `n` blocks and return the next one along with its offset, or None if
+    /// reached the end of file.
+    ///
+    /// This is similar to `Iterator::nth`, but the skipped blocks are not read
+    /// into memory: only their length is read and their data is seeked over.
+    pub fn nth(&mut self, n: usize) -> Option<Result<(u64, Vec<u8>)>> {
+        match self.skip_chunks(n) {
+            Err(e) => Some(Err(e)),
+            Ok(skipped) if skipped < n => None,
+            Ok(_) => self.next(),
+        }
+    }
+
+    /// Skip up to `n` blocks without reading their data, and return the number of
+    /// blocks skipped, which is less than `n` only if the end of file was reached
+    pub fn skip_chunks(&mut self, n: usize) -> Result<usize> {
+        for skipped in 0..n {
+            match self.skip_chunk() {
+                None => return Ok(skipped),
+                Some(Err(e)) => return Err(e),
+                Some(Ok(_)) => {}
+            }
+        }
+        Ok(n)
+    }
+
+    /// Count the remaining chunks, seeking past their data instead of reading it
+    ///
+    /// A chunk cut short by the end of file is an error, not counted.
+    pub fn count_chunks(&mut self) -> Result<u64> {
+        Ok(self.skip_chunks(usize::MAX)? as u64)
+    }
+
+    /// Read the remaining blocks, and return the last `n` of them along with their offsets
+    ///
+    /// This reads every block once, keeping only the last `n` in memory, so it works
+    /// for any format, without trailing length nor index. Fewer blocks are returned if
+    /// there are less than `n` remaining.
+    pub fn last_n(&mut self, n: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        if n == 0 {
+            self.skip_chunks(usize::MAX)?;
+            return Ok(Vec::new());
+        }
+        let mut last = std::collections::VecDeque::with_capacity(n.min(1024));
+        while let Some(r) = self.next() {
+            if last.len() == n {
+                last.pop_front();
+            }
+            last.push_back(r?);
+        }
+        Ok(last.into())
+    }
+
+    /// Read the blocks of indices `from` (inclusive) to `to` (exclusive) along with
+    /// their offsets, the indices being counted from the current position
+    ///
+    /// The blocks before `from` are skipped with `skip_chunks`, and the range is clamped
+    /// to the blocks available, so fewer than `to - from` blocks are returned if the end
+    /// of file is reached.
+    pub fn read_index_range(&mut self, from: usize, to: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        if from > to {
+            return Err(SeqDataError::InvalidRange {
+                start: from as u64,
+                end: to as u64,
+            });
+        }
+        if self.skip_chunks(from)? < from {
+            return Ok(Vec::new());
+        }
+        let mut blocks = Vec::new();
+        while blocks.len() < to - from {
+            match self.next() {
+                None => break,
+                Some(r) => blocks.push(r?),
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Return an iterator over the offsets of the remaining blocks, without reading their data
+    ///
+    /// The blocks are consumed by the iterator: once it is dropped, a call to `next`
+    /// returns the block following the last offset yielded. The iterator stops after
+    /// the first error.
+    pub fn offsets(&mut self) -> impl Iterator<Item = Result<u64>> + '_ {
+        self.scan_chunks().map(|r| r.map(|meta| meta.offset))
+    }
+
+    /// Return an iterator over the index, offset and length of the remaining blocks,
+    /// without reading their data
+    ///
+    /// Only the length prefixes are read, and the data is seeked over. The index is
+    /// counted from the current position. As with `offsets`, the blocks are consumed by
+    /// the iterator, which stops after the first error.
+    pub fn scan_chunks(&mut self) -> impl Iterator<Item = Result<ChunkMeta>> + '_ {
+        let mut index = 0;
+        let mut failed = false;
+        std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+            let r = self.skip_chunk()?.map(|(offset, len)| {
+                let meta = ChunkMeta { index, offset, len };
+                index += 1;
+                meta
+            });
+            failed = r.is_err();
+            Some(r)
+        })
+    }
+
+    // the data is followed by the trailer magic or padding, or the reader is limited
+    // to the data present when opened, and not by the end of file
+    fn at_data_end(&self) -> bool {
+        self.bounded && self.pos >= self.len
+    }
+
+    /// Skip over the next block without reading its data, returning its offset and length
+    pub(crate) fn skip_chunk(&mut self) -> Option<Result<(u64, u64)>> {
+        if let Err(e) = self.finish_chunk() {
+            return Some(Err(e));
+        }
+        if self.at_data_end() {
+            return None;
+        }
+        match read_chunk_length::<Format, _>(self.reader()) {
+            None => None,
+            Some(Err(e)) => Some(Err(e)),
+            Some(Ok(raw)) => {
+                let (len, _) = split_prefix::<Format>(raw);
+                let end = match chunk_end::<Format>(self.pos, len, self.len) {
+                    Ok(end) => end,
+                    Err(e) => return Some(Err(e)),
+                };
+                if let Err(e) = self
+                    .buf_reader
+                    .seek_relative(len as i64 + framing_size::<Format>() as i64)
+                {
+                    return Some(Err(e.into()));
+                }
+                let current_pos = self.pos;
+                self.pos = end;
+                Some(Ok((current_pos, len)))
+            }
+        }
+    }
+}
+
+/// Iterate over the blocks along with their offset, as returned by `SeqDataReader::next`
+///
+/// Collecting into a `Result<Vec<_>>` stops at the first error.
+impl<Format: SeqDataFormat, R: Read + Seek> Iterator for SeqDataReader<Format, R> {
+    type Item = Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        SeqDataReader::next(self)
+    }
+}
+
+impl<Format: SeqDataFormat> TryFrom<&Path> for SeqDataReader<Format> {
+    type Error = SeqDataError;
+
+    /// Open a SeqData for reading, discarding its header
+    ///
+    /// Use `SeqDataReader::open` when the header is needed
+    fn try_from(path: &Path) -> Result<Self> {
+        Self::open(path).map(|(reader, _)| reader)
+    }
+}
+
+/// Seq Data Reader with seek
+///
+/// The data is read with positional reads from the backend `B`, a `File` by default
+pub struct SeqDataReaderSeek<Format: SeqDataFormat, B: ReadAt = File> {
+    backend: B,
+    phantom: PhantomData<Format>,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<Format: SeqDataFormat> SeqDataReaderSeek<Format> {
+    /// Open a new Seq Data seeker
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open for seeking");
+        let handle = on_error(File::open(path), "open")?;
+        Self::from_backend(handle)
+    }
+
+    /// Hint the OS that the data between the offsets `start` and `end` is going to be read
+    ///
+    /// This issues a readahead of the range so that a following burst of `next_at`
+    /// in this range is served from the page cache. This is only a hint, and does
+    /// nothing on platforms without support for it.
+    pub fn warm_range(&self, start: u64, end: u64) -> Result<()> {
+        if start > end {
+            return Err(SeqDataError::InvalidRange { start, end });
+        }
+        if end > self.len {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: end,
+                len: self.len,
+            });
+        }
+        if start == end {
+            return Ok(());
+        }
+        readahead(&self.backend, self.start + start, end - start)
+    }
+}
+
+impl<Format: SeqDataFormat> SeqDataReaderSeek<Format, Cursor<Vec<u8>>> {
+    /// Create a Seq Data seeker over a SeqData held in memory, returning the header
+    pub fn from_bytes(data: Vec<u8>) -> Result<(Self, Vec<u8>)> {
+        Self::from_backend(Cursor::new(data))
+    }
+}
+
+impl<Format: SeqDataFormat, T: AsRef<[u8]>> SeqDataReaderSeek<Format, Cursor<T>> {
+    /// Return every block of the data, borrowed from the buffer read from, in one pass
+    ///
+    /// For a memory mapped file (e.g. `SeqDataReaderMmap`), this gives zero copy
+    /// random access to all the blocks, without parsing the file again. For formats with
+    /// `CHUNK_META_SIZE` set, each block starts with the chunk metadata. The framing of
+    /// every chunk is checked, and the first error is returned.
+    pub fn index(&self) -> Result<Vec<&[u8]>> {
+        let data = self.backend.get_ref().as_ref();
+        let start = self.start as usize;
+        let mut data = &data[start..start + self.len as usize];
+        let mut blocks = Vec::new();
+        while !data.is_empty() {
+            if data.len() < prefix_size::<Format>() {
+                return Err(SeqDataError::TruncatedChunk {
+                    len: None,
+                    available: data.len() as u64,
+                });
+            }
+            let (lenbuf, rest) = data.split_at(prefix_size::<Format>());
+            let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+            let size = len as usize + framing_size::<Format>();
+            if size > rest.len() {
+                return Err(SeqDataError::TruncatedChunk {
+                    len: Some(len),
+                    available: rest.len() as u64,
+                });
+            }
+            let (chunk, rest) = rest.split_at(size);
+            let block_len = check_trailer_slice::<Format>(chunk)?;
+            blocks.push(&chunk[..block_len]);
+            data = rest;
+        }
+        Ok(blocks)
+    }
+}
+
+impl<Format: SeqDataFormat, B: ReadAt> SeqDataReaderSeek<Format, B> {
+    /// Create a Seq Data seeker reading from `backend`, returning the header
+    pub fn from_backend(backend: B) -> Result<(Self, Vec<u8>)> {
+        let total_len = backend.size()?;
+        let start = data_start::<Format>();
+        let trailer_len = Format::TRAILER_MAGIC.len() as u64;
+        if total_len < start + trailer_len {
+            return on_error(Err(file_too_short::<Format>(total_len)), "header");
+        }
+        let mut header = vec![0; start as usize];
+        backend.read_exact_at(&mut header, 0)?;
+        if header[..Format::MAGIC.len()] != *Format::MAGIC {
+            return on_error(Err(SeqDataError::MagicMismatch), "header");
+        }
+        // leave out the count of chunks, if any
+        header.truncate(Format::MAGIC.len() + Format::HEADER_SIZE);
+        let header = header.split_off(Format::MAGIC.len());
+        on_error(validate_header::<Format>(&header), "header")?;
+        let total_len = on_error(strip_padding::<Format>(&header, total_len), "header")?;
+        if trailer_len > 0 {
+            let mut trailer = vec![0; trailer_len as usize];
+            backend.read_exact_at(&mut trailer, total_len - trailer_len)?;
+            if trailer != Format::TRAILER_MAGIC {
+                return on_error(Err(SeqDataError::TrailerMismatch), "header");
+            }
+        }
+
+        Ok((
+            Self {
+                backend,
+                phantom: PhantomData,
+                start,
+                len: total_len - start - trailer_len,
+                pos: 0,
+            },
+            header,
+        ))
+    }
+
+    /// Return the backend this reader reads from
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Return the next block along with the current offset if it exists, or None if
+    /// reached the end of file.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Vec<u8>> {
+        let data =
+            read_chunk_at::<Format, _>(&self.backend, self.start + self.pos, self.start + self.len)
+                .map_err(|e| corrupted_at(e, self.pos))?;
+        self.pos += chunk_size::<Format>((data.len() - Format::CHUNK_META_SIZE) as u64);
+        Ok(data)
+    }
+
+    /// Offset that the next chunk appended to this file would report
+    ///
+    /// This is the length of the data when the file was opened.
+    pub fn append_position(&self) -> u64 {
+        self.len
+    }
+
+    /// Return the next block at the offset specified
+    ///
+    /// Note that if the position specified is not a valid boundary,
+    /// then arbitrary invalid stuff might be returns, or some Err
+    /// related to reading data
+    pub fn next_at(&mut self, pos: u64) -> Result<Vec<u8>> {
+        self.check_pos(pos)?;
+        self.pos = pos;
+        self.next()
+    }
+
+    /// Return an iterator over the blocks at the offsets of `index`, from the last one
+    /// to the first one, along with their offset
+    ///
+    /// This allows reading backwards a file written without `TRAILING_LENGTH`, at the
+    /// cost of building an index. An error is yielded, and nothing else, if the index
+    /// is for a data longer than this file.
+    pub fn iter_rev<'a>(
+        &'a mut self,
+        index: &'a Index,
+    ) -> impl Iterator<Item = Result<(u64, Vec<u8>)>> + 'a {
+        let mut error = (index.data_len() > self.len).then(|| SeqDataError::IndexMismatch {
+            indexed: index.data_len(),
+            len: self.len,
+        });
+        let offsets = if error.is_some() {
+            &[]
+        } else {
+            index.offsets()
+        };
+        let mut offsets = offsets.iter().rev();
+        std::iter::from_fn(move || {
+            if let Some(e) = error.take() {
+                return Some(Err(e));
+            }
+            let pos = *offsets.next()?;
+            Some(self.next_at(pos).map(|data| (pos, data)))
+        })
+    }
+
+    fn check_pos(&self, pos: u64) -> Result<()> {
+        if pos >= self.len {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: pos,
+                len: self.len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes starting at `payload_off` in the block at the offset specified,
+    /// without reading the rest of the block
+    ///
+    /// The range has to be within the block, and for formats with `CHUNK_META_SIZE` set,
+    /// the block starts with the chunk metadata. The trailer of the chunk, if any, is
+    /// not read nor checked.
+    pub fn read_at_slice(&mut self, pos: u64, payload_off: usize, len: usize) -> Result<Vec<u8>> {
+        self.check_pos(pos)?;
+
+        let mut lenbuf = [0; MAX_PREFIX_SIZE];
+        let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+        let offset = self.start + pos;
+        self.backend.read_exact_at(lenbuf, offset)?;
+        let (block_len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+        let block_len = block_len as usize + Format::CHUNK_META_SIZE;
+        if payload_off
+            .checked_add(len)
+            .is_none_or(|end| end > block_len)
+        {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: payload_off.saturating_add(len) as u64,
+                len: block_len as u64,
+            });
+        }
+
+        let mut out = vec![0; len];
+        self.backend.read_exact_at(
+            &mut out,
+            offset + prefix_size::<Format>() as u64 + payload_off as u64,
+        )?;
+        Ok(out)
+    }
+
+    /// Read the block at the offset specified into the scattered buffers `bufs`,
+    /// returning the length of the block
+    ///
+    /// The buffers are filled in order, and an error is returned if their total capacity
+    /// is less than the block length. When the capacity exceeds the block length, the
+    /// bytes past the block length are left untouched. For formats with `CHUNK_META_SIZE`
+    /// set, the block starts with the chunk metadata. The trailer of the chunk, if any,
+    /// is not read nor checked.
+    pub fn read_at_vectored(
+        &mut self,
+        pos: u64,
+        bufs: &mut [std::io::IoSliceMut<'_>],
+    ) -> Result<usize> {
+        self.check_pos(pos)?;
+
+        let mut lenbuf = [0; MAX_PREFIX_SIZE];
+        let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+        let mut offset = self.start + pos;
+        self.backend.read_exact_at(lenbuf, offset)?;
+        offset += prefix_size::<Format>() as u64;
+        let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+        let len = len as usize + Format::CHUNK_META_SIZE;
+        let capacity: usize = bufs.iter().map(|b| b.len()).sum();
+        if capacity < len {
+            return Err(SeqDataError::BufferTooSmall {
+                capacity: capacity as u64,
+                len: len as u64,
+            });
+        }
+
+        // only fill the first len bytes of the buffers
+        let mut remaining = len;
+        for buf in bufs.iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            let n = buf.len().min(remaining);
+            self.backend.read_exact_at(&mut buf[..n], offset)?;
+            offset += n as u64;
+            remaining -= n;
+        }
+        Ok(len)
+    }
+
+    /// Return the block ending at the offset specified, along with its offset
+    ///
+    /// `end_pos` is typically the offset of the block following the one wanted,
+    /// or the data length for the last block. This is only available for formats
+    /// with `TRAILING_LENGTH` set.
+    pub fn read_ending_at(&mut self, end_pos: u64) -> Result<(u64, Vec<u8>)> {
+        if !Format::TRAILING_LENGTH {
+            return Err(SeqDataError::Unsupported(
+                "format does not have trailing length",
+            ));
+        }
+        let suffix_size = (prefix_size::<Format>() + Format::SENTINEL.len()) as u64;
+        if end_pos > self.len || end_pos < chunk_size::<Format>(0) {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: end_pos,
+                len: self.len,
+            });
+        }
+
+        let mut lenbuf = [0; MAX_PREFIX_SIZE];
+        let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+        self.backend
+            .read_exact_at(lenbuf, self.start + end_pos - suffix_size)?;
+        let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+        let pos = end_pos
+            .checked_sub(chunk_size::<Format>(len))
+            .ok_or(SeqDataError::InvalidChunkEnd { len, end: end_pos })?;
+        let data = self.next_at(pos)?;
+        Ok((pos, data))
+    }
+}
+
+impl<Format: SeqDataFormat> TryFrom<&Path> for SeqDataReaderSeek<Format> {
+    type Error = SeqDataError;
+
+    /// Open a SeqData for seeking, discarding its header
+    fn try_from(path: &Path) -> Result<Self> {
+        Self::open(path).map(|(reader, _)| reader)
+    }
+}
+
+/// Length prefix decoded, whatever its width
+pub(crate) type PrefixLength = u64;
+
+/// Size in bytes of the length prefix of each chunk with the default `PrefixWidth::U32`
+pub const PREFIX_SIZE: usize = PrefixWidth::U32.size();
+
+/// Size in bytes of the widest length prefix
+pub(crate) const MAX_PREFIX_SIZE: usize = PrefixWidth::U64.size();
+
+/// Size in bytes of the length prefix of each chunk for the format specified
+pub fn prefix_size<Format: SeqDataFormat>() -> usize {
+    Format::PREFIX_WIDTH.size()
+}
+
+/// Bit of the length prefix set on chunks continued by the next one, for formats with
+/// `CONTINUATION` set
+fn continuation_bit<Format: SeqDataFormat>() -> PrefixLength {
+    1 << (prefix_size::<Format>() * 8 - 1)
+}
+
+/// Maximum size of the data of a chunk
+pub(crate) fn max_chunk_len<Format: SeqDataFormat>() -> usize {
+    let max = if Format::CONTINUATION {
+        continuation_bit::<Format>() - 1
+    } else {
+        PrefixLength::MAX >> (64 - prefix_size::<Format>() * 8)
+    };
+    max.try_into().unwrap_or(usize::MAX)
+}
+
+/// Decode the length prefix at the start of `bytes`
+pub(crate) fn decode_prefix<Format: SeqDataFormat>(bytes: &[u8]) -> PrefixLength {
+    let size = prefix_size::<Format>();
+    let mut raw = [0; MAX_PREFIX_SIZE];
+    raw[..size].copy_from_slice(&bytes[..size]);
+    PrefixLength::from_le_bytes(raw)
+}
+
+/// Split a length prefix into the length of the chunk, and whether it is continued
+pub(crate) fn split_prefix<Format: SeqDataFormat>(raw: PrefixLength) -> (PrefixLength, bool) {
+    if Format::CONTINUATION {
+        let bit = continuation_bit::<Format>();
+        (raw & !bit, raw & bit != 0)
+    } else {
+        (raw, false)
+    }
+}
+
+/// Length prefix encoded, which derefs to its bytes
+pub(crate) struct Prefix {
+    bytes: [u8; MAX_PREFIX_SIZE],
+    size: usize,
+}
+
+impl std::ops::Deref for Prefix {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.size]
+    }
+}
+
+/// Encode the length prefix of a chunk of `len` bytes, which need to be at most
+/// `max_chunk_len`
+pub(crate) fn encode_prefix<Format: SeqDataFormat>(len: usize, more: bool) -> Prefix {
+    let mut raw = len as PrefixLength;
+    if Format::CONTINUATION && more {
+        raw |= continuation_bit::<Format>();
+    }
+    Prefix {
+        bytes: raw.to_le_bytes(),
+        size: prefix_size::<Format>(),
+    }
+}
+
+/// Check that a chunk of `len` bytes fits the length prefix of the format
+pub(crate) fn check_chunk_len<Format: SeqDataFormat>(len: usize) -> Result<()> {
+    if len > max_chunk_len::<Format>() {
+        return Err(SeqDataError::ChunkTooLarge {
+            len: len as u64,
+            max: max_chunk_len::<Format>() as u64,
+        });
+    }
+    Ok(())
+}
+
+/// Size of the data following each chunk: the checksum if any, the repeated length for
+/// trailing length formats, then the sentinel if any
+pub(crate) fn trailer_size<Format: SeqDataFormat>() -> usize {
+    let trailing_length = if Format::TRAILING_LENGTH {
+        prefix_size::<Format>()
+    } else {
+        0
+    };
+    checksum_size::<Format>() + trailing_length + Format::SENTINEL.len()
+}
+
+/// Size of the checksum following the data of each chunk, 0 if the format has none
+pub(crate) fn checksum_size<Format: SeqDataFormat>() -> usize {
+    Format::CHECKSUM.map_or(0, |checksum| checksum.width())
+}
+
+/// Checksum of a chunk metadata and data, empty if the format has none
+pub(crate) fn chunk_checksum<Format: SeqDataFormat>(meta: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; checksum_size::<Format>()];
+    if let Some(checksum) = Format::CHECKSUM {
+        checksum.compute(&[meta, data], &mut out);
+    }
+    out
+}
+
+/// Size of the data read after the length prefix of a chunk, besides its data:
+/// the chunk metadata and the trailer
+pub(crate) fn framing_size<Format: SeqDataFormat>() -> usize {
+    Format::CHUNK_META_SIZE + trailer_size::<Format>()
+}
+
+/// Size taken in the file by a chunk of `len` bytes, including its framing
+pub(crate) fn chunk_size<Format: SeqDataFormat>(len: u64) -> u64 {
+    prefix_size::<Format>() as u64 + len + framing_size::<Format>() as u64
+}
+
+/// Return the end of the chunk of `len` bytes at `pos`, or a `TruncatedChunk` error if
+/// it goes past `end`
+///
+/// The length comes from the file, so this is to be checked before allocating anything
+/// from it: a corrupted length prefix can be anything up to the maximum of its width.
+pub(crate) fn chunk_end<Format: SeqDataFormat>(pos: u64, len: u64, end: u64) -> Result<u64> {
+    let prefix_end = pos.saturating_add(prefix_size::<Format>() as u64);
+    len.checked_add(framing_size::<Format>() as u64)
+        .and_then(|size| size.checked_add(prefix_end))
+        .filter(|chunk_end| *chunk_end <= end)
+        .ok_or_else(|| SeqDataError::TruncatedChunk {
+            len: Some(len),
+            available: end.saturating_sub(prefix_end),
+        })
+}
+
+pub(crate) fn check_chunk_meta<Format: SeqDataFormat>(meta: &[u8]) -> Result<()> {
+    if meta.len() != Format::CHUNK_META_SIZE {
+        return Err(SeqDataError::ChunkMetaSize {
+            expected: Format::CHUNK_META_SIZE,
+            got: meta.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Add the offset of the chunk to the errors about corrupted chunks (invalid sentinel,
+/// trailing length or checksum), leaving the other errors untouched
+pub(crate) fn corrupted_at(e: SeqDataError, pos: u64) -> SeqDataError {
+    if e.kind() != std::io::ErrorKind::InvalidData {
+        return e;
+    }
+    SeqDataError::CorruptedChunk {
+        offset: pos,
+        error: Box::new(e),
+    }
+}
+
+/// Check and remove the trailer at the end of a chunk buffer (metadata and data)
+pub(crate) fn check_trailer<Format: SeqDataFormat>(mut buf: Vec<u8>) -> Result<Vec<u8>> {
+    let len = check_trailer_slice::<Format>(&buf)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// Check the trailer at the end of `buf`, returning the length of what precedes it
+fn check_trailer_slice<Format: SeqDataFormat>(buf: &[u8]) -> Result<usize> {
+    let checksum_start = buf.len() - trailer_size::<Format>();
+    let framing_start = checksum_start + checksum_size::<Format>();
+    check_trailer_framing::<Format>(
+        &buf[framing_start..],
+        checksum_start - Format::CHUNK_META_SIZE,
+    )?;
+    if Format::CHECKSUM.is_some()
+        && chunk_checksum::<Format>(&buf[..checksum_start], &[])
+            != buf[checksum_start..framing_start]
+    {
+        return Err(SeqDataError::ChecksumMismatch);
+    }
+    Ok(checksum_start)
+}
+
+/// Check the trailing length if any and the sentinel following a chunk of `len` bytes
+fn check_trailer_framing<Format: SeqDataFormat>(framing: &[u8], len: usize) -> Result<()> {
+    let sentinel_start = framing.len() - Format::SENTINEL.len();
+    if framing[sentinel_start..] != *Format::SENTINEL {
+        return Err(SeqDataError::SentinelMismatch);
+    }
+    if Format::TRAILING_LENGTH {
+        let (trailer, _) = split_prefix::<Format>(decode_prefix::<Format>(framing));
+        if trailer as usize != len {
+            return Err(SeqDataError::TrailingLengthMismatch {
+                leading: len as u64,
+                trailing: trailer,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Return the end of the chunk at `offset` if its framing looks valid
+fn plausible_chunk_end<Format: SeqDataFormat, R: Read + Seek>(
+    file: &mut R,
+    start: u64,
+    offset: u64,
+    len: u64,
+) -> Result<Option<u64>> {
+    if offset + chunk_size::<Format>(0) > len {
+        return Ok(None);
+    }
+    let mut lenbuf = [0; MAX_PREFIX_SIZE];
+    let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+    file.seek(std::io::SeekFrom::Start(start + offset))?;
+    file.read_exact(lenbuf)?;
+    let (chunk_len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+    let Ok(end) = chunk_end::<Format>(offset, chunk_len, len) else {
+        return Ok(None);
+    };
+    let sentinel_start = end - Format::SENTINEL.len() as u64;
+    if Format::TRAILING_LENGTH {
+        file.seek(std::io::SeekFrom::Start(
+            start + sentinel_start - lenbuf.len() as u64,
+        ))?;
+        file.read_exact(lenbuf)?;
+        if split_prefix::<Format>(decode_prefix::<Format>(lenbuf)).0 != chunk_len {
+            return Ok(None);
+        }
+    }
+    if !Format::SENTINEL.is_empty() {
+        let mut sentinel = vec![0; Format::SENTINEL.len()];
+        file.seek(std::io::SeekFrom::Start(start + sentinel_start))?;
+        file.read_exact(&mut sentinel)?;
+        if sentinel != Format::SENTINEL {
+            return Ok(None);
+        }
+    }
+    Ok(Some(end))
+}
+
+/// Return the first offset at or after `from` where a chunk could start
+///
+/// Without sentinel, any offset is a candidate, otherwise this is the offset
+/// following the next occurrence of the sentinel starting at or after `from`
+fn resync_candidate<Format: SeqDataFormat, R: Read + Seek>(
+    file: &mut R,
+    start: u64,
+    from: u64,
+    len: u64,
+) -> Result<Option<u64>> {
+    let sentinel = Format::SENTINEL;
+    if sentinel.is_empty() {
+        return Ok(Some(from).filter(|from| *from < len));
+    }
+    let found = find_pattern(file, start, from, len, sentinel)?;
+    Ok(found.map(|offset| offset + sentinel.len() as u64))
+}
+
+/// Return the offset of the first occurrence of `pattern` starting at or after `from`
+fn find_pattern<R: Read + Seek>(
+    file: &mut R,
+    start: u64,
+    from: u64,
+    len: u64,
+    pattern: &[u8],
+) -> Result<Option<u64>> {
+    let mut buf = vec![0; (64 * 1024).max(2 * pattern.len())];
+    let mut pos = from;
+    while pos + pattern.len() as u64 <= len {
+        let n = ((len - pos) as usize).min(buf.len());
+        file.seek(std::io::SeekFrom::Start(start + pos))?;
+        file.read_exact(&mut buf[..n])?;
+        if let Some(i) = buf[..n].windows(pattern.len()).position(|w| w == pattern) {
+            return Ok(Some(pos + i as u64));
+        }
+        pos += (n + 1 - pattern.len()) as u64;
+    }
+    Ok(None)
+}
+
+fn read_chunk_length<Format: SeqDataFormat, R: Read>(file: &mut R) -> Option<Result<PrefixLength>> {
+    let mut lenbuf = [0; MAX_PREFIX_SIZE];
+    let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+    // try to read the length, if the length return a none, we just expect
+    // having reached the end of the stream then
+    match optional_read_exact(file, lenbuf) {
+        None => None,
+        Some(Err(e)) => Some(Err(e.into())),
+        Some(Ok(())) => Some(Ok(decode_prefix::<Format>(lenbuf))),
+    }
+}
+
+/// Outcome of `read_chunk`: a whole chunk, or how it is cut short by the end of file
+#[derive(Debug)]
+enum ChunkRead {
+    /// Whole chunk, along with whether it has the continuation flag set
+    Complete { more: bool },
+    /// End of file within the length prefix, after `available` bytes of it
+    TornPrefix { available: usize },
+    /// End of file within the chunk of `len` bytes, after `available` bytes of what
+    /// follows its length prefix (metadata, data and trailer)
+    TornPayload { len: u64, available: u64 },
+}
+
+impl ChunkRead {
+    /// Return the continuation flag of the whole chunk, reporting a torn chunk as
+    /// `SeqDataError::TruncatedChunk`
+    fn complete(self) -> Result<bool> {
+        match self {
+            ChunkRead::Complete { more } => Ok(more),
+            ChunkRead::TornPrefix { available } => Err(SeqDataError::TruncatedChunk {
+                len: None,
+                available: available as u64,
+            }),
+            ChunkRead::TornPayload { len, available } => Err(SeqDataError::TruncatedChunk {
+                len: Some(len),
+                available,
+            }),
+        }
+    }
+}
+
+/// Read a chunk into `out`, or return None on an end of file before any byte of the
+/// chunk, leaving `out` untouched
+///
+/// `out` is resized to the block (metadata included) when the chunk is complete, and
+/// its content is unspecified otherwise. A chunk cut short by the end of file isn't an
+/// error here, but one of the torn variants of `ChunkRead`, so that callers can tell it
+/// from an I/O error. `available` is the number of bytes from the start of the chunk to
+/// the end of the data: a length prefix going past it is a torn payload, and nothing is
+/// allocated nor read for it.
+fn read_chunk<Format: SeqDataFormat, R: Read>(
+    file: &mut R,
+    out: &mut Vec<u8>,
+    available: u64,
+) -> Option<Result<ChunkRead>> {
+    let mut lenbuf = [0; MAX_PREFIX_SIZE];
+    let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+    match read_up_to(file, lenbuf) {
+        Err(e) => return Some(Err(e.into())),
+        Ok(0) => return None,
+        Ok(available) if available < lenbuf.len() => {
+            return Some(Ok(ChunkRead::TornPrefix { available }))
+        }
+        Ok(_) => {}
+    }
+    let (len, more) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+    if chunk_end::<Format>(0, len, available).is_err() {
+        // skip the rest of the data like a read to the end would, without allocating
+        let rest = available.saturating_sub(lenbuf.len() as u64);
+        return Some(
+            std::io::copy(&mut file.take(rest), &mut std::io::sink())
+                .map(|available| ChunkRead::TornPayload { len, available })
+                .map_err(Into::into),
+        );
+    }
+    // resize the buffer to the prefix length 'len' and read all data
+    out.clear();
+    out.resize(len as usize + framing_size::<Format>(), 0);
+    match read_up_to(file, out) {
+        Err(e) => Some(Err(e.into())),
+        Ok(available) if available < out.len() => Some(Ok(ChunkRead::TornPayload {
+            len,
+            available: available as u64,
+        })),
+        Ok(_) => Some(check_trailer_slice::<Format>(out).map(|block_len| {
+            out.truncate(block_len);
+            ChunkRead::Complete { more }
+        })),
+    }
+}
+
+/// Read a whole chunk, or return None on an end of file before any byte of the chunk
+///
+/// `available` is as for `read_chunk`.
+fn read_chunk_data<Format: SeqDataFormat, R: Read>(
+    file: &mut R,
+    available: u64,
+) -> Option<Result<Vec<u8>>> {
+    let mut data = Vec::new();
+    read_chunk::<Format, R>(file, &mut data, available)
+        .map(|r| r.and_then(ChunkRead::complete).map(|_| data))
+}
+
+/// Read the chunk at the offset specified with positional reads, which need to end
+/// before `end`
+pub(crate) fn read_chunk_at<Format: SeqDataFormat, B: ReadAt + ?Sized>(
+    backend: &B,
+    offset: u64,
+    end: u64,
+) -> Result<Vec<u8>> {
+    let mut lenbuf = [0; MAX_PREFIX_SIZE];
+    let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+    backend.read_exact_at(lenbuf, offset)?;
+    let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+    chunk_end::<Format>(offset, len, end)?;
+    let mut out = vec![0; len as usize + framing_size::<Format>()];
+    backend.read_exact_at(&mut out, offset + prefix_size::<Format>() as u64)?;
+    check_trailer::<Format>(out)
+}
+
+fn write_chunk<Format: SeqDataFormat, W: Write>(
+    file: &mut W,
+    meta: &[u8],
+    data: &[u8],
+    more: bool,
+) -> Result<()> {
+    check_chunk_meta::<Format>(meta)?;
+    check_chunk_len::<Format>(data.len())?;
+    let header = encode_prefix::<Format>(data.len(), more);
+    file.write_all(&header)?;
+    file.write_all(meta)?;
+    file.write_all(data)?;
+    file.write_all(&chunk_checksum::<Format>(meta, data))?;
+    if Format::TRAILING_LENGTH {
+        file.write_all(&header)?;
+    }
+    file.write_all(Format::SENTINEL)?;
+    Ok(())
+}
+
+// the length is unchanged, so is the trailer if any besides the checksum.
+// `available` is the number of bytes from the start of the chunk to the end of the data
+fn overwrite_chunk<Format: SeqDataFormat>(
+    file: &mut File,
+    data: &[u8],
+    available: u64,
+) -> Result<()> {
+    let mut lenbuf = [0; MAX_PREFIX_SIZE];
+    let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+    file.read_exact(lenbuf)?;
+    let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+    chunk_end::<Format>(0, len, available)?;
+    if len != data.len() as u64 {
+        return Err(SeqDataError::ChunkSizeMismatch {
+            len,
+            got: data.len() as u64,
+        });
+    }
+    let mut meta = vec![0; Format::CHUNK_META_SIZE];
+    file.read_exact(&mut meta)?;
+    file.write_all(data)?;
+    Ok(file.write_all(&chunk_checksum::<Format>(&meta, data))?)
+}
+
+fn write_chunk_counted<Format: SeqDataFormat, W: Write>(
+    file: &mut W,
+    meta: &[u8],
+    data: &[u8],
+    more: bool,
+    written: &mut u64,
+) -> Result<()> {
+    check_chunk_meta::<Format>(meta)?;
+    check_chunk_len::<Format>(data.len())?;
+    let header = encode_prefix::<Format>(data.len(), more);
+    write_all_counted(file, &header, written)?;
+    write_all_counted(file, meta, written)?;
+    write_all_counted(file, data, written)?;
+    write_all_counted(file, &chunk_checksum::<Format>(meta, data), written)?;
+    if Format::TRAILING_LENGTH {
+        write_all_counted(file, &header, written)?;
+    }
+    write_all_counted(file, Format::SENTINEL, written)?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn readahead(file: &File, offset: u64, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let r = unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            libc::POSIX_FADV_WILLNEED,
+        )
+    };
+    // posix_fadvise returns the error number instead of setting errno
+    if r != 0 {
+        return Err(std::io::Error::from_raw_os_error(r).into());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn readahead(_file: &File, _offset: u64, _len: u64) -> Result<()> {
+    Ok(())
+}
+
+fn check_header_size<Format: SeqDataFormat>(header: &[u8]) -> Result<()> {
+    if Format::HEADER_SIZE != header.len() {
+        return Err(SeqDataError::HeaderSize {
+            expected: Format::HEADER_SIZE,
+            got: header.len(),
+        });
+    }
+    if Format::DIRTY_FLAG.is_some_and(|offset| offset >= Format::HEADER_SIZE) {
+        return Err(invalid_header("dirty flag is not in the header"));
+    }
+    if let Some(offset) = Format::PADDING_LENGTH {
+        if offset + PADDING_LENGTH_SIZE > Format::HEADER_SIZE {
+            return Err(invalid_header("padding length is not in the header"));
+        }
+        if Format::DIRTY_FLAG
+            .is_some_and(|flag| (offset..offset + PADDING_LENGTH_SIZE).contains(&flag))
+        {
+            return Err(invalid_header("dirty flag overlaps the padding length"));
+        }
+        if padding_length::<Format>(header) != 0 {
+            return Err(invalid_header(
+                "padding length need to be zero in the header",
+            ));
+        }
+    }
+    Ok(())
+}
+
+const PADDING_LENGTH_SIZE: usize = 4;
+
+/// Number of zero bytes padding the end of the file, as recorded in its header
+pub(crate) fn padding_length<Format: SeqDataFormat>(header: &[u8]) -> u64 {
+    Format::PADDING_LENGTH
+        .and_then(|offset| header.get(offset..offset + PADDING_LENGTH_SIZE))
+        .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()) as u64)
+}
+
+// write the padding length in place in the header, keeping the current position
+fn set_padding_length<Format: SeqDataFormat>(file: &mut File, padding: u32) -> Result<()> {
+    if let Some(offset) = Format::PADDING_LENGTH {
+        let pos = file.stream_position()?;
+        file.seek(std::io::SeekFrom::Start(
+            Format::MAGIC.len() as u64 + offset as u64,
+        ))?;
+        file.write_all(&padding.to_le_bytes())?;
+        file.seek(std::io::SeekFrom::Start(pos))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_header<Format: SeqDataFormat>(header: &[u8]) -> Result<()> {
+    Format::validate_header(header).map_err(|e| invalid_header(e.to_string()))
+}
+
+pub(crate) fn invalid_header<S: Into<String>>(reason: S) -> SeqDataError {
+    SeqDataError::InvalidHeader(reason.into())
+}
+
+// write the dirty flag in place in the header, keeping the current position
+fn set_dirty_flag<Format: SeqDataFormat>(file: &mut File, dirty: bool) -> Result<()> {
+    if let Some(offset) = Format::DIRTY_FLAG {
+        if offset >= Format::HEADER_SIZE {
+            return Err(invalid_header("dirty flag is not in the header"));
+        }
+        let pos = file.stream_position()?;
+        file.seek(std::io::SeekFrom::Start(
+            Format::MAGIC.len() as u64 + offset as u64,
+        ))?;
+        file.write_all(&[dirty as u8])?;
+        file.seek(std::io::SeekFrom::Start(pos))?;
+    }
+    Ok(())
+}
+
+/// Open the file at `path` left empty by an interrupted creation, or fail with `exists`
+/// if it has some content
+fn reopen_empty(path: &Path, exists: std::io::Error) -> Result<File> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    if file.metadata()?.len() != 0 {
+        return Err(exists.into());
+    }
+    debug!(path = %path.display(), "initializing empty file");
+    Ok(file)
+}
+
+/// Whether the data is followed by the trailer magic or padding, so that reading need
+/// to stop at the data length instead of the end of file
+pub(crate) fn data_followed<Format: SeqDataFormat>() -> bool {
+    !Format::TRAILER_MAGIC.is_empty() || Format::PADDING_LENGTH.is_some()
+}
+
+/// Offset in the file of the first chunk
+fn data_start<Format: SeqDataFormat>() -> u64 {
+    let count_size = if Format::CHUNK_COUNT {
+        CHUNK_COUNT_SIZE as u64
+    } else {
+        0
+    };
+    Format::MAGIC.len() as u64 + Format::HEADER_SIZE as u64 + count_size
+}
+
+const CHUNK_COUNT_SIZE: usize = 8;
+
+fn write_magic_and_header<Format: SeqDataFormat, W: Write>(
+    sink: &mut W,
+    header: &[u8],
+) -> Result<()> {
+    sink.write_all(Format::MAGIC)?;
+    sink.write_all(header)?;
+    if Format::CHUNK_COUNT {
+        sink.write_all(&[0; CHUNK_COUNT_SIZE])?;
+    }
+    Ok(())
+}
+
+// write the count of chunks in place after the header, keeping the current position
+fn set_chunk_count<Format: SeqDataFormat>(file: &mut File, count: u64) -> Result<()> {
+    let pos = file.stream_position()?;
+    file.seek(std::io::SeekFrom::Start(
+        Format::MAGIC.len() as u64 + Format::HEADER_SIZE as u64,
+    ))?;
+    file.write_all(&count.to_le_bytes())?;
+    file.seek(std::io::SeekFrom::Start(pos))?;
+    Ok(())
+}
+
+fn get_file_length<Format: SeqDataFormat, R: Read + Seek>(
+    phantom: PhantomData<Format>,
+    file: &mut R,
+) -> Result<u64> {
+    let total_len = file.seek(std::io::SeekFrom::End(0))?;
+    data_length(phantom, file, total_len)
+}
+
+/// Return the length of the data of a file of `total_len` bytes, leaving the file at its start
+fn data_length<Format: SeqDataFormat, R: Read + Seek>(
+    _phantom: PhantomData<Format>,
+    file: &mut R,
+    mut total_len: u64,
+) -> Result<u64> {
+    let minimum_size = data_start::<Format>() + Format::TRAILER_MAGIC.len() as u64;
+    if total_len < minimum_size {
+        return on_error(Err(file_too_short::<Format>(total_len)), "header");
+    }
+    if Format::PADDING_LENGTH.is_some() {
+        let mut header = vec![0; Format::HEADER_SIZE];
+        file.seek(std::io::SeekFrom::Start(Format::MAGIC.len() as u64))?;
+        file.read_exact(&mut header)?;
+        total_len = on_error(strip_padding::<Format>(&header, total_len), "header")?;
+    }
+    if !Format::TRAILER_MAGIC.is_empty() {
+        on_error(check_trailer_magic::<Format, _>(file, total_len), "header")?;
+    }
+    file.seek(std::io::SeekFrom::Start(0))?;
+    Ok(total_len - minimum_size)
+}
+
+/// Return the length of a file of `total_len` bytes once the padding recorded in
+/// `header` is removed
+pub(crate) fn strip_padding<Format: SeqDataFormat>(header: &[u8], total_len: u64) -> Result<u64> {
+    let padding = padding_length::<Format>(header);
+    let minimum_size = data_start::<Format>() + Format::TRAILER_MAGIC.len() as u64;
+    if total_len < minimum_size + padding {
+        return Err(invalid_header(format!(
+            "padding length {} past the end of the file",
+            padding
+        )));
+    }
+    Ok(total_len - padding)
+}
+
+/// Check that the file of `total_len` bytes ends with the trailer magic
+fn check_trailer_magic<Format: SeqDataFormat, R: Read + Seek>(
+    file: &mut R,
+    total_len: u64,
+) -> Result<()> {
+    let trailer_len = Format::TRAILER_MAGIC.len() as u64;
+    if total_len < data_start::<Format>() + trailer_len {
+        return Err(file_too_short::<Format>(total_len));
+    }
+    let mut trailer = vec![0; Format::TRAILER_MAGIC.len()];
+    file.seek(std::io::SeekFrom::Start(total_len - trailer_len))?;
+    file.read_exact(&mut trailer)?;
+    if trailer != Format::TRAILER_MAGIC {
+        return Err(SeqDataError::TrailerMismatch);
+    }
+    Ok(())
+}
+
+/// Error for a file of `len` bytes, too short for the magic, header and trailer magic
+pub(crate) fn file_too_short<Format: SeqDataFormat>(len: u64) -> SeqDataError {
+    SeqDataError::FileTooShort {
+        len,
+        min: data_start::<Format>() + Format::TRAILER_MAGIC.len() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Framed;
+    impl SeqDataFormat for Framed {
+        const MAGIC: &'static [u8] = b"";
+        const HEADER_SIZE: usize = 0;
+        const TRAILING_LENGTH: bool = true;
+    }
+
+    fn encoded(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_chunk::<Framed, _>(&mut out, &[], data, false).unwrap();
+        out
+    }
+
+    fn read(bytes: &[u8]) -> (Option<ChunkRead>, Vec<u8>) {
+        let mut out = Vec::new();
+        let read = read_chunk::<Framed, _>(&mut &bytes[..], &mut out, u64::MAX).map(|r| r.unwrap());
+        (read, out)
+    }
+
+    #[test]
+    fn read_chunk_complete() {
+        let (read, out) = read(&encoded(b"hello"));
+        assert!(matches!(read, Some(ChunkRead::Complete { more: false })));
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn read_chunk_end_of_file() {
+        assert!(read(&[]).0.is_none());
+    }
+
+    #[test]
+    fn read_chunk_torn_prefix() {
+        let bytes = encoded(b"hello");
+        for available in 1..PREFIX_SIZE {
+            let (read, _) = read(&bytes[..available]);
+            match read {
+                Some(ChunkRead::TornPrefix { available: a }) => assert_eq!(a, available),
+                r => panic!("expected a torn prefix, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn read_chunk_torn_payload() {
+        let bytes = encoded(b"hello");
+        // cut in the data, then in the trailing length
+        for cut in [PREFIX_SIZE, PREFIX_SIZE + 2, bytes.len() - 1] {
+            let (read, _) = read(&bytes[..cut]);
+            match read {
+                Some(ChunkRead::TornPayload { len, available }) => {
+                    assert_eq!(len, 5);
+                    assert_eq!(available, (cut - PREFIX_SIZE) as u64);
+                }
+                r => panic!("expected a torn payload, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn torn_chunks_are_truncated_chunk_errors() {
+        let err = ChunkRead::TornPayload {
+            len: 5,
+            available: 2,
+        }
+        .complete()
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            err,
+            SeqDataError::TruncatedChunk {
+                len: Some(5),
+                available: 2
+            }
+        ));
+    }
+
+    thread_local! {
+        static READAHEADS: std::cell::RefCell<Vec<(u64, u64)>> = Default::default();
+    }
+
+    fn record_readahead(_: &Cursor<Vec<u8>>, offset: u64, len: u64) -> Result<()> {
+        READAHEADS.with(|r| r.borrow_mut().push((offset, len)));
+        Ok(())
+    }
+
+    #[test]
+    fn prefetch_issues_readahead_ahead_of_the_reads() {
+        let chunk = 300_000;
+        let mut bytes = Vec::new();
+        for _ in 0..5 {
+            write_chunk::<NoMagicNoHeader, _>(&mut bytes, &[], &vec![0; chunk], false).unwrap();
+        }
+        let len = bytes.len() as u64;
+        let (mut reader, _) = SeqDataReader::<NoMagicNoHeader, _>::from_bytes(bytes).unwrap();
+        reader.prefetch = Some(record_readahead);
+        let mut ends = Vec::new();
+        while let Some(r) = reader.next() {
+            let (offset, data) = r.unwrap();
+            ends.push(offset + chunk_size::<NoMagicNoHeader>(data.len() as u64));
+        }
+        // a readahead after the first chunk, then once less than half of it is left ahead
+        let readaheads = READAHEADS.with(|r| r.take());
+        assert_eq!(
+            readaheads,
+            [(ends[0], PREFETCH_SIZE), (ends[2], len - ends[2])]
+        );
     }
-    Ok(total_len - minimum_size)
 }
@@ -0,0 +1,39 @@
+//! Tracing events, compiled out when the `tracing` feature is disabled
+
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! trace {
+    ($($arg:tt)*) => { tracing::trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {};
+}
+
+pub(crate) use debug;
+pub(crate) use trace;
+
+/// Emit a debug event if `result` is an error, and pass it through
+#[inline]
+pub(crate) fn on_error<T, E: std::fmt::Display>(
+    result: Result<T, E>,
+    operation: &'static str,
+) -> Result<T, E> {
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        tracing::debug!(operation, error = %e, "seqdata error");
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = operation;
+    result
+}
@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::format::SeqDataFormat;
+use crate::logging::{debug, on_error};
+use crate::{Result, SeqDataReaderSeek};
+
+/// Seq Data Reader with seek, over a memory mapped file
+///
+/// The file must not be truncated while it is mapped, as accessing the truncated part
+/// of the mapping faults, e.g. with a SIGBUS on unix.
+pub struct SeqDataReaderMmap<Format: SeqDataFormat> {
+    inner: SeqDataReaderSeek<Format, Cursor<Mmap>>,
+}
+
+impl<Format: SeqDataFormat> SeqDataReaderMmap<Format> {
+    /// Map the SeqData at the location specified, returning the reader and the header
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "map for reading");
+        let file = on_error(File::open(path), "open")?;
+        // safety: the mapping is read only, and truncating the file while mapped is
+        // documented as not allowed
+        let map = on_error(unsafe { Mmap::map(&file) }, "open")?;
+        let (inner, header) = SeqDataReaderSeek::from_backend(Cursor::new(map))?;
+        Ok((Self { inner }, header))
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.len == 0
+    }
+
+    /// Return every block of the data, borrowed from the mapping, in one pass
+    ///
+    /// See `SeqDataReaderSeek::index`
+    pub fn index(&self) -> Result<Vec<&[u8]>> {
+        self.inner.index()
+    }
+
+    /// Return the block at the offset specified
+    ///
+    /// See `SeqDataReaderSeek::next_at` for the caveats on the offset
+    pub fn next_at(&mut self, pos: u64) -> Result<Vec<u8>> {
+        self.inner.next_at(pos)
+    }
+}
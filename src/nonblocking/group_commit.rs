@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::SeqDataWriter;
+use crate::format::SeqDataFormat;
+use crate::{Result, SeqDataError};
+
+struct Request {
+    data: Vec<u8>,
+    done: oneshot::Sender<Result<u64>>,
+}
+
+/// Async writer batching appends from multiple tasks into group commits
+///
+/// Appends are sent to a background task that writes them and then issues a single
+/// `sync_data` for the whole batch. A batch is committed when `max_delay` has elapsed
+/// since its first append, or when it holds at least `max_batch_bytes` bytes.
+///
+/// Chunks are written in the order their append was received by the background
+/// task, and each `append` resolves only once the batch containing it has been
+/// durably synced. A write or sync error fails every append of the batch and stops
+/// the writer; the error is also returned by `close`.
+pub struct GroupCommitWriter {
+    tx: mpsc::Sender<Request>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl GroupCommitWriter {
+    /// Spawn the background task committing to `writer`
+    ///
+    /// This need to be called from within a tokio runtime
+    pub fn spawn<Format: SeqDataFormat + Send + 'static>(
+        writer: SeqDataWriter<Format>,
+        max_delay: Duration,
+        max_batch_bytes: usize,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(1024);
+        let task = tokio::spawn(commit_loop(writer, rx, max_delay, max_batch_bytes));
+        Self { tx, task }
+    }
+
+    /// Append a new data chunk, returning its offset once it has been durably written
+    pub async fn append(&self, data: Vec<u8>) -> Result<u64> {
+        let (done, wait) = oneshot::channel();
+        self.tx
+            .send(Request { data, done })
+            .await
+            .map_err(|_| SeqDataError::WriterStopped)?;
+        wait.await.map_err(|_| SeqDataError::WriterStopped)?
+    }
+
+    /// Commit the pending appends, close the writer and stop the background task
+    pub async fn close(self) -> Result<()> {
+        drop(self.tx);
+        self.task.await.map_err(std::io::Error::other)?
+    }
+}
+
+async fn commit_loop<Format: SeqDataFormat>(
+    mut writer: SeqDataWriter<Format>,
+    mut rx: mpsc::Receiver<Request>,
+    max_delay: Duration,
+    max_batch_bytes: usize,
+) -> Result<()> {
+    while let Some(first) = rx.recv().await {
+        let deadline = Instant::now() + max_delay;
+        let mut batch_bytes = first.data.len();
+        let mut batch = vec![first];
+        while batch_bytes < max_batch_bytes {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(request)) => {
+                    batch_bytes += request.data.len();
+                    batch.push(request);
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(batch.len());
+        let mut result = Ok(());
+        for request in &batch {
+            offsets.push(writer.position());
+            result = writer.append(&request.data).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = writer.sync().await;
+        }
+
+        match result {
+            Ok(()) => {
+                for (request, offset) in batch.into_iter().zip(offsets) {
+                    let _ = request.done.send(Ok(offset));
+                }
+            }
+            Err(e) => {
+                for request in batch {
+                    let _ = request
+                        .done
+                        .send(Err(std::io::Error::new(e.kind(), e.to_string()).into()));
+                }
+                return Err(e);
+            }
+        }
+    }
+    writer.close().await
+}
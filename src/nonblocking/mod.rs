@@ -1,46 +1,87 @@
 // use std::io::{BufReader, Read, Seek, Write};
+use std::future::Future;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::format::SeqDataFormat;
+use crate::logging::{debug, on_error, trace};
+use crate::{
+    check_chunk_len, check_chunk_meta, check_trailer, chunk_checksum, chunk_end, chunk_size,
+    corrupted_at, data_followed, data_start, decode_prefix, encode_prefix, file_too_short,
+    framing_size, invalid_header, padding_length, prefix_size, read_chunk_at, split_prefix,
+    strip_padding, validate_header, MetaChunk, Result, SeqDataError, Stats, MAX_PREFIX_SIZE,
+    REPEAT_WRITE_SIZE,
+};
+
+mod group_commit;
+
+pub use group_commit::GroupCommitWriter;
 
 /// Writer for a new SeqData
 pub struct SeqDataWriter<Format: SeqDataFormat> {
     file: File,
+    pos: u64,
+    bytes_written: u64,
+    chunks_written: u64,
     phantom: PhantomData<Format>,
 }
 
 impl<Format: SeqDataFormat> SeqDataWriter<Format> {
     /// Create a new SeqData File at the location specified
     ///
-    /// If the file already exists, this call will fail
+    /// If the file already exists, this call will fail, unless the file is empty,
+    /// as left by an interrupted creation, like `crate::SeqDataWriter::create`
     ///
     /// The header need to fits the size of Format::HEADER_SIZE
-    pub async fn create<P: AsRef<Path>>(path: P, header: &[u8]) -> std::io::Result<Self> {
+    pub async fn create<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<Self> {
         if Format::HEADER_SIZE != header.len() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "header has invalid size, expecting {} but got {}",
-                    Format::HEADER_SIZE,
-                    header.len()
-                ),
+            return Err(SeqDataError::HeaderSize {
+                expected: Format::HEADER_SIZE,
+                got: header.len(),
+            });
+        }
+        if padding_length::<Format>(header) != 0 {
+            return Err(invalid_header(
+                "padding length need to be zero in the header",
             ));
         }
+        check_append_only::<Format>()?;
+        validate_header::<Format>(header)?;
 
-        let mut file = OpenOptions::new()
+        let path = path.as_ref();
+        debug!(path = %path.display(), "create");
+        let mut file = match OpenOptions::new()
             .read(false)
             .write(true)
             .create_new(true)
             .append(true)
             .open(path)
-            .await?;
-        file.write_all(&Format::MAGIC).await?;
-        file.write_all(header).await?;
+            .await
+        {
+            // initialize again an empty file left by an interrupted creation
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                on_error(reopen_empty(path, e).await, "create")?
+            }
+            r => on_error(r, "create")?,
+        };
+        // don't leave a file with a partial header behind
+        if let Err(e) = write_magic_and_header::<Format>(&mut file, header).await {
+            let _ = tokio::fs::remove_file(path).await;
+            return on_error(Err(e), "create");
+        }
         Ok(SeqDataWriter {
             file,
+            pos: 0,
+            bytes_written: 0,
+            chunks_written: 0,
             phantom: PhantomData,
         })
     }
@@ -50,42 +91,295 @@ impl<Format: SeqDataFormat> SeqDataWriter<Format> {
     /// If the file already exists, this call will fail
     ///
     /// The header need to fits the size of Format::HEADER_SIZE
-    pub async fn open<P: AsRef<Path>>(path: P, header: &[u8]) -> std::io::Result<(Self, Vec<u8>)> {
+    pub async fn open<P: AsRef<Path>>(path: P, header: &[u8]) -> Result<(Self, Vec<u8>)> {
         if Format::HEADER_SIZE != header.len() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "header has invalid size, expecting {} but got {}",
-                    Format::HEADER_SIZE,
-                    header.len()
-                ),
-            ));
+            return Err(SeqDataError::HeaderSize {
+                expected: Format::HEADER_SIZE,
+                got: header.len(),
+            });
         }
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create_new(false)
-            .append(true)
-            .open(path)
-            .await?;
+        check_append_only::<Format>()?;
+
+        debug!(path = %path.as_ref().display(), "open for writing");
+        let mut file = on_error(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(false)
+                .append(true)
+                .open(path)
+                .await,
+            "open",
+        )?;
 
         file.seek(std::io::SeekFrom::Start(0)).await?;
         let header = read_magic_and_header(PhantomData::<Format>, &mut file).await?;
-        file.seek(std::io::SeekFrom::End(0)).await?;
+        if padding_length::<Format>(&header) != 0 {
+            return Err(SeqDataError::Unsupported(
+                "appending to a padded file is not supported",
+            ));
+        }
+        let mut end = file.seek(std::io::SeekFrom::End(0)).await?;
+        if !Format::TRAILER_MAGIC.is_empty() {
+            // the trailer magic is written again by close
+            check_trailer_magic::<Format>(&mut file, end).await?;
+            end -= Format::TRAILER_MAGIC.len() as u64;
+            file.set_len(end).await?;
+        }
 
         Ok((
             SeqDataWriter {
                 file,
+                pos: end - data_start::<Format>(),
+                bytes_written: 0,
+                chunks_written: 0,
                 phantom: PhantomData,
             },
             header,
         ))
     }
 
+    /// Finish writing, writing the trailer magic if the format has one
+    ///
+    /// For formats with `TRAILER_MAGIC` set, a file not closed is not readable
+    pub async fn close(mut self) -> Result<()> {
+        self.file.write_all(Format::TRAILER_MAGIC).await?;
+        Ok(self.file.flush().await?)
+    }
+
+    /// Spawn a task appending each message received on `rx` as a data chunk
+    ///
+    /// Once every sender is dropped, the writer is closed and the task returns the
+    /// number of chunks appended. The task stops on the first write error and returns
+    /// it, dropping the messages still in the channel.
+    ///
+    /// This need to be called from within a tokio runtime
+    pub fn spawn_consumer(
+        mut self,
+        mut rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    ) -> tokio::task::JoinHandle<Result<u64>>
+    where
+        Format: Send + 'static,
+    {
+        tokio::spawn(async move {
+            let mut count = 0;
+            while let Some(data) = rx.recv().await {
+                self.append(&data).await?;
+                count += 1;
+            }
+            self.close().await?;
+            Ok(count)
+        })
+    }
+
     /// Append a new data chunk to this file
-    pub async fn append(&mut self, data: &[u8]) -> std::io::Result<()> {
-        write_chunk(&mut self.file, data).await
+    ///
+    /// The chunk may still be buffered when this returns, and is only durable after
+    /// `sync`.
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, `append_with_meta` need to be used instead
+    pub async fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.append_with_meta(&[], data).await
+    }
+
+    /// Flush the chunks appended so far to the OS
+    pub async fn flush(&mut self) -> Result<()> {
+        Ok(self.file.flush().await?)
+    }
+
+    /// Flush the chunks appended so far and sync them to the disk
+    ///
+    /// This is the async version of `crate::SeqDataWriter::sync`
+    pub async fn sync(&mut self) -> Result<()> {
+        self.flush().await?;
+        Ok(on_error(self.file.sync_data().await, "sync")?)
+    }
+
+    /// Write the manifest of this file
+    ///
+    /// This is the async version of `crate::SeqDataWriter::write_manifest`
+    pub async fn write_manifest(&mut self, manifest: &[u8]) -> Result<()> {
+        if !Format::MANIFEST {
+            return Err(SeqDataError::Unsupported("format does not have a manifest"));
+        }
+        if self.pos != 0 {
+            return Err(SeqDataError::ManifestAlreadyWritten);
+        }
+        let meta = vec![0; Format::CHUNK_META_SIZE];
+        on_error(
+            write_chunk::<Format>(&mut self.file, &meta, manifest).await,
+            "append",
+        )?;
+        let chunk_size = chunk_size::<Format>(manifest.len() as u64);
+        self.pos += chunk_size;
+        self.bytes_written += chunk_size;
+        Ok(())
+    }
+
+    fn check_append(&self) -> Result<()> {
+        if Format::MANIFEST && self.pos == 0 {
+            return Err(SeqDataError::ManifestMissing);
+        }
+        Ok(())
+    }
+
+    /// Append a new data chunk along with its metadata to this file
+    ///
+    /// The metadata need to fits the size of Format::CHUNK_META_SIZE
+    pub async fn append_with_meta(&mut self, meta: &[u8], data: &[u8]) -> Result<()> {
+        trace!(offset = self.pos, len = data.len(), "append");
+        on_error(self.check_append(), "append")?;
+        on_error(
+            write_chunk::<Format>(&mut self.file, meta, data).await,
+            "append",
+        )?;
+        let chunk_size = chunk_size::<Format>(data.len() as u64);
+        self.pos += chunk_size;
+        self.bytes_written += chunk_size;
+        self.chunks_written += 1;
+        Ok(())
+    }
+
+    /// Append a new data chunk to this file and sync it to the disk, returning the offset
+    /// of the chunk
+    ///
+    /// This is the async version of `SeqDataWriter::append_durable`, with the same
+    /// cost of a `sync_data` for every chunk.
+    pub async fn append_durable(&mut self, data: &[u8]) -> Result<u64> {
+        let offset = self.pos;
+        self.append(data).await?;
+        self.file.flush().await?;
+        on_error(self.file.sync_data().await, "sync")?;
+        Ok(offset)
+    }
+
+    /// Append each element of `chunks` as its own data chunk, returning their offsets
+    ///
+    /// This is equivalent to calling `append` in a loop
+    pub async fn append_slice_iter(&mut self, chunks: &[&[u8]]) -> Result<Vec<u64>> {
+        let mut offsets = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            offsets.push(self.pos);
+            self.append(chunk).await?;
+        }
+        Ok(offsets)
+    }
+
+    /// Append each element of `chunks` as its own data chunk, with a single write
+    ///
+    /// This is the async version of `crate::SeqDataWriter::append_many`
+    pub async fn append_many(&mut self, chunks: &[&[u8]]) -> Result<()> {
+        let size = chunks
+            .iter()
+            .map(|chunk| chunk_size::<Format>(chunk.len() as u64))
+            .sum::<u64>();
+        trace!(offset = self.pos, n = chunks.len(), size, "append many");
+        on_error(self.check_append(), "append")?;
+        let mut batch = Vec::with_capacity(size as usize);
+        for chunk in chunks {
+            on_error(
+                crate::write_chunk::<Format, _>(&mut batch, &[], chunk, false),
+                "append",
+            )?;
+        }
+        on_error(self.file.write_all(&batch).await, "append")?;
+        self.pos += size;
+        self.bytes_written += size;
+        self.chunks_written += chunks.len() as u64;
+        Ok(())
+    }
+
+    /// Append `n` chunks of the same data, returning their offsets
+    ///
+    /// This is the async version of `SeqDataWriter::append_repeated`
+    pub async fn append_repeated(&mut self, data: &[u8], n: usize) -> Result<Vec<u64>> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        trace!(offset = self.pos, len = data.len(), n, "append repeated");
+        on_error(self.check_append(), "append")?;
+        let size = chunk_size::<Format>(data.len() as u64);
+        let mut chunk = Vec::with_capacity(size as usize);
+        on_error(
+            crate::write_chunk::<Format, _>(&mut chunk, &[], data, false),
+            "append",
+        )?;
+        let per_write = (REPEAT_WRITE_SIZE / chunk.len()).clamp(1, n);
+        let batch = chunk.repeat(per_write);
+
+        let mut offsets = Vec::with_capacity(n);
+        while offsets.len() < n {
+            let count = (n - offsets.len()).min(per_write);
+            on_error(
+                self.file.write_all(&batch[..count * chunk.len()]).await,
+                "append",
+            )?;
+            for _ in 0..count {
+                offsets.push(self.pos);
+                self.pos += size;
+                self.bytes_written += size;
+                self.chunks_written += 1;
+            }
+        }
+        Ok(offsets)
+    }
+
+    /// Offset at which the next chunk will be appended
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Number of bytes appended through this writer, length prefixes included
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Number of chunks appended through this writer
+    pub fn chunks_written(&self) -> u64 {
+        self.chunks_written
+    }
+
+    /// Return the length of the data as reported by the file metadata
+    ///
+    /// Writes still in flight in the underlying tokio file are not accounted for
+    pub async fn current_len(&self) -> Result<u64> {
+        let meta = self.file.metadata().await?;
+        Ok(meta.len().saturating_sub(data_start::<Format>()))
+    }
+}
+
+impl crate::SeqData {
+    /// Create a new SeqData File at the location specified, containing every chunk of `chunks`
+    ///
+    /// This is the async version of `SeqData::write_all`, returning the number of
+    /// chunks written. For formats with `MANIFEST` set, the first chunk is written as
+    /// the manifest.
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub async fn write_all_stream<Format, S>(
+        path: impl AsRef<Path>,
+        header: &[u8],
+        chunks: S,
+    ) -> Result<u64>
+    where
+        Format: SeqDataFormat,
+        S: Stream,
+        S::Item: AsRef<[u8]>,
+    {
+        let mut writer = SeqDataWriter::<Format>::create(path, header).await?;
+        let mut chunks = std::pin::pin!(chunks);
+        let mut count = 0;
+        while let Some(chunk) = std::future::poll_fn(|cx| chunks.as_mut().poll_next(cx)).await {
+            if Format::MANIFEST && count == 0 {
+                writer.write_manifest(chunk.as_ref()).await?;
+            } else {
+                writer.append(chunk.as_ref()).await?;
+            }
+            count += 1;
+        }
+        writer.close().await?;
+        Ok(count)
     }
 }
 
@@ -94,82 +388,358 @@ pub struct SeqDataReader<Format: SeqDataFormat> {
     buf_reader: tokio::io::BufReader<File>,
     pos: u64,
     len: u64,
+    pending: PendingChunk,
+    max_interrupts: usize,
+    manifest: Option<Vec<u8>>,
     phantom: PhantomData<Format>,
 }
 
+/// Open the file at `path` left empty by an interrupted creation, or fail with `exists`
+/// if it has some content
+async fn reopen_empty(path: &Path, exists: std::io::Error) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .append(true)
+        .open(path)
+        .await?;
+    if file.metadata().await?.len() != 0 {
+        return Err(exists.into());
+    }
+    Ok(file)
+}
+
+// the file is opened in append mode, so neither the count of chunks nor the dirty flag
+// can be updated in place
+fn check_append_only<Format: SeqDataFormat>() -> Result<()> {
+    if Format::CHUNK_COUNT {
+        return Err(SeqDataError::Unsupported(
+            "writing a format with a count of chunks is not supported",
+        ));
+    }
+    if Format::DIRTY_FLAG.is_some() {
+        return Err(SeqDataError::Unsupported(
+            "writing a format with a dirty flag is not supported",
+        ));
+    }
+    Ok(())
+}
+
+async fn write_magic_and_header<Format: SeqDataFormat>(
+    file: &mut File,
+    header: &[u8],
+) -> Result<()> {
+    file.write_all(Format::MAGIC).await?;
+    file.write_all(header).await?;
+    // tokio buffers the writes, so errors are only reported by the flush
+    Ok(file.flush().await?)
+}
+
 async fn read_magic_and_header<Format: SeqDataFormat>(
+    format: PhantomData<Format>,
+    file: &mut File,
+) -> Result<Vec<u8>> {
+    on_error(read_magic_and_header_raw(format, file).await, "header")
+}
+
+async fn read_magic_and_header_raw<Format: SeqDataFormat>(
     _format: PhantomData<Format>,
     file: &mut File,
-) -> std::io::Result<Vec<u8>> {
+) -> Result<Vec<u8>> {
     // try to read the magic
     const MAGIC_READ_BUF_SIZE: usize = 16;
     let mut magic_read_buf = [0u8; MAGIC_READ_BUF_SIZE];
     let mut magic_slice = Format::MAGIC;
     while !magic_slice.is_empty() {
-        let sz = Format::MAGIC.len().min(MAGIC_READ_BUF_SIZE);
-        let rd = file.read(&mut magic_read_buf[0..sz]).await?;
-        if rd == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::UnexpectedEof,
-                "unexpected EOF in magic reading",
-            ));
-        }
+        let sz = magic_slice.len().min(MAGIC_READ_BUF_SIZE);
+        let rd = read_up_to(file, &mut magic_read_buf[0..sz]).await?;
         if magic_slice[0..rd] != magic_read_buf[0..rd] {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "magic do not match expected value",
-            ));
+            return Err(SeqDataError::MagicMismatch);
+        }
+        if rd < sz {
+            let len = Format::MAGIC.len() - magic_slice.len() + rd;
+            return Err(file_too_short::<Format>(len as u64));
         }
         magic_slice = &magic_slice[rd..];
     }
 
     let mut header = vec![0u8; Format::HEADER_SIZE];
-    file.read_exact(&mut header).await?;
+    let rd = read_up_to(file, &mut header).await?;
+    if rd < header.len() {
+        return Err(file_too_short::<Format>((Format::MAGIC.len() + rd) as u64));
+    }
+    validate_header::<Format>(&header)?;
+    // skip the count of chunks, which is only maintained by the blocking writer
+    if Format::CHUNK_COUNT {
+        let rd = read_up_to(file, &mut [0; 8]).await?;
+        if rd < 8 {
+            let len = Format::MAGIC.len() + Format::HEADER_SIZE + rd;
+            return Err(file_too_short::<Format>(len as u64));
+        }
+    }
     Ok(header)
 }
 
+type NextOwned<Format> =
+    Pin<Box<dyn Future<Output = (SeqDataReader<Format>, Option<Result<(u64, Vec<u8>)>>)> + Send>>;
+
+async fn next_owned<Format: SeqDataFormat>(
+    mut reader: SeqDataReader<Format>,
+) -> (SeqDataReader<Format>, Option<Result<(u64, Vec<u8>)>>) {
+    let next = reader.next().await;
+    (reader, next)
+}
+
+// stream owning the reader, moved in and out of the future reading the next block
+struct ReaderStream<Format: SeqDataFormat> {
+    pending: Option<NextOwned<Format>>,
+}
+
+impl<Format: SeqDataFormat + Send + 'static> Stream for ReaderStream<Format> {
+    type Item = Result<(u64, Vec<u8>)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Some(pending) = self.pending.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match pending.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready((reader, next)) => {
+                self.pending = next.as_ref().map(|_| Box::pin(next_owned(reader)) as _);
+                Poll::Ready(next)
+            }
+        }
+    }
+}
+
 impl<Format: SeqDataFormat> SeqDataReader<Format> {
     /// Open a SeqData for reading
-    pub async fn open<P: AsRef<Path>>(path: P) -> std::io::Result<(Self, Vec<u8>)> {
-        let mut file = File::open(path).await?;
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open for reading");
+        let mut file = on_error(File::open(path).await, "open")?;
 
         let phantom = PhantomData;
         let len = get_file_length(phantom, &mut file).await?;
         let header = read_magic_and_header(phantom, &mut file).await?;
+        debug!(len, "opened for reading");
 
         let buf_reader = tokio::io::BufReader::with_capacity(1024 * 1024, file);
-        Ok((
-            SeqDataReader {
-                buf_reader,
-                pos: 0,
-                len,
-                phantom,
-            },
-            header,
-        ))
+        let mut reader = SeqDataReader {
+            buf_reader,
+            pos: 0,
+            len,
+            pending: PendingChunk::default(),
+            max_interrupts: DEFAULT_MAX_INTERRUPTS,
+            manifest: None,
+            phantom,
+        };
+        if Format::MANIFEST {
+            match reader.next().await {
+                None => return Err(SeqDataError::ManifestMissing),
+                Some(Err(e)) => return Err(e),
+                Some(Ok((_, mut manifest))) => {
+                    reader.manifest = Some(manifest.split_off(Format::CHUNK_META_SIZE))
+                }
+            }
+        }
+        Ok((reader, header))
+    }
+
+    /// Return the manifest of this file, for formats with `MANIFEST` set
+    pub fn manifest(&self) -> Option<&[u8]> {
+        self.manifest.as_deref()
+    }
+
+    /// Set the number of consecutive `Interrupted` errors tolerated from the
+    /// underlying file before giving up on a read, `DEFAULT_MAX_INTERRUPTS` by default
+    pub fn with_max_interrupts(mut self, max_interrupts: usize) -> Self {
+        self.max_interrupts = max_interrupts;
+        self
     }
 
     pub fn len(&self) -> u64 {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Offset that the next chunk appended to this file would report
+    ///
+    /// This is the same as `len`, as the length of the data when the file was opened
+    /// is where a writer appends the next chunk.
+    pub fn append_position(&self) -> u64 {
+        self.len
+    }
+
     pub fn position(&self) -> u64 {
         self.pos
     }
 
+    /// Move the reader to the chunk offset specified, e.g. a checkpoint taken with
+    /// `position`, so that the next call to `next` returns the chunk at this offset
+    ///
+    /// The offset need to be a chunk boundary, or `len` to move at the end of the data.
+    /// Any chunk partially read by a cancelled `next` is discarded.
+    pub async fn seek_to_offset(&mut self, pos: u64) -> Result<()> {
+        if pos > self.len {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: pos,
+                len: self.len,
+            });
+        }
+        // seeking through the BufReader, and not the file under it, discards the buffer
+        self.buf_reader
+            .seek(std::io::SeekFrom::Start(data_start::<Format>() + pos))
+            .await?;
+        self.pending = PendingChunk::default();
+        self.pos = pos;
+        Ok(())
+    }
+
     /// Return the next block along with the current offset if it exists, or None if
     /// reached the end of file.
-    pub async fn next(&mut self) -> Option<std::io::Result<(u64, Vec<u8>)>> {
-        match read_chunk(&mut self.buf_reader).await {
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, the block starts with the chunk metadata
+    ///
+    /// This is cancel safe: if the future is dropped before completion, the
+    /// partially read chunk is kept and the next call resumes reading it.
+    pub async fn next(&mut self) -> Option<Result<(u64, Vec<u8>)>> {
+        // the data is followed by the trailer magic or padding and not by the end of file
+        if data_followed::<Format>() && self.pos >= self.len {
+            return None;
+        }
+        let available = self.len.saturating_sub(self.pos);
+        match self
+            .pending
+            .read::<Format, _>(&mut self.buf_reader, available, self.max_interrupts)
+            .await
+        {
             None => None,
-            Some(Err(e)) => Some(Err(e)),
+            Some(Err(e)) => Some(on_error(Err(e), "next")),
             Some(Ok(buf)) => {
+                let buf = match on_error(
+                    check_trailer::<Format>(buf).map_err(|e| corrupted_at(e, self.pos)),
+                    "next",
+                ) {
+                    Ok(buf) => buf,
+                    Err(e) => return Some(Err(e)),
+                };
                 let current_pos = self.pos;
-                self.pos += size_of::<PrefixLength>() as u64 + buf.len() as u64;
+                self.pos += chunk_size::<Format>((buf.len() - Format::CHUNK_META_SIZE) as u64);
                 Some(Ok((current_pos, buf)))
             }
         }
     }
+
+    /// Turn this reader into a stream of the blocks along with their offset
+    ///
+    /// The stream owns the reader, so it can be returned from functions or moved
+    /// into a spawned task.
+    pub fn into_stream(self) -> impl Stream<Item = Result<(u64, Vec<u8>)>> + Send
+    where
+        Format: Send + 'static,
+    {
+        ReaderStream {
+            pending: Some(Box::pin(next_owned(self))),
+        }
+    }
+
+    /// Same as `next` but return the block in a shared buffer, that can be cheaply
+    /// cloned to hand the same block to multiple consumers
+    pub async fn next_shared(&mut self) -> Option<Result<(u64, Arc<[u8]>)>> {
+        self.next()
+            .await
+            .map(|r| r.map(|(offset, data)| (offset, Arc::from(data))))
+    }
+
+    /// Return the next block along with the current offset, its metadata and its data
+    /// separately, or None if reached the end of file.
+    ///
+    /// This is cancel safe, like `next`
+    pub async fn next_with_meta(&mut self) -> Option<Result<MetaChunk>> {
+        self.next().await.map(|r| {
+            r.map(|(offset, mut meta)| {
+                let data = meta.split_off(Format::CHUNK_META_SIZE);
+                (offset, meta, data)
+            })
+        })
+    }
+
+    /// Same as `next` but give up after the duration specified
+    ///
+    /// On timeout, an error of kind `TimedOut` is returned, and the reader stays
+    /// usable: the next call resumes where the timed out read stopped.
+    pub async fn next_timeout(&mut self, dur: Duration) -> Option<Result<(u64, Vec<u8>)>> {
+        match tokio::time::timeout(dur, self.next()).await {
+            Ok(r) => r,
+            Err(_) => Some(Err(SeqDataError::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "timed out reading chunk",
+            )))),
+        }
+    }
+
+    /// Count the remaining chunks, seeking past their data instead of reading it
+    pub async fn count_chunks(&mut self) -> Result<u64> {
+        Ok(self.stats().await?.count)
+    }
+
+    /// Summarize the remaining chunks, seeking past their data instead of reading it
+    pub async fn stats(&mut self) -> Result<Stats> {
+        let mut stats = Stats::default();
+        while let Some(r) = self.skip_chunk().await {
+            let (_, len) = r?;
+            stats.min_len = if stats.count == 0 {
+                len
+            } else {
+                stats.min_len.min(len)
+            };
+            stats.max_len = stats.max_len.max(len);
+            stats.total_len += len;
+            stats.count += 1;
+        }
+        Ok(stats)
+    }
+
+    /// Skip over the next block without reading its data, returning its offset and length
+    async fn skip_chunk(&mut self) -> Option<Result<(u64, u64)>> {
+        if data_followed::<Format>() && self.pos >= self.len {
+            return None;
+        }
+        if self.pending.len_read > 0 {
+            return Some(Err(SeqDataError::InvalidInput(
+                "cannot skip a partially read chunk".into(),
+            )));
+        }
+        let mut lenbuf = [0; MAX_PREFIX_SIZE];
+        let lenbuf = &mut lenbuf[..prefix_size::<Format>()];
+        match optional_read_exact_bounded(&mut self.buf_reader, lenbuf, self.max_interrupts).await {
+            None => None,
+            Some(Err(e)) => Some(Err(e.into())),
+            Some(Ok(())) => {
+                let (len, _) = split_prefix::<Format>(decode_prefix::<Format>(lenbuf));
+                let end = match chunk_end::<Format>(self.pos, len, self.len) {
+                    Ok(end) => end,
+                    Err(e) => return Some(Err(e)),
+                };
+                // only seek when the buffer doesn't cover the chunk, as seeking discards it
+                let skip = len as usize + framing_size::<Format>();
+                if skip <= self.buf_reader.buffer().len() {
+                    self.buf_reader.consume(skip);
+                } else if let Err(e) = self
+                    .buf_reader
+                    .seek(std::io::SeekFrom::Current(skip as i64))
+                    .await
+                {
+                    return Some(Err(e.into()));
+                }
+                let current_pos = self.pos;
+                self.pos = end;
+                Some(Ok((current_pos, len)))
+            }
+        }
+    }
 }
 
 /// Seq Data Reader with seek
@@ -182,14 +752,15 @@ pub struct SeqDataReaderSeek<Format: SeqDataFormat> {
 
 impl<Format: SeqDataFormat> SeqDataReaderSeek<Format> {
     /// Open a new Seq Data seeker
-    pub async fn open<P: AsRef<Path>>(path: P) -> std::io::Result<(Self, Vec<u8>)> {
-        let mut handle = File::open(path).await?;
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<u8>)> {
+        debug!(path = %path.as_ref().display(), "open for seeking");
+        let mut handle = on_error(File::open(path).await, "open")?;
 
         let phantom = PhantomData;
         let len = get_file_length(phantom, &mut handle).await?;
         let header = read_magic_and_header(phantom, &mut handle).await?;
 
-        let start = handle.seek(std::io::SeekFrom::Current(0)).await?;
+        let start = handle.stream_position().await?;
 
         Ok((
             Self {
@@ -204,8 +775,18 @@ impl<Format: SeqDataFormat> SeqDataReaderSeek<Format> {
 
     /// Return the next block along with the current offset if it exists, or None if
     /// reached the end of file.
-    pub async fn next(&mut self) -> std::io::Result<Vec<u8>> {
-        read_chunk(&mut self.handle).await.unwrap()
+    pub async fn next(&mut self) -> Result<Vec<u8>> {
+        let pos = self.handle.stream_position().await? - self.start;
+        read_chunk::<Format, _>(&mut self.handle, self.len.saturating_sub(pos))
+            .await
+            .unwrap()
+    }
+
+    /// Offset that the next chunk appended to this file would report
+    ///
+    /// This is the length of the data when the file was opened.
+    pub fn append_position(&self) -> u64 {
+        self.len
     }
 
     /// Return the next block at the offset specified
@@ -213,80 +794,276 @@ impl<Format: SeqDataFormat> SeqDataReaderSeek<Format> {
     /// Note that if the position specified is not a valid boundary,
     /// then arbitrary invalid stuff might be returns, or some Err
     /// related to reading data
-    pub async fn next_at(&mut self, pos: u64) -> std::io::Result<Vec<u8>> {
+    pub async fn next_at(&mut self, pos: u64) -> Result<Vec<u8>> {
         if pos >= self.len {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "trying to access data at {} but data length {}",
-                    pos, self.len
-                ),
-            ));
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: pos,
+                len: self.len,
+            });
         }
 
         let seek = self.start + pos;
         self.handle.seek(std::io::SeekFrom::Start(seek)).await?;
         self.next().await
     }
+
+    /// Read the blocks at each of the offsets specified concurrently, returning them
+    /// in the same order as `offsets`
+    ///
+    /// The reads are positional reads on a single duplicate of the file descriptor,
+    /// run on the blocking thread pool with at most `READ_MANY_PARALLELISM` of them
+    /// in flight. On unix, the position of this reader is left untouched. On the first error,
+    /// the pending reads are cancelled and the error is returned.
+    pub async fn read_many(&self, offsets: &[u64]) -> Result<Vec<Vec<u8>>>
+    where
+        Format: 'static,
+    {
+        if let Some(pos) = offsets.iter().find(|pos| **pos >= self.len) {
+            return Err(SeqDataError::OffsetOutOfRange {
+                offset: *pos,
+                len: self.len,
+            });
+        }
+
+        let file = Arc::new(self.handle.try_clone().await?.into_std().await);
+        let mut results = vec![Vec::new(); offsets.len()];
+        let mut tasks = tokio::task::JoinSet::new();
+        let mut next = 0;
+        loop {
+            while next < offsets.len() && tasks.len() < READ_MANY_PARALLELISM {
+                let file = file.clone();
+                let offset = self.start + offsets[next];
+                let end = self.start + self.len;
+                let index = next;
+                tasks.spawn_blocking(move || {
+                    read_chunk_at::<Format, _>(&*file, offset, end).map(|data| (index, data))
+                });
+                next += 1;
+            }
+            match tasks.join_next().await {
+                None => break,
+                Some(joined) => {
+                    let (index, data) = joined.map_err(std::io::Error::other)??;
+                    results[index] = data;
+                }
+            }
+        }
+        Ok(results)
+    }
 }
 
-type PrefixLength = u32;
+/// Maximum number of reads in flight in `SeqDataReaderSeek::read_many`
+pub const READ_MANY_PARALLELISM: usize = 16;
 
-async fn read_chunk<R: AsyncRead + std::marker::Unpin>(
-    file: &mut R,
-) -> Option<std::io::Result<Vec<u8>>> {
-    let mut lenbuf = [0; size_of::<PrefixLength>()];
-    // try to read the length, if the length return a none, we just expect
-    // having reached the end of the stream then
-    match optional_read_exact(file, &mut lenbuf).await {
-        None => None,
-        Some(Err(e)) => Some(Err(e)),
-        Some(Ok(())) => {
-            let len = PrefixLength::from_le_bytes(lenbuf);
-
-            // create a buffer of the prefix length 'len' and read all data
-            let mut out = vec![0; len as usize];
-            match file.read_exact(&mut out).await {
-                Err(e) => Some(Err(e)),
-                Ok(_sz) => Some(Ok(out)),
+/// Default number of consecutive `Interrupted` errors tolerated while reading
+pub const DEFAULT_MAX_INTERRUPTS: usize = 1024;
+
+/// State of a chunk being read, so that reading can be resumed after a cancellation
+#[derive(Default)]
+struct PendingChunk {
+    lenbuf: [u8; MAX_PREFIX_SIZE],
+    len_read: usize,
+    data: Option<Vec<u8>>,
+    data_read: usize,
+}
+
+impl PendingChunk {
+    /// Read the chunk, `available` being the number of bytes from its start to the end
+    /// of the data
+    async fn read<Format: SeqDataFormat, R: AsyncRead + std::marker::Unpin>(
+        &mut self,
+        file: &mut R,
+        available: u64,
+        max_interrupts: usize,
+    ) -> Option<Result<Vec<u8>>> {
+        let result = self
+            .read_resumable::<Format, _>(file, available, max_interrupts)
+            .await;
+        // on completion, either successful or not, start afresh on the next chunk
+        *self = PendingChunk::default();
+        result
+    }
+
+    // every await point of this function only use cancel safe operations,
+    // and all progress is recorded in self
+    async fn read_resumable<Format: SeqDataFormat, R: AsyncRead + std::marker::Unpin>(
+        &mut self,
+        file: &mut R,
+        available: u64,
+        max_interrupts: usize,
+    ) -> Option<Result<Vec<u8>>> {
+        let mut interrupts = 0;
+        let prefix_size = prefix_size::<Format>();
+        while self.len_read < prefix_size {
+            match file
+                .read(&mut self.lenbuf[self.len_read..prefix_size])
+                .await
+            {
+                // we just expect having reached the end of the stream then
+                Ok(0) if self.len_read == 0 => return None,
+                Ok(0) => {
+                    return Some(Err(SeqDataError::TruncatedChunk {
+                        len: None,
+                        available: self.len_read as u64,
+                    }))
+                }
+                Ok(n) => {
+                    self.len_read += n;
+                    interrupts = 0;
+                }
+                Err(ref e)
+                    if e.kind() == std::io::ErrorKind::Interrupted
+                        && interrupts < max_interrupts =>
+                {
+                    interrupts += 1;
+                }
+                Err(e) => return Some(Err(e.into())),
             }
         }
+
+        // the chunk metadata and trailer, if any, are read along with the data
+        let (data_len, _) = split_prefix::<Format>(decode_prefix::<Format>(&self.lenbuf));
+        // checked before allocating from the length
+        if let Err(e) = chunk_end::<Format>(0, data_len, available) {
+            return Some(Err(e));
+        }
+        let len = data_len as usize + framing_size::<Format>();
+        let out = self.data.get_or_insert_with(|| vec![0; len]);
+        while self.data_read < len {
+            match file.read(&mut out[self.data_read..]).await {
+                Ok(0) => {
+                    return Some(Err(SeqDataError::TruncatedChunk {
+                        len: Some(data_len),
+                        available: self.data_read as u64,
+                    }))
+                }
+                Ok(n) => {
+                    self.data_read += n;
+                    interrupts = 0;
+                }
+                Err(ref e)
+                    if e.kind() == std::io::ErrorKind::Interrupted
+                        && interrupts < max_interrupts =>
+                {
+                    interrupts += 1;
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        self.data.take().map(Ok)
     }
 }
 
-async fn write_chunk(file: &mut File, data: &[u8]) -> std::io::Result<()> {
-    let max = PrefixLength::MAX as usize;
-    assert!(data.len() <= max);
-    let len: u32 = data.len() as PrefixLength;
-    let header = len.to_le_bytes();
+async fn read_chunk<Format: SeqDataFormat, R: AsyncRead + std::marker::Unpin>(
+    file: &mut R,
+    available: u64,
+) -> Option<Result<Vec<u8>>> {
+    PendingChunk::default()
+        .read::<Format, _>(file, available, DEFAULT_MAX_INTERRUPTS)
+        .await
+        .map(|r| r.and_then(check_trailer::<Format>))
+}
+
+async fn write_chunk<Format: SeqDataFormat>(
+    file: &mut File,
+    meta: &[u8],
+    data: &[u8],
+) -> Result<()> {
+    check_chunk_meta::<Format>(meta)?;
+    check_chunk_len::<Format>(data.len())?;
+    let header = encode_prefix::<Format>(data.len(), false);
     file.write_all(&header).await?;
+    file.write_all(meta).await?;
     file.write_all(data).await?;
+    file.write_all(&chunk_checksum::<Format>(meta, data))
+        .await?;
+    if Format::TRAILING_LENGTH {
+        file.write_all(&header).await?;
+    }
+    file.write_all(Format::SENTINEL).await?;
     Ok(())
 }
 
 async fn get_file_length<Format: SeqDataFormat>(
     _phantom: PhantomData<Format>,
     file: &mut File,
-) -> std::io::Result<u64> {
+) -> Result<u64> {
     let meta = file.metadata().await?;
-    let total_len = meta.len();
+    let mut total_len = meta.len();
 
-    let minimum_size = Format::MAGIC.len() as u64 + Format::HEADER_SIZE as u64;
+    let minimum_size = data_start::<Format>() + Format::TRAILER_MAGIC.len() as u64;
     if total_len < minimum_size {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "file not contains enough bytes for magic and header",
-        ));
+        return on_error(Err(file_too_short::<Format>(total_len)), "header");
+    }
+    if Format::PADDING_LENGTH.is_some() {
+        let mut header = vec![0; Format::HEADER_SIZE];
+        file.seek(std::io::SeekFrom::Start(Format::MAGIC.len() as u64))
+            .await?;
+        file.read_exact(&mut header).await?;
+        file.seek(std::io::SeekFrom::Start(0)).await?;
+        total_len = on_error(strip_padding::<Format>(&header, total_len), "header")?;
+    }
+    if !Format::TRAILER_MAGIC.is_empty() {
+        on_error(
+            check_trailer_magic::<Format>(file, total_len).await,
+            "header",
+        )?;
+        file.seek(std::io::SeekFrom::Start(0)).await?;
     }
     Ok(total_len - minimum_size)
 }
 
+/// Check that the file of `total_len` bytes ends with the trailer magic
+async fn check_trailer_magic<Format: SeqDataFormat>(file: &mut File, total_len: u64) -> Result<()> {
+    let trailer_len = Format::TRAILER_MAGIC.len() as u64;
+    if total_len < data_start::<Format>() + trailer_len {
+        return Err(file_too_short::<Format>(total_len));
+    }
+    let mut trailer = vec![0; Format::TRAILER_MAGIC.len()];
+    file.seek(std::io::SeekFrom::Start(total_len - trailer_len))
+        .await?;
+    file.read_exact(&mut trailer).await?;
+    if trailer != Format::TRAILER_MAGIC {
+        return Err(SeqDataError::TrailerMismatch);
+    }
+    Ok(())
+}
+
 /// this is a version of read_exact that returns a None if the stream is empty
 pub async fn optional_read_exact<R: AsyncRead + ?Sized + std::marker::Unpin>(
+    this: &mut R,
+    buf: &mut [u8],
+) -> Option<std::io::Result<()>> {
+    optional_read_exact_bounded(this, buf, DEFAULT_MAX_INTERRUPTS).await
+}
+
+/// read into `buf` until it is full or the end of the stream is reached,
+/// returning the number of bytes read
+async fn read_up_to<R: AsyncRead + ?Sized + std::marker::Unpin>(
+    this: &mut R,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut read_bytes = 0;
+    while read_bytes < buf.len() {
+        match this.read(&mut buf[read_bytes..]).await {
+            Ok(0) => break,
+            Ok(n) => read_bytes += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read_bytes)
+}
+
+/// this is a version of `optional_read_exact` that gives up after `max_interrupts`
+/// consecutive `Interrupted` errors, returning the last one
+pub async fn optional_read_exact_bounded<R: AsyncRead + ?Sized + std::marker::Unpin>(
     this: &mut R,
     mut buf: &mut [u8],
+    max_interrupts: usize,
 ) -> Option<std::io::Result<()>> {
     let mut read_bytes = 0;
+    let mut interrupts = 0;
     while !buf.is_empty() {
         match this.read(buf).await {
             Ok(0) => break,
@@ -294,8 +1071,13 @@ pub async fn optional_read_exact<R: AsyncRead + ?Sized + std::marker::Unpin>(
                 let tmp = buf;
                 buf = &mut tmp[n..];
                 read_bytes += n;
+                interrupts = 0;
+            }
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::Interrupted && interrupts < max_interrupts =>
+            {
+                interrupts += 1;
             }
-            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
             Err(e) => return Some(Err(e)),
         }
     }
@@ -310,3 +1092,45 @@ pub async fn optional_read_exact<R: AsyncRead + ?Sized + std::marker::Unpin>(
         Some(Ok(()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader failing every read with `Interrupted`, counting the reads
+    struct AlwaysInterrupted {
+        reads: usize,
+    }
+
+    impl AsyncRead for AlwaysInterrupted {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            self.reads += 1;
+            Poll::Ready(Err(std::io::ErrorKind::Interrupted.into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn interrupted_reads_give_up_after_bound() {
+        let mut reader = AlwaysInterrupted { reads: 0 };
+        let mut buf = [0; 4];
+        let r = optional_read_exact_bounded(&mut reader, &mut buf, 5).await;
+        let err = r.unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert_eq!(reader.reads, 6);
+    }
+
+    #[tokio::test]
+    async fn interrupted_chunk_read_gives_up_after_bound() {
+        let mut reader = AlwaysInterrupted { reads: 0 };
+        let r = PendingChunk::default()
+            .read::<crate::NoMagicNoHeader, _>(&mut reader, u64::MAX, 3)
+            .await;
+        let err = r.unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert_eq!(reader.reads, 4);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::Cursor;
+
+/// Positional reads from a backend, used by `SeqDataReaderSeek`
+///
+/// This is implemented for `File`, for `Cursor` over any byte buffer, which covers
+/// in-memory data, and for `memmap2::Mmap` with the `mmap` feature.
+pub trait ReadAt {
+    /// Read into `buf` from the offset specified, returning the number of bytes read
+    ///
+    /// This doesn't use any cursor, so it can be called concurrently from a shared reference
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize>;
+
+    /// Return the total size of the backend in bytes
+    fn size(&self) -> std::io::Result<u64>;
+
+    /// Read exactly `buf.len()` bytes from the offset specified
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReadAt for File {
+    #[cfg(unix)]
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+
+    // this moves the file cursor on windows
+    #[cfg(windows)]
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn read_at(&self, _buf: &mut [u8], _offset: u64) -> std::io::Result<usize> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "positional reads are not supported on this platform",
+        ))
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl<T: AsRef<[u8]>> ReadAt for Cursor<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        Ok(read_slice_at(self.get_ref().as_ref(), buf, offset))
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.get_ref().as_ref().len() as u64)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl ReadAt for memmap2::Mmap {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        Ok(read_slice_at(self, buf, offset))
+    }
+
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+fn read_slice_at(data: &[u8], buf: &mut [u8], offset: u64) -> usize {
+    let start = data.len().min(offset.try_into().unwrap_or(usize::MAX));
+    let n = buf.len().min(data.len() - start);
+    buf[..n].copy_from_slice(&data[start..start + n]);
+    n
+}
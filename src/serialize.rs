@@ -0,0 +1,36 @@
+use std::io::{Read, Seek, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::format::SeqDataFormat;
+use crate::{Result, SeqDataError, SeqDataReader, SeqDataStreamWriter};
+
+// encoding to and decoding from memory, so any error is about the value or the data
+fn encoding_error(e: bincode::Error) -> SeqDataError {
+    SeqDataError::Encoding(e)
+}
+
+impl<W: Write, Format: SeqDataFormat> SeqDataStreamWriter<W, Format> {
+    /// Append a new data chunk holding the bincode encoding of `value`
+    pub fn append_serialize<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let data = bincode::serialize(value).map_err(encoding_error)?;
+        self.append(&data)
+    }
+}
+
+impl<Format: SeqDataFormat, R: Read + Seek> SeqDataReader<Format, R> {
+    /// Turn this reader into an iterator decoding each block from bincode
+    ///
+    /// For formats with `CHUNK_META_SIZE` set, only the data following the metadata
+    /// is decoded. Decoding errors are reported as `SeqDataError::Encoding`.
+    pub fn iter_deserialize<T: DeserializeOwned>(mut self) -> impl Iterator<Item = Result<T>> {
+        std::iter::from_fn(move || {
+            self.next().map(|r| {
+                r.and_then(|(_, data)| {
+                    bincode::deserialize(&data[Format::CHUNK_META_SIZE..]).map_err(encoding_error)
+                })
+            })
+        })
+    }
+}
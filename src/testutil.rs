@@ -0,0 +1,76 @@
+//! Helpers for testing format definitions
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::format::SeqDataFormat;
+use crate::{SeqDataReader, SeqDataWriter};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary directory, unique to the process and the call, removed with its content
+/// when dropped so that it's cleaned up even on panic
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a new empty temporary directory
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "seq-data-file-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    /// Path of the directory
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Path of the entry `name` in the directory
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Default for TempDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Write `chunks` to a temporary SeqData file with the header specified, and return
+/// the chunks as read back from this file
+///
+/// Formats with a manifest get an empty one. The temporary file is removed before
+/// returning. This panics on any I/O error, or if the header read back doesn't match
+/// the one written.
+pub fn roundtrip<Format: SeqDataFormat>(header: &[u8], chunks: &[&[u8]]) -> Vec<Vec<u8>> {
+    let dir = TempDir::new();
+    let path = dir.join("roundtrip.sdf");
+
+    let mut writer = SeqDataWriter::<Format>::create(&path, header).unwrap();
+    if Format::MANIFEST {
+        writer.write_manifest(&[]).unwrap();
+    }
+    for chunk in chunks {
+        writer.append(chunk).unwrap();
+    }
+    writer.close().unwrap();
+
+    let (mut reader, read_header) = SeqDataReader::<Format>::open(&path).unwrap();
+    assert_eq!(read_header, header, "header read back doesn't match");
+    let mut out = Vec::with_capacity(chunks.len());
+    while let Some(chunk) = reader.next() {
+        let (_, data) = chunk.unwrap();
+        out.push(data);
+    }
+    out
+}
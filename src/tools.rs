@@ -0,0 +1,538 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::format::{DynSeqDataFormat, SeqDataFormat};
+use crate::{
+    data_start, get_file_length, read_magic_and_header, Result, SeqDataReader, SeqDataWriter,
+};
+
+/// Utilities operating on whole SeqData files
+pub struct SeqData;
+
+/// Position and size of a chunk in a SeqData
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkMeta {
+    /// Index of the chunk, starting at 0
+    pub index: u64,
+    /// Offset of the chunk in the data
+    pub offset: u64,
+    /// Length of the chunk data, length prefix excluded
+    pub len: u64,
+}
+
+/// Summary of the chunks of a SeqData
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of chunks
+    pub count: u64,
+    /// Total length of the chunks data, length prefixes excluded
+    pub total_len: u64,
+    /// Length of the smallest chunk, 0 if there's no chunk
+    pub min_len: u64,
+    /// Length of the biggest chunk, 0 if there's no chunk
+    pub max_len: u64,
+}
+
+/// Time taken by the calls to `SeqDataReader::next`, as returned by
+/// `SeqDataReader::timing_summary`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingSummary {
+    /// Number of chunks read
+    pub count: u64,
+    /// Total time reading the chunks
+    pub total: Duration,
+    /// Time reading the fastest chunk, 0 if there's no chunk
+    pub min: Duration,
+    /// Time reading the slowest chunk, 0 if there's no chunk
+    pub max: Duration,
+}
+
+impl TimingSummary {
+    /// Average time reading a chunk, 0 if there's no chunk
+    pub fn avg(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        self.min = if self.count == 0 {
+            elapsed
+        } else {
+            self.min.min(elapsed)
+        };
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+        self.count += 1;
+    }
+}
+
+/// Structure of a SeqData file, as returned by `SeqData::describe`
+#[derive(Debug, Clone)]
+pub struct Description {
+    /// Magic bytes of the file
+    pub magic: Vec<u8>,
+    /// Header of the file
+    pub header: Vec<u8>,
+    /// Length of the data, magic and header excluded
+    pub data_len: u64,
+    /// Chunks found in the data
+    pub chunks: Vec<ChunkMeta>,
+    /// Whether all the chunks have been inspected, or the limit has been reached
+    pub complete: bool,
+}
+
+/// Summary of a valid SeqData, as returned by `SeqData::verify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Header of the file
+    pub header: Vec<u8>,
+    /// Number of chunks
+    pub chunks: u64,
+    /// Length of the data, magic and header excluded
+    pub data_len: u64,
+}
+
+/// Outcome of `SeqData::verify_against`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyResult {
+    /// Every chunk matches its expected checksum
+    Valid,
+    /// The first chunk not matching its expected checksum
+    Mismatch {
+        /// Index of the chunk, starting at 0
+        index: u64,
+        /// Offset of the chunk in the data
+        offset: u64,
+        /// Checksum expected for the chunk
+        expected: u32,
+        /// Checksum of the chunk data
+        actual: u32,
+    },
+    /// All the checksums match, but the file doesn't have the expected number of chunks
+    CountMismatch {
+        /// Number of checksums given
+        expected: u64,
+        /// Number of chunks in the file
+        actual: u64,
+    },
+}
+
+impl SeqData {
+    /// Remove the file at the location specified, only if it is a SeqData of this format
+    ///
+    /// The magic and header are checked before removing the file, and an error is
+    /// returned without removing anything if they don't match. Note that this only
+    /// protects against removing an unrelated file for formats with a magic.
+    pub fn remove<Format: SeqDataFormat>(path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        check_format::<Format>(path)?;
+        Ok(std::fs::remove_file(path)?)
+    }
+
+    /// Swap the SeqData files at the two locations specified, after checking that both
+    /// are SeqData of this format
+    ///
+    /// On Linux, this is done atomically with `renameat2(RENAME_EXCHANGE)`. Elsewhere, or if
+    /// the filesystem doesn't support it, this falls back to three renames through a
+    /// temporary name next to `a`, and a reader may briefly see `a` missing. If the
+    /// second rename fails, `a` is moved back in place before returning the error.
+    pub fn swap<Format: SeqDataFormat>(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Result<()> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        check_format::<Format>(a)?;
+        check_format::<Format>(b)?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        match rename_exchange(a, b) {
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP)
+                ) => {}
+            r => return Ok(r?),
+        }
+        Ok(swap_by_rename(a, b)?)
+    }
+
+    /// Create a new SeqData File at the location specified, containing every chunk of `chunks`
+    ///
+    /// Return the number of chunks written. For formats with `MANIFEST` set, the
+    /// first chunk is written as the manifest.
+    ///
+    /// The header need to fits the size of Format::HEADER_SIZE
+    pub fn write_all<Format, I>(path: impl AsRef<Path>, header: &[u8], chunks: I) -> Result<u64>
+    where
+        Format: SeqDataFormat,
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let mut writer =
+            SeqDataWriter::<Format>::create(path, header)?.with_buffering(64 * 1024)?;
+        let mut count = 0;
+        for chunk in chunks {
+            if Format::MANIFEST && count == 0 {
+                writer.write_manifest(chunk.as_ref())?;
+            } else {
+                writer.append(chunk.as_ref())?;
+            }
+            count += 1;
+        }
+        writer.close()?;
+        Ok(count)
+    }
+
+    /// Merge the chunks of the `inputs` files into a new file at `output`, sorted by the
+    /// key extracted from each chunk by `key`, returning the number of chunks written
+    ///
+    /// Each input need to be already sorted by `key`: this is a k-way merge, keeping
+    /// only the current chunk of each input in memory. Chunks with equal keys are written
+    /// in the order of the inputs. For formats with `CHUNK_META_SIZE` set, `key` is given
+    /// the chunk metadata followed by the data, and for formats with `MANIFEST` set, the
+    /// manifest of the first input is written to the output.
+    pub fn merge_by<Format, P, K, F>(
+        inputs: &[P],
+        output: impl AsRef<Path>,
+        header: &[u8],
+        key: F,
+    ) -> Result<u64>
+    where
+        Format: SeqDataFormat,
+        P: AsRef<Path>,
+        K: Ord,
+        F: Fn(&[u8]) -> K,
+    {
+        let mut readers = inputs
+            .iter()
+            .map(|path| SeqDataReader::<Format>::open(path).map(|(reader, _)| reader))
+            .collect::<Result<Vec<_>>>()?;
+
+        // the current chunk of each input, and the heap of their keys
+        let mut current = Vec::with_capacity(readers.len());
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (index, reader) in readers.iter_mut().enumerate() {
+            let chunk = reader.next().transpose()?.map(|(_, chunk)| chunk);
+            if let Some(chunk) = &chunk {
+                heap.push(Reverse((key(chunk), index)));
+            }
+            current.push(chunk);
+        }
+
+        let mut writer =
+            SeqDataWriter::<Format>::create(output, header)?.with_buffering(64 * 1024)?;
+        if Format::MANIFEST {
+            let manifest = readers.first().and_then(|r| r.manifest()).unwrap_or(&[]);
+            writer.write_manifest(manifest)?;
+        }
+        let mut count = 0;
+        while let Some(Reverse((_, index))) = heap.pop() {
+            let chunk = current[index]
+                .take()
+                .expect("chunk of the input in the heap");
+            let (meta, data) = chunk.split_at(Format::CHUNK_META_SIZE);
+            writer.append_with_meta(meta, data)?;
+            count += 1;
+
+            if let Some((_, chunk)) = readers[index].next().transpose()? {
+                heap.push(Reverse((key(&chunk), index)));
+                current[index] = Some(chunk);
+            }
+        }
+        writer.close()?;
+        Ok(count)
+    }
+
+    /// Find which of the `candidates` formats the file at the location specified is in
+    ///
+    /// Return the index of the first candidate whose magic the file starts with, and
+    /// that has enough bytes for its header, or None if no candidate matches. The
+    /// leading bytes of the file are only read once. As an empty magic matches any file,
+    /// candidates without magic should be put last.
+    pub fn detect_format(
+        path: impl AsRef<Path>,
+        candidates: &[&dyn DynSeqDataFormat],
+    ) -> Result<Option<usize>> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let max_magic = candidates
+            .iter()
+            .map(|c| c.magic().len())
+            .max()
+            .unwrap_or(0);
+        let mut leading = Vec::with_capacity(max_magic);
+        file.take(max_magic as u64).read_to_end(&mut leading)?;
+
+        Ok(candidates.iter().position(|c| {
+            let magic = c.magic();
+            leading.starts_with(magic) && file_len >= (magic.len() + c.header_size()) as u64
+        }))
+    }
+
+    /// Check whether the file at the location specified looks like a SeqData of this format
+    ///
+    /// Only the magic is read and the size checked to hold the magic, the header and
+    /// the trailer magic if any, so this is a cheap check for scanning directories
+    /// that doesn't validate the header nor the chunks. Errors opening the file are
+    /// returned as errors, not as an invalid file.
+    pub fn is_valid<Format: SeqDataFormat>(path: impl AsRef<Path>) -> Result<bool> {
+        let file = File::open(path)?;
+        let minimum_size = data_start::<Format>() + Format::TRAILER_MAGIC.len() as u64;
+        if file.metadata()?.len() < minimum_size {
+            return Ok(false);
+        }
+        let mut magic = Vec::with_capacity(Format::MAGIC.len());
+        file.take(Format::MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        Ok(magic == Format::MAGIC)
+    }
+
+    /// Verify the SeqData at the location specified, reading every chunk up to the end
+    ///
+    /// The magic and header are checked, along with the framing of every chunk (and
+    /// their checksum for formats with `CHECKSUM` set). The first error is returned.
+    pub fn verify<Format: SeqDataFormat>(path: impl AsRef<Path>) -> Result<VerifyReport> {
+        let (mut reader, header) = SeqDataReader::<Format>::open(path)?;
+        let mut chunks = 0;
+        while let Some(r) = reader.next() {
+            r?;
+            chunks += 1;
+        }
+        Ok(VerifyReport {
+            header,
+            chunks,
+            data_len: reader.len(),
+        })
+    }
+
+    /// Verify each of the SeqData at the locations specified like `verify`, returning the
+    /// result for each file in the same order
+    ///
+    /// With the `rayon` feature, the files are verified in parallel on the rayon global
+    /// thread pool, which has one thread per CPU unless configured otherwise with
+    /// `rayon::ThreadPoolBuilder`. Without it, they are verified one after another on the
+    /// calling thread.
+    pub fn verify_many<Format: SeqDataFormat>(
+        paths: &[PathBuf],
+    ) -> Vec<(PathBuf, Result<VerifyReport>)> {
+        let verify = |path: &PathBuf| (path.clone(), Self::verify::<Format>(path));
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            paths.par_iter().map(verify).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        paths.iter().map(verify).collect()
+    }
+
+    /// Verify the chunks of the SeqData at the location specified against the CRC32 of
+    /// their data in `expected`, reporting the first chunk that doesn't match
+    ///
+    /// Only the data is hashed, the metadata of formats with `CHUNK_META_SIZE` is not.
+    pub fn verify_against<Format: SeqDataFormat>(
+        path: impl AsRef<Path>,
+        expected: &[u32],
+    ) -> Result<VerifyResult> {
+        let (mut reader, _) = SeqDataReader::<Format>::open(path)?;
+        let mut index = 0;
+        while index < expected.len() {
+            match reader.next_with_meta() {
+                None => break,
+                Some(Err(e)) => return Err(e),
+                Some(Ok((offset, _, data))) => {
+                    let actual = crc32fast::hash(&data);
+                    if actual != expected[index] {
+                        return Ok(VerifyResult::Mismatch {
+                            index: index as u64,
+                            offset,
+                            expected: expected[index],
+                            actual,
+                        });
+                    }
+                }
+            }
+            index += 1;
+        }
+        // count the chunks in excess, if any
+        let mut actual = index as u64;
+        while let Some(r) = reader.skip_chunk() {
+            r?;
+            actual += 1;
+        }
+        if actual != expected.len() as u64 {
+            return Ok(VerifyResult::CountMismatch {
+                expected: expected.len() as u64,
+                actual,
+            });
+        }
+        Ok(VerifyResult::Valid)
+    }
+
+    /// Count the chunks of the SeqData at the location specified
+    ///
+    /// The file is walked once, reading only the length of the chunks. A chunk cut short
+    /// by the end of file is an error. For files of fixed size records,
+    /// `FixedRecordReader::count` gives the count without walking the file.
+    pub fn count_chunks<Format: SeqDataFormat>(path: impl AsRef<Path>) -> Result<u64> {
+        let (mut reader, _) = SeqDataReader::<Format>::open(path)?;
+        reader.count_chunks()
+    }
+
+    /// Describe the structure of the SeqData at the location specified
+    ///
+    /// Only the length of the chunks is read, and no more than `limit` chunks
+    /// are inspected if a limit is given.
+    pub fn describe<Format: SeqDataFormat>(
+        path: impl AsRef<Path>,
+        limit: Option<usize>,
+    ) -> Result<Description> {
+        let (mut reader, header) = SeqDataReader::<Format>::open(path)?;
+        let chunks = reader
+            .scan_chunks()
+            .take(limit.unwrap_or(usize::MAX))
+            .collect::<Result<Vec<_>>>()?;
+        let complete =
+            limit.is_none_or(|limit| chunks.len() < limit) || reader.position() >= reader.len();
+        Ok(Description {
+            magic: Format::MAGIC.to_vec(),
+            header,
+            data_len: reader.len(),
+            chunks,
+            complete,
+        })
+    }
+}
+
+/// Check the magic and header of the file at the location specified
+fn check_format<Format: SeqDataFormat>(path: &Path) -> Result<()> {
+    let mut file = File::open(path)?;
+    let phantom = PhantomData::<Format>;
+    get_file_length(phantom, &mut file)?;
+    read_magic_and_header(phantom, &mut file)?;
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn rename_exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    let r = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if r != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// swap `a` and `b` with three renames, through a temporary name next to `a` reserved by
+// creating it, so that no existing file is overwritten
+fn swap_by_rename(a: &Path, b: &Path) -> std::io::Result<()> {
+    let mut n = 0u32;
+    let tmp = loop {
+        let mut tmp = a.as_os_str().to_owned();
+        tmp.push(format!(".swap{}", n));
+        let tmp = PathBuf::from(tmp);
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(_) => break tmp,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    if let Err(e) = std::fs::rename(a, &tmp) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    if let Err(e) = std::fs::rename(b, a) {
+        let _ = std::fs::rename(&tmp, a);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, b)
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "magic: ")?;
+        write_hex(f, &self.magic)?;
+        write!(f, "\nheader: ")?;
+        write_hex(f, &self.header)?;
+        writeln!(f, "\ndata length: {}", self.data_len)?;
+        writeln!(f, "{:>8} {:>12} {:>12}", "index", "offset", "length")?;
+        for chunk in &self.chunks {
+            writeln!(
+                f,
+                "{:>8} {:>12} {:>12}",
+                chunk.index, chunk.offset, chunk.len
+            )?;
+        }
+        if !self.complete {
+            writeln!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn swap_by_rename_keeps_existing_files() {
+        let tmp = TempDir::new();
+        let dir = tmp.path();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        std::fs::write(&a, b"first").unwrap();
+        std::fs::write(&b, b"second").unwrap();
+        // a leftover of the old fixed temporary name, which must not be overwritten
+        std::fs::write(dir.join("a.swap0"), b"other").unwrap();
+
+        swap_by_rename(&a, &b).unwrap();
+        assert_eq!(std::fs::read(&a).unwrap(), b"second");
+        assert_eq!(std::fs::read(&b).unwrap(), b"first");
+        assert_eq!(std::fs::read(dir.join("a.swap0")).unwrap(), b"other");
+        assert_eq!(entries(dir), ["a", "a.swap0", "b"]);
+    }
+
+    #[test]
+    fn swap_by_rename_restores_a_on_failure() {
+        let tmp = TempDir::new();
+        let dir = tmp.path();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        std::fs::write(&a, b"first").unwrap();
+
+        // b is missing, so the second rename fails
+        assert!(swap_by_rename(&a, &b).is_err());
+        assert_eq!(std::fs::read(&a).unwrap(), b"first");
+        assert_eq!(entries(dir), ["a"]);
+    }
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "cache")]
+mod common;
+
+use std::path::Path;
+
+use common::{Plain, TempDir};
+use seq_data_file::{CachedSeekReader, SeqData, SeqDataReaderSeek};
+
+// offsets of the chunks "aaa", "bbb" and "ccc"
+const A: u64 = 0;
+const B: u64 = 7;
+const C: u64 = 14;
+
+fn cached(path: &Path, max_entries: usize) -> CachedSeekReader<Plain> {
+    SeqData::write_all::<Plain, _>(path, &[], [b"aaa", b"bbb", b"ccc"]).unwrap();
+    let (reader, _) = SeqDataReaderSeek::<Plain>::open(path).unwrap();
+    CachedSeekReader::new(reader, max_entries)
+}
+
+// rewrite the chunks in upper case in place, so that only the cached chunks
+// are still read in lower case
+fn rewrite(path: &Path) {
+    let data = std::fs::read(path).unwrap();
+    std::fs::write(path, data.to_ascii_uppercase()).unwrap();
+}
+
+#[test]
+fn least_recently_used_is_evicted() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut reader = cached(&path, 2);
+    assert_eq!(reader.next_at(A).unwrap(), b"aaa");
+    assert_eq!(reader.next_at(B).unwrap(), b"bbb");
+    assert_eq!(reader.next_at(A).unwrap(), b"aaa");
+    assert_eq!(reader.next_at(C).unwrap(), b"ccc");
+    assert_eq!(reader.cached_entries(), 2);
+
+    rewrite(&path);
+    assert_eq!(reader.next_at(A).unwrap(), b"aaa");
+    assert_eq!(reader.next_at(C).unwrap(), b"ccc");
+    assert_eq!(reader.next_at(B).unwrap(), b"BBB");
+    // reading B evicted A, the least recently used
+    assert_eq!(reader.next_at(C).unwrap(), b"ccc");
+    assert_eq!(reader.next_at(A).unwrap(), b"AAA");
+}
+
+#[test]
+fn zero_entries_caches_nothing() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut reader = cached(&path, 0);
+    assert_eq!(reader.next_at(A).unwrap(), b"aaa");
+    assert_eq!(reader.cached_entries(), 0);
+
+    rewrite(&path);
+    assert_eq!(reader.next_at(A).unwrap(), b"AAA");
+    assert_eq!(reader.cached_entries(), 0);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{Headered, TempDir};
+use seq_data_file::{ColumnarReader, ColumnarWriter, SeqDataError};
+
+#[test]
+fn columnar_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = ColumnarWriter::<Headered>::create(&path, b"v1").unwrap();
+    assert_eq!(writer.append(b"one").unwrap(), 0);
+    assert_eq!(writer.append(b"").unwrap(), 1);
+    assert_eq!(writer.append(b"three").unwrap(), 2);
+    writer.close().unwrap();
+    // magic, header, data, 3 lengths and the count
+    assert_eq!(common::file_len(&path), 6 + 2 + 8 + 4 * 8);
+
+    let (mut reader, header) = ColumnarReader::<Headered>::open(&path).unwrap();
+    assert_eq!(header, b"v1");
+    assert_eq!(reader.count(), 3);
+    assert_eq!(reader.lengths(), [3, 0, 5]);
+    assert_eq!(reader.get(2).unwrap(), b"three");
+    assert_eq!(reader.get(0).unwrap(), b"one");
+    assert_eq!(reader.next().unwrap().unwrap(), (1, b"".to_vec()));
+    assert_eq!(reader.next().unwrap().unwrap(), (2, b"three".to_vec()));
+    assert!(reader.next().is_none());
+
+    let err = reader.get(3).unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::IndexOutOfRange { index: 3, count: 3 }
+    ));
+}
+
+#[test]
+fn columnar_unclosed_is_unreadable() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = ColumnarWriter::<Headered>::create(&path, b"v1").unwrap();
+    writer.append(b"one").unwrap();
+    drop(writer);
+    assert!(ColumnarReader::<Headered>::open(&path).is_err());
+}
@@ -0,0 +1,171 @@
+#![allow(dead_code)]
+use std::path::Path;
+
+use seq_data_file::{ChecksumAlgorithm, Crc32, PrefixWidth, SeqDataFormat, SeqDataReader};
+
+pub use seq_data_file::testutil::TempDir;
+
+pub fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+/// Read every chunk with its offset, panicking on error
+pub fn read_all<Format: SeqDataFormat>(path: &Path) -> Vec<(u64, Vec<u8>)> {
+    let (reader, _) = SeqDataReader::<Format>::open(path).unwrap();
+    reader.map(|chunk| chunk.unwrap()).collect()
+}
+
+/// Read every chunk, panicking on error
+pub fn chunks<Format: SeqDataFormat>(path: &Path) -> Vec<Vec<u8>> {
+    read_all::<Format>(path)
+        .into_iter()
+        .map(|(_, chunk)| chunk)
+        .collect()
+}
+
+/// Format with only a magic
+pub struct Plain;
+impl SeqDataFormat for Plain {
+    const MAGIC: &'static [u8] = b"PLAIN";
+    const HEADER_SIZE: usize = 0;
+}
+
+/// Format with a magic of a different value than `Plain`
+pub struct Other;
+impl SeqDataFormat for Other {
+    const MAGIC: &'static [u8] = b"OTHER";
+    const HEADER_SIZE: usize = 0;
+}
+
+/// Format with a 2 bytes header
+pub struct Headered;
+impl SeqDataFormat for Headered {
+    const MAGIC: &'static [u8] = b"HEADER";
+    const HEADER_SIZE: usize = 2;
+}
+
+/// Format with a magic longer than the 16 bytes read at once when checking it
+pub struct LongMagic;
+impl SeqDataFormat for LongMagic {
+    const MAGIC: &'static [u8] = b"a magic much longer than the read buffer";
+    const HEADER_SIZE: usize = 3;
+}
+
+/// Format with 2 bytes of metadata per chunk
+pub struct WithMeta;
+impl SeqDataFormat for WithMeta {
+    const MAGIC: &'static [u8] = b"META";
+    const HEADER_SIZE: usize = 0;
+    const CHUNK_META_SIZE: usize = 2;
+}
+
+/// Format with 16 bits length prefixes
+pub struct Narrow;
+impl SeqDataFormat for Narrow {
+    const MAGIC: &'static [u8] = b"NARROW";
+    const HEADER_SIZE: usize = 0;
+    const PREFIX_WIDTH: PrefixWidth = PrefixWidth::U16;
+}
+
+/// Format with 64 bits length prefixes
+pub struct Wide;
+impl SeqDataFormat for Wide {
+    const MAGIC: &'static [u8] = b"WIDE";
+    const HEADER_SIZE: usize = 0;
+    const PREFIX_WIDTH: PrefixWidth = PrefixWidth::U64;
+}
+
+/// Format with a CRC32 after each chunk
+pub struct Checksummed;
+impl SeqDataFormat for Checksummed {
+    const MAGIC: &'static [u8] = b"CRC";
+    const HEADER_SIZE: usize = 0;
+    const CHECKSUM: Option<ChecksumAlgorithm> = Some(ChecksumAlgorithm::of::<Crc32>());
+}
+
+/// Format with a trailer magic written by close
+pub struct WithTrailer;
+impl SeqDataFormat for WithTrailer {
+    const MAGIC: &'static [u8] = b"TRAILED";
+    const HEADER_SIZE: usize = 0;
+    const TRAILER_MAGIC: &'static [u8] = b"END!";
+}
+
+/// Format rejecting a header of version 0
+pub struct Validated;
+impl SeqDataFormat for Validated {
+    const MAGIC: &'static [u8] = b"VALID";
+    const HEADER_SIZE: usize = 1;
+
+    fn validate_header(header: &[u8]) -> Result<(), String> {
+        match header[0] {
+            0 => Err("version 0 is reserved".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Format with a chunk count after a 1 byte header
+pub struct Counted;
+impl SeqDataFormat for Counted {
+    const MAGIC: &'static [u8] = b"COUNTED";
+    const HEADER_SIZE: usize = 1;
+    const CHUNK_COUNT: bool = true;
+}
+
+/// Format with a manifest chunk before the data chunks
+pub struct WithManifest;
+impl SeqDataFormat for WithManifest {
+    const MAGIC: &'static [u8] = b"MANIFEST";
+    const HEADER_SIZE: usize = 0;
+    const MANIFEST: bool = true;
+}
+
+/// Format with a dirty flag in the first byte of the header
+pub struct Dirty;
+impl SeqDataFormat for Dirty {
+    const MAGIC: &'static [u8] = b"DIRTY";
+    const HEADER_SIZE: usize = 1;
+    const DIRTY_FLAG: Option<usize> = Some(0);
+}
+
+/// Format recording the padding of `close_padded` in its 4 bytes header
+pub struct Padded;
+impl SeqDataFormat for Padded {
+    const MAGIC: &'static [u8] = b"PADDED";
+    const HEADER_SIZE: usize = 4;
+    const PADDING_LENGTH: Option<usize> = Some(0);
+}
+
+/// Format with the length of each chunk repeated after it
+pub struct Trailing;
+impl SeqDataFormat for Trailing {
+    const MAGIC: &'static [u8] = b"TRAILING";
+    const HEADER_SIZE: usize = 0;
+    const TRAILING_LENGTH: bool = true;
+}
+
+/// Format with a sentinel after each chunk
+pub struct Sentinel;
+impl SeqDataFormat for Sentinel {
+    const MAGIC: &'static [u8] = b"SENTINEL";
+    const HEADER_SIZE: usize = 0;
+    const SENTINEL: &'static [u8] = b"\xfe\xed";
+}
+
+/// Format with 16 bits length prefixes whose top bit flags a continued record
+pub struct Continued;
+impl SeqDataFormat for Continued {
+    const MAGIC: &'static [u8] = b"CONTINUED";
+    const HEADER_SIZE: usize = 0;
+    const PREFIX_WIDTH: PrefixWidth = PrefixWidth::U16;
+    const CONTINUATION: bool = true;
+}
+
+/// Format with sync markers
+pub struct Marked;
+impl SeqDataFormat for Marked {
+    const MAGIC: &'static [u8] = b"MARKED";
+    const HEADER_SIZE: usize = 0;
+    const SYNC_MARKER: &'static [u8] = b"SYNC";
+}
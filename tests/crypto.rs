@@ -0,0 +1,94 @@
+#![cfg(feature = "crypto")]
+mod common;
+
+use common::{Plain, TempDir, WithManifest, WithMeta};
+use seq_data_file::{EncryptedReader, SeqDataError, SeqDataWriter, KEY_SIZE};
+
+const KEY: [u8; KEY_SIZE] = [7; KEY_SIZE];
+
+fn decrypt_all(path: &std::path::Path) -> Vec<seq_data_file::Result<(u64, Vec<u8>)>> {
+    let (mut reader, _) = EncryptedReader::<Plain>::open(path, &KEY).unwrap();
+    std::iter::from_fn(|| reader.next()).collect()
+}
+
+fn is_decryption_failed(r: &seq_data_file::Result<(u64, Vec<u8>)>) -> bool {
+    matches!(r, Err(SeqDataError::DecryptionFailed { .. }))
+}
+
+#[test]
+fn roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create_encrypted(&path, &[], &KEY).unwrap();
+    writer.append(b"one").unwrap();
+    writer.append(b"").unwrap();
+    writer.append(b"three").unwrap();
+    writer.close().unwrap();
+
+    // the data is not stored in clear
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(!bytes.windows(5).any(|w| w == b"three"));
+
+    let chunks = decrypt_all(&path)
+        .into_iter()
+        .map(|r| r.unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(chunks, [b"one".to_vec(), Vec::new(), b"three".to_vec()]);
+}
+
+#[test]
+fn flipped_byte_fails_authentication() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create_encrypted(&path, &[], &KEY).unwrap();
+    writer.append(b"secret").unwrap();
+    writer.close().unwrap();
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    // magic, length prefix and nonce, then the first byte of the ciphertext
+    bytes[5 + 4 + 12] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let results = decrypt_all(&path);
+    assert_eq!(results.len(), 1);
+    assert!(is_decryption_failed(&results[0]), "{:?}", results[0]);
+    let err = std::io::Error::from(results.into_iter().next().unwrap().unwrap_err());
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn moved_chunk_fails_authentication() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create_encrypted(&path, &[], &KEY).unwrap();
+    writer.append(b"one!").unwrap();
+    writer.append(b"two!").unwrap();
+    writer.close().unwrap();
+
+    // both chunks have the same size, swap them
+    let mut bytes = std::fs::read(&path).unwrap();
+    let chunk_size = 4 + 12 + 4 + 16;
+    assert_eq!(bytes.len(), 5 + 2 * chunk_size);
+    let (first, second) = bytes[5..].split_at_mut(chunk_size);
+    first.swap_with_slice(second);
+    std::fs::write(&path, &bytes).unwrap();
+
+    let results = decrypt_all(&path);
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(is_decryption_failed), "{:?}", results);
+}
+
+#[test]
+fn formats_with_manifest_or_meta_are_rejected() {
+    let dir = TempDir::new();
+    let err = SeqDataWriter::<WithManifest>::create_encrypted(dir.join("manifest"), &[], &KEY)
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::Unsupported(_)), "{:?}", err);
+    assert!(!dir.join("manifest").exists());
+
+    let err = SeqDataWriter::<WithMeta>::create_encrypted(dir.join("meta"), &[], &KEY)
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::Unsupported(_)), "{:?}", err);
+}
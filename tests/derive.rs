@@ -0,0 +1,33 @@
+#![cfg(feature = "derive")]
+mod common;
+
+use common::TempDir;
+use seq_data_file::{SeqData, SeqDataFormat, SeqDataReader};
+
+#[derive(SeqDataFormat)]
+#[magic = b"DERIVED"]
+#[header_size = 2]
+struct Derived;
+
+#[derive(SeqDataFormat)]
+struct Bare;
+
+#[test]
+fn derived_constants() {
+    assert_eq!(Derived::MAGIC, b"DERIVED");
+    assert_eq!(Derived::HEADER_SIZE, 2);
+    assert!(Bare::MAGIC.is_empty());
+    assert_eq!(Bare::HEADER_SIZE, 0);
+}
+
+#[test]
+fn derived_format_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Derived, _>(&path, b"v1", [&b"one"[..], b"two"]).unwrap();
+    assert!(std::fs::read(&path).unwrap().starts_with(b"DERIVEDv1"));
+
+    let (_, header) = SeqDataReader::<Derived>::open(&path).unwrap();
+    assert_eq!(header, b"v1");
+    assert_eq!(common::chunks::<Derived>(&path), [b"one", b"two"]);
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use common::{Checksummed, Headered, Plain, TempDir, WithMeta, WithTrailer};
+use seq_data_file::{Index, SeqDataError, SeqDataReader, SeqDataReaderSeek, SeqDataWriter};
+
+fn error_of<T>(r: seq_data_file::Result<T>) -> SeqDataError {
+    match r {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn magic_mismatch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"HEADEX\0\0").unwrap();
+    let err = error_of(SeqDataReader::<Headered>::open(&path));
+    assert!(matches!(err, SeqDataError::MagicMismatch), "{:?}", err);
+    let err = error_of(SeqDataReaderSeek::<Headered>::open(&path));
+    assert!(matches!(err, SeqDataError::MagicMismatch), "{:?}", err);
+}
+
+#[test]
+fn file_too_short() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    for len in [0, 2, 7] {
+        std::fs::write(&path, &b"HEADER\0\0"[..len]).unwrap();
+        let err = error_of(SeqDataReader::<Headered>::open(&path));
+        assert!(
+            matches!(err, SeqDataError::FileTooShort { len: l, min: 8 } if l == len as u64),
+            "{:?}",
+            err
+        );
+        let err = error_of(SeqDataReaderSeek::<Headered>::open(&path));
+        assert!(
+            matches!(err, SeqDataError::FileTooShort { len: l, min: 8 } if l == len as u64),
+            "{:?}",
+            err
+        );
+    }
+}
+
+#[test]
+fn header_size() {
+    let dir = TempDir::new();
+    let err = error_of(SeqDataWriter::<Headered>::create(dir.join("data"), b"abc"));
+    assert!(
+        matches!(
+            err,
+            SeqDataError::HeaderSize {
+                expected: 2,
+                got: 3
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn header_mismatch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqDataWriter::<Headered>::create(&path, b"ab").unwrap();
+    let err = error_of(SeqDataReader::<Headered>::open_expect_header(&path, b"cd"));
+    match err {
+        SeqDataError::HeaderMismatch { expected, got } => {
+            assert_eq!(expected, b"cd");
+            assert_eq!(got, b"ab");
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn trailer_mismatch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithTrailer>::create(&path, &[]).unwrap();
+    writer.append(b"data").unwrap();
+    drop(writer);
+    let err = error_of(SeqDataReader::<WithTrailer>::open(&path));
+    assert!(matches!(err, SeqDataError::TrailerMismatch), "{:?}", err);
+}
+
+#[test]
+fn chunk_meta_size() {
+    let dir = TempDir::new();
+    let mut writer = SeqDataWriter::<WithMeta>::create(dir.join("data"), &[]).unwrap();
+    let err = error_of(writer.append_with_meta(b"x", b"data"));
+    assert!(
+        matches!(
+            err,
+            SeqDataError::ChunkMetaSize {
+                expected: 2,
+                got: 1
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn size_limit_exceeded() {
+    let dir = TempDir::new();
+    let mut writer = SeqDataWriter::<WithMeta>::create(dir.join("data"), &[])
+        .unwrap()
+        .with_size_limit(20);
+    let err = error_of(writer.append_with_meta(b"mm", &[0; 16]));
+    assert!(
+        matches!(err, SeqDataError::SizeLimitExceeded { limit: 20, .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn truncated_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithMeta>::create(&path, &[]).unwrap();
+    writer.append_with_meta(b"mm", b"hello").unwrap();
+    drop(writer);
+    let len = common::file_len(&path);
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - 2).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<WithMeta>::open(&path).unwrap();
+    let err = error_of(reader.next().unwrap());
+    assert!(
+        matches!(err, SeqDataError::TruncatedChunk { len: Some(5), .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn index_errors() {
+    let err = error_of(Index::from_bytes(b"SD"));
+    assert!(
+        matches!(err, SeqDataError::FileTooShort { len: 2, .. }),
+        "{:?}",
+        err
+    );
+    let mut bytes = Index::new(0, Vec::new()).to_bytes();
+    bytes[0] ^= 1;
+    let err = error_of(Index::from_bytes(&bytes));
+    assert!(matches!(err, SeqDataError::MagicMismatch), "{:?}", err);
+}
+
+#[test]
+fn out_of_range() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    let err = error_of(writer.overwrite_at(7, b"one"));
+    assert!(
+        matches!(err, SeqDataError::OffsetOutOfRange { offset: 7, len: 7 }),
+        "{:?}",
+        err
+    );
+    let err = error_of(writer.overwrite_at(0, b"two!"));
+    assert!(
+        matches!(err, SeqDataError::ChunkSizeMismatch { len: 3, got: 4 }),
+        "{:?}",
+        err
+    );
+    writer.close().unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let err = error_of(reader.seek_to_offset(8));
+    assert!(
+        matches!(err, SeqDataError::OffsetOutOfRange { offset: 8, len: 7 }),
+        "{:?}",
+        err
+    );
+    let (mut reader, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    let err = error_of(reader.next_at(7));
+    assert!(
+        matches!(err, SeqDataError::OffsetOutOfRange { offset: 7, len: 7 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn unsupported_by_format() {
+    let dir = TempDir::new();
+    let mut writer = SeqDataWriter::<Plain>::create(dir.join("data"), &[]).unwrap();
+    let err = error_of(writer.write_manifest(b"manifest"));
+    assert!(matches!(err, SeqDataError::Unsupported(_)), "{:?}", err);
+    let err = error_of(writer.append_sync_marker());
+    assert!(matches!(err, SeqDataError::Unsupported(_)), "{:?}", err);
+}
+
+#[test]
+fn corrupted_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    writer.close().unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Checksummed>::open(&path).unwrap();
+    let err = error_of(reader.next().unwrap());
+    match err {
+        SeqDataError::CorruptedChunk { offset: 0, error } => {
+            assert!(
+                matches!(*error, SeqDataError::ChecksumMismatch),
+                "{:?}",
+                error
+            )
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn io_error_kinds() {
+    use std::io::{Error, ErrorKind};
+    let kind = |e: SeqDataError| Error::from(e).kind();
+    assert_eq!(kind(SeqDataError::MagicMismatch), ErrorKind::InvalidData);
+    assert_eq!(kind(SeqDataError::TrailerMismatch), ErrorKind::InvalidData);
+    assert_eq!(
+        kind(SeqDataError::ChunkTooLarge { len: 2, max: 1 }),
+        ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        kind(SeqDataError::SizeLimitExceeded {
+            grow: 1,
+            len: 2,
+            limit: 1
+        }),
+        ErrorKind::FileTooLarge
+    );
+    assert_eq!(
+        kind(SeqDataError::Io(ErrorKind::NotFound.into())),
+        ErrorKind::NotFound
+    );
+
+    // the variant goes through the io error and back
+    let err = Error::from(SeqDataError::IndexOutOfRange { index: 3, count: 2 });
+    assert!(matches!(
+        SeqDataError::of(&err),
+        Some(SeqDataError::IndexOutOfRange { index: 3, count: 2 })
+    ));
+    assert!(matches!(
+        SeqDataError::from(err),
+        SeqDataError::IndexOutOfRange { index: 3, count: 2 }
+    ));
+}
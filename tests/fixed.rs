@@ -0,0 +1,49 @@
+mod common;
+
+use common::{Headered, TempDir};
+use seq_data_file::{FixedRecordReader, SeqDataError};
+
+fn records(path: &std::path::Path, data: &[u8]) {
+    let mut file = b"HEADERv1".to_vec();
+    file.extend_from_slice(data);
+    std::fs::write(path, file).unwrap();
+}
+
+#[test]
+fn fixed_records_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    records(&path, b"aaaabbbbcccc");
+
+    let (mut reader, header) = FixedRecordReader::<Headered, 4>::open(&path).unwrap();
+    assert_eq!(header, b"v1");
+    assert_eq!(reader.count(), 3);
+    assert_eq!(reader.next().unwrap().unwrap(), (0, *b"aaaa"));
+    assert_eq!(reader.get(2).unwrap(), *b"cccc");
+    assert!(reader.next().is_none());
+    assert_eq!(reader.get(1).unwrap(), *b"bbbb");
+    assert_eq!(reader.next().unwrap().unwrap(), (2, *b"cccc"));
+
+    let err = reader.get(3).unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::IndexOutOfRange { index: 3, count: 3 }
+    ));
+}
+
+#[test]
+fn fixed_records_of_wrong_size() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    records(&path, b"aaaabbbbcc");
+
+    let err = FixedRecordReader::<Headered, 4>::open(&path).err().unwrap();
+    assert!(matches!(
+        err,
+        SeqDataError::RecordSizeMismatch {
+            len: 10,
+            record_size: 4
+        }
+    ));
+    assert!(FixedRecordReader::<Headered, 5>::open(&path).is_ok());
+}
@@ -0,0 +1,336 @@
+mod common;
+
+use common::{
+    chunks, file_len, Checksummed, Counted, LongMagic, Narrow, Plain, TempDir, Wide, WithManifest,
+    WithTrailer,
+};
+use seq_data_file::{
+    prefix_size, testutil, ChunkChecksum, Crc32, PrefixWidth, SeqData, SeqDataError, SeqDataFormat,
+    SeqDataReader, SeqDataReaderSeek, SeqDataWriter, PREFIX_SIZE,
+};
+
+#[test]
+fn magic_without_header_roundtrip() {
+    let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+    assert_eq!(testutil::roundtrip::<Plain>(&[], chunks), chunks);
+}
+
+#[test]
+fn long_magic_roundtrip() {
+    let chunks: &[&[u8]] = &[b"one", b"two"];
+    assert_eq!(testutil::roundtrip::<LongMagic>(b"hdr", chunks), chunks);
+}
+
+#[test]
+fn long_magic_mismatch_after_read_buffer() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqDataWriter::<LongMagic>::create(&path, b"hdr")
+        .unwrap()
+        .close()
+        .unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    // differ past the first read of the magic
+    bytes[20] ^= 1;
+    std::fs::write(&path, bytes).unwrap();
+    assert!(SeqDataReader::<LongMagic>::open(&path).is_err());
+}
+
+#[test]
+fn checksum_not_counted_in_length_prefix() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    writer.append(b"hello").unwrap();
+    writer.close().unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    let chunk = &bytes[Checksummed::MAGIC.len()..];
+    assert_eq!(chunk[..PREFIX_SIZE], 5u32.to_le_bytes());
+    assert_eq!(&chunk[PREFIX_SIZE..PREFIX_SIZE + 5], b"hello");
+    assert_eq!(chunk.len(), PREFIX_SIZE + 5 + Crc32::WIDTH);
+}
+
+#[test]
+fn checksum_mismatch_reports_chunk_offset() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    writer.append(b"first").unwrap();
+    let offset = writer.position();
+    writer.append(b"second").unwrap();
+    writer.append(b"third").unwrap();
+    writer.close().unwrap();
+
+    // flip a byte of the data of the second chunk
+    let mut bytes = std::fs::read(&path).unwrap();
+    let data_start = Checksummed::MAGIC.len() + offset as usize + PREFIX_SIZE;
+    bytes[data_start + 2] ^= 0x10;
+    std::fs::write(&path, bytes).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Checksummed>::open(&path).unwrap();
+    assert_eq!(reader.next().unwrap().unwrap(), (0, b"first".to_vec()));
+    let err = reader.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(
+        err.to_string()
+            .contains(&format!("corrupted chunk at offset {}:", offset)),
+        "{}",
+        err
+    );
+
+    let (mut reader, _) = SeqDataReaderSeek::<Checksummed>::open(&path).unwrap();
+    let err = reader.next_at(offset).unwrap_err();
+    assert!(err.to_string().contains(&format!("at offset {}", offset)));
+}
+
+fn checksum_of<C: ChunkChecksum>(data: &[u8]) -> Vec<u8> {
+    let mut checksum = C::default();
+    checksum.update(data);
+    checksum.finalize().as_ref().to_vec()
+}
+
+#[test]
+fn crc32_known_value() {
+    assert_eq!(
+        checksum_of::<Crc32>(b"123456789"),
+        0xCBF43926u32.to_le_bytes()
+    );
+}
+
+#[test]
+fn checksum_written_after_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    writer.append(b"hello").unwrap();
+    writer.close().unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(
+        &bytes[Checksummed::MAGIC.len() + PREFIX_SIZE + 5..],
+        &checksum_of::<Crc32>(b"hello")[..]
+    );
+}
+
+#[cfg(feature = "crc32c")]
+mod crc32c {
+    use super::checksum_of;
+    use seq_data_file::{testutil, ChecksumAlgorithm, Crc32c, SeqDataFormat};
+
+    struct Castagnoli;
+    impl SeqDataFormat for Castagnoli {
+        const MAGIC: &'static [u8] = b"CRC32C";
+        const HEADER_SIZE: usize = 0;
+        const CHECKSUM: Option<ChecksumAlgorithm> = Some(ChecksumAlgorithm::of::<Crc32c>());
+    }
+
+    #[test]
+    fn known_value() {
+        assert_eq!(
+            checksum_of::<Crc32c>(b"123456789"),
+            0xE3069283u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+        assert_eq!(testutil::roundtrip::<Castagnoli>(&[], chunks), chunks);
+    }
+}
+
+#[cfg(feature = "xxhash")]
+mod xxhash {
+    use super::checksum_of;
+    use seq_data_file::{testutil, ChecksumAlgorithm, SeqDataFormat, XxHash64};
+
+    struct Xxh64;
+    impl SeqDataFormat for Xxh64 {
+        const MAGIC: &'static [u8] = b"XXH64";
+        const HEADER_SIZE: usize = 0;
+        const CHECKSUM: Option<ChecksumAlgorithm> = Some(ChecksumAlgorithm::of::<XxHash64>());
+    }
+
+    #[test]
+    fn known_value() {
+        assert_eq!(
+            checksum_of::<XxHash64>(b""),
+            0xEF46DB3751D8E999u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+        assert_eq!(testutil::roundtrip::<Xxh64>(&[], chunks), chunks);
+    }
+}
+
+fn is_trailer_mismatch(err: SeqDataError) -> bool {
+    matches!(err, SeqDataError::TrailerMismatch)
+}
+
+#[test]
+fn unclosed_trailer_magic_file_fails_to_open() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithTrailer>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    drop(writer);
+
+    assert!(is_trailer_mismatch(
+        SeqDataReader::<WithTrailer>::open(&path).err().unwrap()
+    ));
+    assert!(is_trailer_mismatch(
+        SeqDataReaderSeek::<WithTrailer>::open(&path).err().unwrap()
+    ));
+    assert!(is_trailer_mismatch(
+        SeqDataWriter::<WithTrailer>::open(&path, &[])
+            .err()
+            .unwrap()
+    ));
+}
+
+#[test]
+fn closed_trailer_magic_file_opens() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithTrailer>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    writer.close().unwrap();
+    assert!(std::fs::read(&path).unwrap().ends_with(b"END!"));
+
+    // appending moves the trailer magic after the new chunks
+    let (mut writer, _) = SeqDataWriter::<WithTrailer>::open(&path, &[]).unwrap();
+    writer.append(b"two").unwrap();
+    writer.close().unwrap();
+
+    let (reader, _) = SeqDataReader::<WithTrailer>::open(&path).unwrap();
+    let chunks = reader.map(|c| c.unwrap().1).collect::<Vec<_>>();
+    assert_eq!(chunks, [b"one", b"two"]);
+    let (mut reader, _) = SeqDataReaderSeek::<WithTrailer>::open(&path).unwrap();
+    assert_eq!(reader.next().unwrap(), b"one");
+}
+
+fn declared_count(path: &std::path::Path) -> Option<u64> {
+    SeqDataReader::<Counted>::open(path)
+        .unwrap()
+        .0
+        .declared_count()
+}
+
+#[test]
+fn chunk_count_after_flush_close_and_reopen() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Counted>::create(&path, b"h").unwrap();
+    assert_eq!(declared_count(&path), Some(0));
+    writer.append(b"one").unwrap();
+    writer.append(b"two").unwrap();
+    writer.flush().unwrap();
+    assert_eq!(declared_count(&path), Some(2));
+    writer.append(b"three").unwrap();
+    writer.close().unwrap();
+    assert_eq!(declared_count(&path), Some(3));
+
+    let (mut writer, _) = SeqDataWriter::<Counted>::open(&path, b"h").unwrap();
+    writer.append(b"four").unwrap();
+    writer.close().unwrap();
+    assert_eq!(declared_count(&path), Some(4));
+
+    // the chunks start after the count
+    let (reader, _) = SeqDataReader::<Counted>::open(&path).unwrap();
+    let chunks = reader.map(|c| c.unwrap()).collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[0], (0, b"one".to_vec()));
+    assert_eq!(chunks[3].1, b"four");
+}
+
+#[test]
+fn roundtrip_options() {
+    let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+    assert_eq!(testutil::roundtrip::<Checksummed>(&[], chunks), chunks);
+    assert_eq!(testutil::roundtrip::<WithTrailer>(&[], chunks), chunks);
+    assert_eq!(testutil::roundtrip::<Counted>(b"h", chunks), chunks);
+    assert_eq!(testutil::roundtrip::<WithManifest>(&[], chunks), chunks);
+}
+
+#[test]
+fn prefix_width_roundtrip() {
+    let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+    assert_eq!(testutil::roundtrip::<Narrow>(&[], chunks), chunks);
+    assert_eq!(testutil::roundtrip::<Wide>(&[], chunks), chunks);
+
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Wide, _>(&path, &[], [b"one"]).unwrap();
+    assert_eq!(file_len(&path), 4 + 8 + 3);
+}
+
+#[test]
+fn narrow_prefix_rejects_larger_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Narrow>::create(&path, &[]).unwrap();
+    writer.append(&[1; 65535]).unwrap();
+    let err = writer.append(&[2; 65536]).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SeqDataError::ChunkTooLarge {
+                len: 65536,
+                max: 65535
+            }
+        ),
+        "{:?}",
+        err
+    );
+    writer.close().unwrap();
+    assert_eq!(chunks::<Narrow>(&path), [vec![1; 65535]]);
+}
+
+fn is_truncated_chunk(err: &SeqDataError) -> bool {
+    matches!(err, SeqDataError::TruncatedChunk { .. })
+}
+
+#[test]
+fn garbage_wide_prefix_is_an_error() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Wide, _>(&path, &[], [b"one", b"two"]).unwrap();
+    let second = Wide::MAGIC.len() + 8 + 3;
+    let mut bytes = std::fs::read(&path).unwrap();
+
+    // far past the end of file, and overflowing once the framing is added
+    for garbage in [1 << 40, u64::MAX - 4] {
+        bytes[second..second + 8].copy_from_slice(&garbage.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+
+        let (mut reader, _) = SeqDataReader::<Wide>::open(&path).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), (0, b"one".to_vec()));
+        assert!(is_truncated_chunk(&reader.next().unwrap().unwrap_err()));
+        assert!(reader.next().is_none());
+
+        let (mut reader, _) = SeqDataReaderSeek::<Wide>::open(&path).unwrap();
+        assert!(is_truncated_chunk(&reader.next_at(11).unwrap_err()));
+
+        let (mut reader, _) = SeqDataReader::<Wide>::open(&path).unwrap();
+        assert!(is_truncated_chunk(&reader.skip_chunks(2).unwrap_err()));
+    }
+}
+
+#[test]
+fn prefix_size_constants() {
+    assert_eq!(PREFIX_SIZE, 4);
+    assert_eq!(prefix_size::<Plain>(), PREFIX_SIZE);
+    assert_eq!(prefix_size::<Narrow>(), PrefixWidth::U16.size());
+    assert_eq!(prefix_size::<Wide>(), 8);
+
+    // the length of a chunk in the file is its data and its length prefix
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Narrow, _>(&path, &[], [&b"one"[..], b"three"]).unwrap();
+    let prefixes = 2 * prefix_size::<Narrow>() as u64;
+    assert_eq!(file_len(&path), Narrow::MAGIC.len() as u64 + prefixes + 8);
+}
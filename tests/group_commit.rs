@@ -0,0 +1,110 @@
+#![cfg(feature = "async")]
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{read_all, Narrow, Plain, TempDir};
+use seq_data_file::nonblocking::{GroupCommitWriter, SeqDataWriter};
+use seq_data_file::{SeqDataError, SeqDataFormat};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+async fn spawn<Format: SeqDataFormat + Send + 'static>(
+    path: &std::path::Path,
+    max_delay: Duration,
+    max_batch_bytes: usize,
+) -> Arc<GroupCommitWriter> {
+    let writer = SeqDataWriter::<Format>::create(path, &[]).await.unwrap();
+    Arc::new(GroupCommitWriter::spawn(writer, max_delay, max_batch_bytes))
+}
+
+async fn close(writer: Arc<GroupCommitWriter>) -> seq_data_file::Result<()> {
+    Arc::try_unwrap(writer).ok().unwrap().close().await
+}
+
+#[tokio::test]
+async fn concurrent_appends_get_their_offset() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = spawn::<Plain>(&path, Duration::from_millis(10), 64).await;
+
+    let tasks = (0..20u8)
+        .map(|i| {
+            let writer = writer.clone();
+            tokio::spawn(async move {
+                let data = vec![i; 1 + i as usize % 5];
+                let offset = writer.append(data.clone()).await.unwrap();
+                (offset, data)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut appended = Vec::new();
+    for task in tasks {
+        appended.push(task.await.unwrap());
+    }
+    close(writer).await.unwrap();
+
+    // the chunks are in the order the appends were received, which is the order of
+    // their offsets
+    appended.sort();
+    assert_eq!(read_all::<Plain>(&path), appended);
+}
+
+#[tokio::test]
+async fn batch_committed_by_size() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = spawn::<Plain>(&path, HOUR, 8).await;
+
+    let first = tokio::spawn({
+        let writer = writer.clone();
+        async move { writer.append(b"abcd".to_vec()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // waiting for more data or the delay
+    assert!(!first.is_finished());
+
+    assert_eq!(writer.append(b"efgh".to_vec()).await.unwrap(), 8);
+    assert_eq!(first.await.unwrap().unwrap(), 0);
+    close(writer).await.unwrap();
+    assert_eq!(
+        read_all::<Plain>(&path),
+        [(0, b"abcd".to_vec()), (8, b"efgh".to_vec())]
+    );
+}
+
+#[tokio::test]
+async fn batch_committed_by_delay() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let delay = Duration::from_millis(50);
+    let writer = spawn::<Plain>(&path, delay, usize::MAX).await;
+
+    let start = std::time::Instant::now();
+    assert_eq!(writer.append(b"one".to_vec()).await.unwrap(), 0);
+    assert!(start.elapsed() >= delay);
+    close(writer).await.unwrap();
+    assert_eq!(read_all::<Plain>(&path), [(0, b"one".to_vec())]);
+}
+
+#[tokio::test]
+async fn error_fails_the_whole_batch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = spawn::<Narrow>(&path, HOUR, 70_000).await;
+
+    let first = tokio::spawn({
+        let writer = writer.clone();
+        async move { writer.append(b"fits".to_vec()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // too large for the 16 bits length prefix
+    assert!(writer.append(vec![0; 70_000]).await.is_err());
+    assert!(first.await.unwrap().is_err());
+
+    // the writer is stopped
+    assert!(writer.append(b"after".to_vec()).await.is_err());
+    let err = close(writer).await.unwrap_err();
+    assert!(matches!(err, SeqDataError::ChunkTooLarge { .. }));
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "http")]
+mod common;
+
+use std::cell::Cell;
+
+use common::{Other, TempDir, WithTrailer};
+use seq_data_file::{HttpRangeClient, RemoteSeekReader, SeqData, SeqDataError};
+
+/// Range client serving a file from memory, cutting or padding its responses by `skew`
+struct MemoryClient {
+    data: Vec<u8>,
+    skew: isize,
+    requests: Cell<usize>,
+}
+
+impl MemoryClient {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            skew: 0,
+            requests: Cell::new(0),
+        }
+    }
+}
+
+impl HttpRangeClient for MemoryClient {
+    fn content_length(&self, url: &str) -> std::io::Result<u64> {
+        assert_eq!(url, "http://example.com/data");
+        Ok(self.data.len() as u64)
+    }
+
+    fn get_range(&self, url: &str, start: u64, len: u64) -> std::io::Result<Vec<u8>> {
+        assert_eq!(url, "http://example.com/data");
+        self.requests.set(self.requests.get() + 1);
+        let start = start as usize;
+        let end = (start + len as usize).saturating_add_signed(self.skew);
+        Ok(self.data[start..end.min(self.data.len())].to_vec())
+    }
+}
+
+fn served(chunks: &[&[u8]]) -> Vec<u8> {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<WithTrailer, _>(&path, &[], chunks.iter().copied()).unwrap();
+    std::fs::read(&path).unwrap()
+}
+
+#[test]
+fn remote_reads_chunks() {
+    let client = MemoryClient::new(served(&[b"one", b"", b"three"]));
+    let (mut reader, header) =
+        RemoteSeekReader::<_, WithTrailer>::open(client, "http://example.com/data").unwrap();
+    assert!(header.is_empty());
+    assert_eq!(reader.append_position(), 20);
+    assert_eq!(reader.next_at(11).unwrap(), b"three");
+    assert_eq!(reader.next_at(0).unwrap(), b"one");
+    assert_eq!(reader.next().unwrap(), b"");
+
+    let requests = reader.backend().client().requests.get();
+    reader.next_at(7).unwrap();
+    assert_eq!(reader.backend().client().requests.get(), requests + 2);
+}
+
+#[test]
+fn remote_checks_the_magic() {
+    let client = MemoryClient::new(served(&[b"one"]));
+    let err = RemoteSeekReader::<_, Other>::open(client, "http://example.com/data")
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::MagicMismatch));
+}
+
+#[test]
+fn remote_rejects_ranges_of_another_length() {
+    let data = served(&[b"one", b"two"]);
+    for skew in [-1, 1] {
+        let mut client = MemoryClient::new(data.clone());
+        client.skew = skew;
+        let err = RemoteSeekReader::<_, WithTrailer>::open(client, "http://example.com/data")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            SeqDataError::RangeResponseLength { offset: 0, .. }
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{Plain, TempDir};
+use seq_data_file::{
+    Index, SeqData, SeqDataError, SeqDataIndexedReader, SeqDataReaderSeek, SeqDataWriter,
+};
+
+fn write_indexed(path: &std::path::Path, chunks: &[&[u8]]) -> Index {
+    SeqData::write_all::<Plain, _>(path, &[], chunks.iter().copied()).unwrap();
+    let index = Index::build::<Plain>(path).unwrap();
+    index.save(Index::sidecar_path(path)).unwrap();
+    index
+}
+
+fn append(path: &std::path::Path, data: &[u8]) {
+    let (mut writer, _) = SeqDataWriter::<Plain>::open(path, &[]).unwrap();
+    writer.append(data).unwrap();
+    writer.close().unwrap();
+}
+
+#[test]
+fn get_returns_the_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let index = write_indexed(&path, &[b"one", b"", b"three"]);
+    assert_eq!(index.offsets(), [0, 7, 11]);
+
+    let (mut reader, _) = SeqDataIndexedReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.len(), 3);
+    assert_eq!(reader.get(2).unwrap(), b"three");
+    assert_eq!(reader.get(0).unwrap(), b"one");
+    assert_eq!(reader.get(1).unwrap(), b"");
+    let err = reader.get(3).unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::IndexOutOfRange { index: 3, count: 3 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn stale_index_after_append() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let index = write_indexed(&path, &[b"one", b"two"]);
+    append(&path, b"three");
+    assert!(index.is_stale(common::file_len(&path) - 5));
+
+    // the index is updated in memory when opening, but not saved
+    let (mut reader, _) = SeqDataIndexedReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.index().offsets(), [0, 7, 14]);
+    assert_eq!(reader.get(2).unwrap(), b"three");
+    assert_eq!(Index::load(Index::sidecar_path(&path)).unwrap(), index);
+}
+
+#[test]
+fn update_appends_only_new_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let index = write_indexed(&path, &[b"one", b"two"]);
+    append(&path, b"three");
+    append(&path, b"four");
+
+    // the offsets already indexed are kept as is, and not scanned again
+    let mut fake = Index::new(index.data_len(), vec![100, 200]);
+    assert_eq!(fake.update::<Plain>(&path).unwrap(), 2);
+    assert_eq!(fake.offsets(), [100, 200, 14, 23]);
+    assert_eq!(fake.data_len(), 31);
+    assert!(!fake.is_stale(31));
+    assert_eq!(fake.update::<Plain>(&path).unwrap(), 0);
+}
+
+#[test]
+fn unknown_version_is_rejected() {
+    let mut bytes = Index::new(7, vec![0]).to_bytes();
+    assert_eq!(Index::from_bytes(&bytes).unwrap(), Index::new(7, vec![0]));
+    // the version follows the 4 bytes magic
+    bytes[4] = 2;
+    let err = Index::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err, SeqDataError::InvalidIndex(_)), "{:?}", err);
+}
+
+#[test]
+fn serialized_layout_is_little_endian() {
+    let index = Index::new(0x0102, vec![0, 0x0304]);
+    let mut expected = b"SDIX\x01".to_vec();
+    expected.extend_from_slice(&[0x02, 0x01, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[0; 8]);
+    expected.extend_from_slice(&[0x04, 0x03, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(index.to_bytes(), expected);
+    assert_eq!(Index::from_bytes(&expected).unwrap(), index);
+
+    let dir = TempDir::new();
+    let path = dir.join("index");
+    index.save(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+    assert_eq!(Index::load(&path).unwrap(), index);
+
+    // an offset missing from the declared count
+    let err = Index::from_bytes(&expected[..expected.len() - 8]).unwrap_err();
+    assert!(matches!(err, SeqDataError::InvalidIndex(_)), "{:?}", err);
+}
+
+#[test]
+fn iter_rev_reads_backwards() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let index = write_indexed(&path, &[b"one", b"", b"three"]);
+    let (mut reader, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    let chunks = reader
+        .iter_rev(&index)
+        .collect::<seq_data_file::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        chunks,
+        [
+            (11, b"three".to_vec()),
+            (7, b"".to_vec()),
+            (0, b"one".to_vec())
+        ]
+    );
+
+    // an index of the file after an append doesn't fit the reader opened before
+    append(&path, b"four");
+    let index = Index::build::<Plain>(&path).unwrap();
+    let mut items = reader.iter_rev(&index);
+    let err = items.next().unwrap().unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SeqDataError::IndexMismatch {
+                indexed: 28,
+                len: 20
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert!(items.next().is_none());
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "mmap")]
+mod common;
+
+use common::{TempDir, WithTrailer};
+use seq_data_file::{ReadAt, SeqData, SeqDataReaderMmap, SeqDataReaderSeek, SeqDataWriter};
+
+#[test]
+fn index_borrows_every_block() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<WithTrailer, _>(&path, &[], [&b"one"[..], b"", b"three"]).unwrap();
+
+    let (mut reader, header) = SeqDataReaderMmap::<WithTrailer>::open(&path).unwrap();
+    assert!(header.is_empty());
+    let index = reader.index().unwrap();
+    assert_eq!(index, [&b"one"[..], b"", b"three"]);
+    assert_eq!(reader.next_at(11).unwrap(), b"three");
+}
+
+#[test]
+fn unclosed_file_fails_to_map() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithTrailer>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    drop(writer);
+    // not closed, so without the trailer magic
+    assert!(SeqDataReaderMmap::<WithTrailer>::open(&path).is_err());
+}
+
+#[test]
+fn mmap_backend() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<WithTrailer, _>(&path, &[], [&b"one"[..], b"two"]).unwrap();
+
+    let file = std::fs::File::open(&path).unwrap();
+    let map = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+    assert_eq!(map.size().unwrap(), common::file_len(&path));
+    let mut buf = [0; 8];
+    assert_eq!(map.read_at(&mut buf, map.len() as u64 - 4).unwrap(), 4);
+    assert_eq!(&buf[..4], b"END!");
+
+    let (mut reader, header) = SeqDataReaderSeek::<WithTrailer, _>::from_backend(map).unwrap();
+    assert!(header.is_empty());
+    assert_eq!(reader.next().unwrap(), b"one");
+    assert_eq!(reader.next().unwrap(), b"two");
+}
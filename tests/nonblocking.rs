@@ -0,0 +1,611 @@
+#![cfg(feature = "async")]
+mod common;
+
+use common::{
+    Counted, Dirty, LongMagic, Narrow, Plain, TempDir, Validated, Wide, WithManifest, WithMeta,
+};
+use std::time::Duration;
+
+use seq_data_file::{nonblocking, SeqData, SeqDataError, SeqDataFormat, Stats};
+
+async fn read_all<Format: SeqDataFormat>(path: &std::path::Path) -> Vec<(u64, Vec<u8>)> {
+    let (mut reader, _) = nonblocking::SeqDataReader::<Format>::open(path)
+        .await
+        .unwrap();
+    let mut out = Vec::new();
+    while let Some(chunk) = reader.next().await {
+        out.push(chunk.unwrap());
+    }
+    out
+}
+
+async fn roundtrip<Format: SeqDataFormat>(header: &[u8], chunks: &[&[u8]]) -> Vec<Vec<u8>> {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Format>::create(&path, header)
+        .await
+        .unwrap();
+    for chunk in chunks {
+        writer.append(chunk).await.unwrap();
+    }
+    writer.close().await.unwrap();
+
+    let (_, read_header) = nonblocking::SeqDataReader::<Format>::open(&path)
+        .await
+        .unwrap();
+    assert_eq!(read_header, header);
+    let chunks = read_all::<Format>(&path).await;
+    chunks.into_iter().map(|(_, data)| data).collect()
+}
+
+#[tokio::test]
+async fn magic_without_header_roundtrip() {
+    let chunks: &[&[u8]] = &[b"one", b"", b"three"];
+    assert_eq!(roundtrip::<Plain>(&[], chunks).await, chunks);
+}
+
+#[tokio::test]
+async fn long_magic_roundtrip() {
+    let chunks: &[&[u8]] = &[b"one", b"two"];
+    assert_eq!(roundtrip::<LongMagic>(b"hdr", chunks).await, chunks);
+}
+
+#[tokio::test]
+async fn magic_errors() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"MAGIX").unwrap();
+    let err = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::MagicMismatch));
+
+    std::fs::write(&path, b"MAG").unwrap();
+    let err = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::FileTooShort { len: 3, min: 5 }));
+}
+
+#[tokio::test]
+async fn append_many_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Narrow>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"first").await.unwrap();
+    writer.append_many(&[b"one", b"", b"three"]).await.unwrap();
+    writer.append(b"last").await.unwrap();
+    writer.close().await.unwrap();
+
+    // each chunk takes its data and a length prefix of 2 bytes
+    let expected: Vec<(u64, Vec<u8>)> = vec![
+        (0, b"first".to_vec()),
+        (7, b"one".to_vec()),
+        (12, b"".to_vec()),
+        (14, b"three".to_vec()),
+        (21, b"last".to_vec()),
+    ];
+    assert_eq!(read_all::<Narrow>(&path).await, expected);
+}
+
+#[tokio::test]
+async fn append_many_chunk_too_large_writes_nothing() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Narrow>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"first").await.unwrap();
+    let large = vec![0; 70_000];
+    assert!(writer
+        .append_many(&[b"one", &large, b"three"])
+        .await
+        .is_err());
+    writer.close().await.unwrap();
+
+    assert_eq!(read_all::<Narrow>(&path).await, [(0, b"first".to_vec())]);
+    assert_eq!(common::file_len(&path), 6 + 2 + 5);
+}
+
+#[tokio::test]
+async fn chunks_visible_after_flush() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Narrow>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"first").await.unwrap();
+    writer.flush().await.unwrap();
+    assert_eq!(read_all::<Narrow>(&path).await, [(0, b"first".to_vec())]);
+
+    writer.append(b"second").await.unwrap();
+    writer.sync().await.unwrap();
+    assert_eq!(
+        read_all::<Narrow>(&path).await,
+        [(0, b"first".to_vec()), (7, b"second".to_vec())]
+    );
+}
+
+#[tokio::test]
+async fn failed_create_leaves_no_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let err = nonblocking::SeqDataWriter::<Validated>::create(&path, &[0])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(!path.exists());
+    assert!(
+        nonblocking::SeqDataWriter::<Validated>::create(&path, &[0, 1])
+            .await
+            .is_err()
+    );
+    assert!(!path.exists());
+
+    nonblocking::SeqDataWriter::<Validated>::create(&path, &[1])
+        .await
+        .unwrap();
+    assert!(path.exists());
+}
+
+#[tokio::test]
+async fn create_initializes_empty_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"").unwrap();
+    let mut writer = nonblocking::SeqDataWriter::<Narrow>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"first").await.unwrap();
+    writer.close().await.unwrap();
+    assert_eq!(read_all::<Narrow>(&path).await, [(0, b"first".to_vec())]);
+}
+
+#[tokio::test]
+async fn create_rejects_nonempty_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"not a seqdata file").unwrap();
+    let err = nonblocking::SeqDataWriter::<Narrow>::create(&path, &[])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read(&path).unwrap(), b"not a seqdata file");
+}
+
+#[tokio::test]
+async fn writer_rejects_chunk_count() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let err = nonblocking::SeqDataWriter::<Counted>::create(&path, b"h")
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn manifest_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<WithManifest>::create(&path, &[])
+        .await
+        .unwrap();
+    assert!(writer.append(b"early").await.is_err());
+    writer.write_manifest(b"manifest").await.unwrap();
+    assert!(writer.write_manifest(b"again").await.is_err());
+    writer.append(b"one").await.unwrap();
+    writer.close().await.unwrap();
+
+    let (reader, _) = nonblocking::SeqDataReader::<WithManifest>::open(&path)
+        .await
+        .unwrap();
+    assert_eq!(reader.manifest(), Some(&b"manifest"[..]));
+    assert_eq!(
+        read_all::<WithManifest>(&path).await,
+        [(12, b"one".to_vec())]
+    );
+
+    // the blocking reader agrees on the layout
+    let (reader, _) = seq_data_file::SeqDataReader::<WithManifest>::open(&path).unwrap();
+    assert_eq!(reader.manifest(), Some(&b"manifest"[..]));
+}
+
+#[tokio::test]
+async fn writer_rejects_dirty_flag() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let err = nonblocking::SeqDataWriter::<Dirty>::create(&path, &[0])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    assert!(!path.exists());
+
+    seq_data_file::SeqDataWriter::<Dirty>::create(&path, &[0])
+        .unwrap()
+        .close()
+        .unwrap();
+    let err = nonblocking::SeqDataWriter::<Dirty>::open(&path, &[0])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    let (reader, _) = seq_data_file::SeqDataReader::<Dirty>::open(&path).unwrap();
+    assert!(reader.was_clean());
+}
+
+#[tokio::test]
+async fn garbage_wide_prefix_is_an_error() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Wide, _>(&path, &[], [b"one", b"two"]).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    let second = Wide::MAGIC.len() + 8 + 3;
+    bytes[second..second + 8].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (mut reader, _) = nonblocking::SeqDataReader::<Wide>::open(&path)
+        .await
+        .unwrap();
+    assert_eq!(reader.next().await.unwrap().unwrap(), (0, b"one".to_vec()));
+    let err = reader.next().await.unwrap().unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::TruncatedChunk { .. }),
+        "{:?}",
+        err
+    );
+
+    let (reader, _) = nonblocking::SeqDataReaderSeek::<Wide>::open(&path)
+        .await
+        .unwrap();
+    let err = reader.read_many(&[11]).await.unwrap_err();
+    assert!(matches!(err, SeqDataError::TruncatedChunk { .. }));
+}
+
+#[test]
+fn next_timeout_resumes_after_timing_out() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    // larger than the buffer of the reader, so reading it waits for the file
+    let chunk = vec![7; 4 * 1024 * 1024];
+    SeqData::write_all::<Plain, _>(&path, &[], [&chunk[..], b"last"]).unwrap();
+
+    // with the only blocking thread busy, the reads of the file wait for it
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .max_blocking_threads(1)
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let (mut reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+            .await
+            .unwrap();
+        let busy = tokio::task::spawn_blocking(|| std::thread::sleep(Duration::from_millis(200)));
+        let err = reader
+            .next_timeout(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        busy.await.unwrap();
+
+        let long = Duration::from_secs(10);
+        assert_eq!(
+            reader.next_timeout(long).await.unwrap().unwrap(),
+            (0, chunk)
+        );
+        let (_, last) = reader.next_timeout(long).await.unwrap().unwrap();
+        assert_eq!(last, b"last");
+        assert!(reader.next_timeout(long).await.is_none());
+    });
+}
+
+#[tokio::test]
+async fn append_slice_iter_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Plain>::create(&path, &[])
+        .await
+        .unwrap();
+    let offsets = writer
+        .append_slice_iter(&[&b"one"[..], b"", b"three"])
+        .await
+        .unwrap();
+    assert_eq!(offsets, [0, 7, 11]);
+    writer.close().await.unwrap();
+    assert_eq!(
+        read_all::<Plain>(&path).await,
+        vec![
+            (0, b"one".to_vec()),
+            (7, b"".to_vec()),
+            (11, b"three".to_vec())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn append_position_is_the_next_chunk_offset() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b"three"]).unwrap();
+    let (reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    assert_eq!(reader.append_position(), 16);
+}
+
+/// Stream yielding the items of an iterator, all ready at once
+struct Ready<I>(I);
+
+impl<I: Iterator + Unpin> futures_core::Stream for Ready<I> {
+    type Item = I::Item;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<I::Item>> {
+        std::task::Poll::Ready(self.0.next())
+    }
+}
+
+#[tokio::test]
+async fn write_all_stream_from_stream() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let chunks = Ready([&b"one"[..], b"", b"three"].into_iter());
+    let count = SeqData::write_all_stream::<Plain, _>(&path, &[], chunks).await;
+    assert_eq!(count.unwrap(), 3);
+    assert_eq!(
+        read_all::<Plain>(&path).await,
+        vec![
+            (0, b"one".to_vec()),
+            (7, b"".to_vec()),
+            (11, b"three".to_vec())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn chunk_metadata_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<WithMeta>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append_with_meta(b"m1", b"one").await.unwrap();
+    writer.append_with_meta(b"m2", b"").await.unwrap();
+    assert!(writer.append_with_meta(b"m", b"x").await.is_err());
+    writer.close().await.unwrap();
+
+    let (mut reader, _) = nonblocking::SeqDataReader::<WithMeta>::open(&path)
+        .await
+        .unwrap();
+    let (offset, meta, data) = reader.next_with_meta().await.unwrap().unwrap();
+    assert_eq!((offset, &meta[..], &data[..]), (0, &b"m1"[..], &b"one"[..]));
+    let (offset, meta, data) = reader.next_with_meta().await.unwrap().unwrap();
+    assert_eq!((offset, &meta[..], &data[..]), (9, &b"m2"[..], &b""[..]));
+    assert!(reader.next_with_meta().await.is_none());
+}
+
+#[tokio::test]
+async fn next_shared_blocks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b"two"]).unwrap();
+    let (mut reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    let (offset, block) = reader.next_shared().await.unwrap().unwrap();
+    assert_eq!((offset, &*block), (0, &b"one"[..]));
+    let (offset, block) = reader.next_shared().await.unwrap().unwrap();
+    assert_eq!((offset, &*block), (7, &b"two"[..]));
+    assert!(reader.next_shared().await.is_none());
+}
+
+#[tokio::test]
+async fn read_many_in_offsets_order() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    // more chunks than reads in flight at once
+    let count = 2 * nonblocking::READ_MANY_PARALLELISM as u8;
+    SeqData::write_all::<Plain, _>(&path, &[], (0..count).map(|i| vec![i; i as usize])).unwrap();
+    let (mut sync_reader, _) = seq_data_file::SeqDataReader::<Plain>::open(&path).unwrap();
+    let offsets = sync_reader
+        .offsets()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let (mut reader, _) = nonblocking::SeqDataReaderSeek::<Plain>::open(&path)
+        .await
+        .unwrap();
+    let mut wanted: Vec<u64> = offsets.iter().rev().copied().collect();
+    wanted.push(offsets[3]);
+    let blocks = reader.read_many(&wanted).await.unwrap();
+    assert_eq!(blocks.len(), wanted.len());
+    for (block, i) in blocks.iter().zip((0..count).rev().chain([3])) {
+        assert_eq!(*block, vec![i; i as usize]);
+    }
+    assert!(reader.read_many(&[]).await.unwrap().is_empty());
+    // the reader is still at the first chunk
+    #[cfg(unix)]
+    assert_eq!(reader.next().await.unwrap(), b"");
+
+    let err = reader
+        .read_many(&[0, reader.append_position()])
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::OffsetOutOfRange { .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn append_repeated_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Plain>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"first").await.unwrap();
+    let offsets = writer.append_repeated(b"one", 3).await.unwrap();
+    assert_eq!(offsets, [9, 16, 23]);
+    writer.close().await.unwrap();
+    let chunks = read_all::<Plain>(&path).await;
+    assert_eq!(chunks.len(), 4);
+    assert!(chunks[1..].iter().all(|(_, chunk)| chunk == b"one"));
+}
+
+#[tokio::test]
+async fn append_durable_writes_through_the_buffer() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Plain>::create(&path, &[])
+        .await
+        .unwrap();
+    writer.append(b"one").await.unwrap();
+    assert_eq!(writer.append_durable(b"two").await.unwrap(), 7);
+    assert_eq!(
+        read_all::<Plain>(&path).await,
+        vec![(0, b"one".to_vec()), (7, b"two".to_vec())]
+    );
+}
+
+async fn collect<S: futures_core::Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+    let mut items = Vec::new();
+    while let Some(item) =
+        std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx)).await
+    {
+        items.push(item);
+    }
+    items
+}
+
+#[tokio::test]
+async fn into_stream_moves_into_a_task() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b"", b"three"]).unwrap();
+    let (reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    let stream = Box::pin(reader.into_stream());
+    let items = tokio::spawn(collect(stream)).await.unwrap();
+    let chunks = items.into_iter().map(|c| c.unwrap()).collect::<Vec<_>>();
+    assert_eq!(
+        chunks,
+        [
+            (0, b"one".to_vec()),
+            (7, b"".to_vec()),
+            (11, b"three".to_vec())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn seek_to_offset_rewinds_to_a_checkpoint() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b"two", b"three"]).unwrap();
+    let (mut reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    reader.next().await.unwrap().unwrap();
+    let checkpoint = reader.position();
+    assert_eq!(checkpoint, 7);
+    assert_eq!(reader.next().await.unwrap().unwrap(), (7, b"two".to_vec()));
+    reader.seek_to_offset(checkpoint).await.unwrap();
+    assert_eq!(reader.next().await.unwrap().unwrap(), (7, b"two".to_vec()));
+
+    reader.seek_to_offset(reader.len()).await.unwrap();
+    assert!(reader.next().await.is_none());
+    let err = reader.seek_to_offset(24).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SeqDataError::OffsetOutOfRange {
+                offset: 24,
+                len: 23
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn spawn_consumer_appends_received_messages() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = nonblocking::SeqDataWriter::<Plain>::create(&path, &[])
+        .await
+        .unwrap();
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    let handle = writer.spawn_consumer(rx);
+    for chunk in [&b"one"[..], b"two", b"three"] {
+        tx.send(chunk.to_vec()).await.unwrap();
+    }
+    drop(tx);
+    assert_eq!(handle.await.unwrap().unwrap(), 3);
+    assert_eq!(
+        read_all::<Plain>(&path).await,
+        [
+            (0, b"one".to_vec()),
+            (7, b"two".to_vec()),
+            (14, b"three".to_vec())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn writer_tracks_position_and_written() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = nonblocking::SeqDataWriter::<Plain>::create(&path, &[])
+        .await
+        .unwrap();
+    assert_eq!(writer.position(), 0);
+    assert_eq!((writer.bytes_written(), writer.chunks_written()), (0, 0));
+    writer.append(b"one").await.unwrap();
+    assert_eq!(writer.position(), 7);
+    writer.append_many(&[b"two", b"three"]).await.unwrap();
+    assert_eq!(writer.position(), 23);
+    assert_eq!((writer.bytes_written(), writer.chunks_written()), (23, 3));
+    writer.close().await.unwrap();
+    assert_eq!(read_all::<Plain>(&path).await.len(), 3);
+}
+
+#[tokio::test]
+async fn count_chunks_and_stats_of_remaining_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b"two", b"three"]).unwrap();
+    let (mut reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    assert_eq!(reader.count_chunks().await.unwrap(), 3);
+    assert!(reader.next().await.is_none());
+
+    let (mut reader, _) = nonblocking::SeqDataReader::<Plain>::open(&path)
+        .await
+        .unwrap();
+    reader.next().await.unwrap().unwrap();
+    assert_eq!(
+        reader.stats().await.unwrap(),
+        Stats {
+            count: 2,
+            total_len: 8,
+            min_len: 3,
+            max_len: 5
+        }
+    );
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{Plain, TempDir};
+use seq_data_file::{testutil, SeqDataReader, SeqDataWriter};
+
+fn write_and_read(path: &std::path::Path) {
+    let mut writer = SeqDataWriter::<Plain>::create(path, &[]).unwrap();
+    writer.append(b"chunk").unwrap();
+    writer.close().unwrap();
+    let (reader, _) = SeqDataReader::<Plain>::open(path).unwrap();
+    let chunks = reader.map(|c| c.unwrap().1).collect::<Vec<_>>();
+    assert_eq!(chunks, testutil::roundtrip::<Plain>(&[], &[b"chunk"]));
+}
+
+// a path longer than the 260 characters of MAX_PATH on Windows
+fn deep_dir(root: &std::path::Path) -> std::path::PathBuf {
+    let mut dir = root.to_path_buf();
+    for i in 0..12 {
+        dir.push(format!("{:02}-a-rather-long-directory-name", i));
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn deeply_nested_path() {
+    let root = TempDir::new();
+    let path = deep_dir(root.path()).join("data");
+    assert!(path.as_os_str().len() > 260);
+    write_and_read(&path);
+}
+
+#[cfg(windows)]
+#[test]
+fn verbatim_long_path() {
+    let root = TempDir::new();
+    // canonicalize gives a verbatim `\\?\` path on Windows
+    let dir = deep_dir(root.path()).canonicalize().unwrap();
+    assert!(dir.as_os_str().to_string_lossy().starts_with(r"\\?\"));
+    let path = dir.join("data");
+    assert!(path.as_os_str().len() > 260);
+    write_and_read(&path);
+}
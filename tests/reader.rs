@@ -0,0 +1,943 @@
+mod common;
+
+use std::io::IoSliceMut;
+use std::time::Duration;
+
+use common::{
+    file_len, Continued, Headered, Marked, Padded, Plain, Sentinel, TempDir, Trailing, Validated,
+    Wide, WithMeta, WithTrailer,
+};
+use seq_data_file::{
+    testutil, ChunkMeta, SeqData, SeqDataError, SeqDataFormat, SeqDataReader, SeqDataReaderSeek,
+    SeqDataStreamWriter, SeqDataWriter, TimingSummary,
+};
+
+fn write_and_cut(path: &std::path::Path, cut: u64) {
+    SeqData::write_all::<Plain, _>(path, &[], [&b"one"[..], b"two", b"three"]).unwrap();
+    let len = file_len(path);
+    let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.set_len(len - cut).unwrap();
+}
+
+#[test]
+fn for_loop_over_reader() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut chunks = Vec::new();
+    for chunk in reader {
+        chunks.push(chunk.unwrap());
+    }
+    assert_eq!(
+        chunks,
+        [
+            (0, b"one".to_vec()),
+            (7, b"two".to_vec()),
+            (14, b"three".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn collect_reader() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let chunks = reader
+        .map(|chunk| chunk.map(|(_, data)| data))
+        .collect::<seq_data_file::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        chunks,
+        testutil::roundtrip::<Plain>(&[], &[b"one", b"two", b"three"])
+    );
+}
+
+#[test]
+fn truncated_file_yields_error_item() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 2);
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let items = reader.collect::<Vec<_>>();
+    assert_eq!(items.len(), 3, "{:?}", items);
+    assert_eq!(items[0].as_ref().unwrap(), &(0, b"one".to_vec()));
+    assert_eq!(items[1].as_ref().unwrap(), &(7, b"two".to_vec()));
+    let err = items[2].as_ref().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert!(reader.collect::<seq_data_file::Result<Vec<_>>>().is_err());
+}
+
+fn numbered(path: &std::path::Path, n: u8) {
+    SeqData::write_all::<Plain, _>(path, &[], (0..n).map(|i| [i])).unwrap();
+}
+
+#[test]
+fn last_n_in_file_order() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    numbered(&path, 10);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    // each chunk takes 4 bytes of length prefix and 1 of data
+    assert_eq!(
+        reader.last_n(3).unwrap(),
+        [(35, vec![7]), (40, vec![8]), (45, vec![9])]
+    );
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn last_n_zero() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    numbered(&path, 10);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert!(reader.last_n(0).unwrap().is_empty());
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn last_n_more_than_count() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    numbered(&path, 3);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(
+        reader.last_n(10).unwrap(),
+        [(0, vec![0]), (5, vec![1]), (10, vec![2])]
+    );
+
+    // only the remaining chunks are considered
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    reader.next().unwrap().unwrap();
+    assert_eq!(reader.last_n(10).unwrap(), [(5, vec![1]), (10, vec![2])]);
+}
+
+fn skipping_errors<Format: SeqDataFormat>(path: &std::path::Path) -> (Vec<Vec<u8>>, Vec<u64>) {
+    let (mut reader, _) = SeqDataReader::<Format>::open(path).unwrap();
+    let mut skipped = Vec::new();
+    let mut chunks = Vec::new();
+    while let Some((_, chunk)) = reader.next_skipping_errors(|offset, _| skipped.push(offset)) {
+        chunks.push(chunk);
+    }
+    (chunks, skipped)
+}
+
+#[test]
+fn next_skipping_errors_resyncs_past_garbage_prefix() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let chunks: [&[u8]; 4] = [b"one", b"two", b"three", b"four"];
+    SeqData::write_all::<Plain, _>(&path, &[], chunks).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    let second = Plain::MAGIC.len() + 4 + 3;
+    bytes[second..second + 4].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (read, skipped) = skipping_errors::<Plain>(&path);
+    assert_eq!(read, [&b"one"[..], b"three", b"four"]);
+    assert_eq!(skipped, [7]);
+}
+
+#[test]
+fn next_skipping_errors_resyncs_past_garbage_wide_prefix() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let chunks: [&[u8]; 4] = [b"one", b"two", b"three", b"four"];
+    SeqData::write_all::<Wide, _>(&path, &[], chunks).unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    // overflowing once the framing is added
+    let second = Wide::MAGIC.len() + 8 + 3;
+    bytes[second..second + 8].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (read, skipped) = skipping_errors::<Wide>(&path);
+    assert_eq!(read, [&b"one"[..], b"three", b"four"]);
+    assert_eq!(skipped, [11]);
+}
+
+fn padded_file(path: &std::path::Path) {
+    let mut writer = SeqDataWriter::<Padded>::create(path, &[0; 4]).unwrap();
+    writer.append(b"one").unwrap();
+    writer.append(b"two").unwrap();
+    writer.close_padded(512).unwrap();
+    assert_eq!(file_len(path), 512);
+}
+
+#[test]
+fn next_cow_stops_at_padding() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    padded_file(&path);
+
+    let (reader, _) = SeqDataReader::<Padded>::open(&path).unwrap();
+    let mut reader = reader.with_timing();
+    let mut blocks = Vec::new();
+    while let Some(block) = reader.next_cow() {
+        let (offset, block) = block.unwrap();
+        blocks.push((offset, block.into_owned()));
+    }
+    assert_eq!(blocks, [(0, b"one".to_vec()), (7, b"two".to_vec())]);
+    assert_eq!(reader.timing_summary().unwrap().count, 2);
+}
+
+#[test]
+fn next_cow_rejects_chunk_going_into_padding() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    padded_file(&path);
+    // make the last chunk go past the data, into the zero padding
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = Padded::MAGIC.len() + 4 + 7;
+    bytes[last] = 10;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Padded>::open(&path).unwrap();
+    assert_eq!(reader.next_cow().unwrap().unwrap().1, &b"one"[..]);
+    let err = reader.next_cow().unwrap().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn next_into_empty_chunk() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&b"one"[..], b""]).unwrap();
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut buf = Vec::new();
+    assert_eq!(reader.next_into(&mut buf).unwrap().unwrap(), 0);
+    assert_eq!(buf, b"one");
+    assert_eq!(reader.next_into(&mut buf).unwrap().unwrap(), 7);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn next_into_leaves_buffer_at_end_of_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [b"one"]).unwrap();
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut buf = Vec::new();
+    reader.next_into(&mut buf).unwrap().unwrap();
+    buf = b"untouched".to_vec();
+    assert!(reader.next_into(&mut buf).is_none());
+    assert_eq!(buf, b"untouched");
+}
+
+#[test]
+fn next_into_reuses_allocation() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&[1; 100][..], &[2; 10], &[3; 50]]).unwrap();
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut buf = Vec::new();
+    reader.next_into(&mut buf).unwrap().unwrap();
+    let (ptr, capacity) = (buf.as_ptr(), buf.capacity());
+    for expected in [&[2; 10][..], &[3; 50]] {
+        reader.next_into(&mut buf).unwrap().unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, capacity));
+    }
+}
+
+#[test]
+fn prefetch_reads_the_same_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let chunks = (0..40u8).map(|i| vec![i; 100_000]).collect::<Vec<_>>();
+    SeqData::write_all::<Plain, _>(&path, &[], &chunks).unwrap();
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let read = reader
+        .with_prefetch()
+        .map(|r| r.unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(read, chunks);
+}
+
+#[test]
+fn nth_skips_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    numbered(&path, 5);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.nth(0).unwrap().unwrap(), (0, vec![0]));
+    assert_eq!(reader.nth(2).unwrap().unwrap(), (15, vec![3]));
+    assert_eq!(reader.nth(0).unwrap().unwrap(), (20, vec![4]));
+    assert!(reader.nth(0).is_none());
+
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert!(reader.nth(5).is_none());
+}
+
+fn parse_version(header: &[u8]) -> std::io::Result<u16> {
+    match u16::from_le_bytes([header[0], header[1]]) {
+        0 => Err(std::io::Error::other("version 0")),
+        version => Ok(version),
+    }
+}
+
+#[test]
+fn open_with_header_parser() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, &3u16.to_le_bytes(), [b"one"]).unwrap();
+    let (mut reader, version) =
+        SeqDataReader::<Headered>::open_with_header_parser(&path, parse_version).unwrap();
+    assert_eq!(version, 3);
+    assert_eq!(reader.next().unwrap().unwrap(), (0, b"one".to_vec()));
+
+    let (mut writer, version) =
+        SeqDataWriter::<Headered>::open_with_header_parser(&path, parse_version).unwrap();
+    assert_eq!(version, 3);
+    writer.append(b"two").unwrap();
+    writer.close().unwrap();
+    assert_eq!(common::chunks::<Headered>(&path), [b"one", b"two"]);
+
+    let path = dir.join("version0");
+    SeqData::write_all::<Headered, _>(&path, &[0, 0], [b"one"]).unwrap();
+    let err = SeqDataReader::<Headered>::open_with_header_parser(&path, parse_version)
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "version 0");
+    assert!(SeqDataWriter::<Headered>::open_with_header_parser(&path, parse_version).is_err());
+}
+
+#[test]
+fn offsets_without_reading_data() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let offsets = reader.offsets().collect::<seq_data_file::Result<Vec<_>>>();
+    assert_eq!(offsets.unwrap(), [0, 7, 14]);
+    assert!(reader.next().is_none());
+
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.offsets().next().unwrap().unwrap(), 0);
+    assert_eq!(reader.next().unwrap().unwrap(), (7, b"two".to_vec()));
+
+    // a chunk cut short ends the iterator with an error
+    write_and_cut(&dir.join("cut"), 2);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(dir.join("cut")).unwrap();
+    let offsets = reader.offsets().collect::<Vec<_>>();
+    assert_eq!(offsets.len(), 3);
+    assert!(offsets[2].is_err());
+}
+
+#[test]
+fn read_ending_at_walks_backwards() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Trailing, _>(&path, &[], [&b"one"[..], b"", b"three"]).unwrap();
+    let (mut reader, _) = SeqDataReaderSeek::<Trailing>::open(&path).unwrap();
+    let mut end = reader.append_position();
+    assert_eq!(end, 32);
+    let mut chunks = Vec::new();
+    while end > 0 {
+        let (pos, data) = reader.read_ending_at(end).unwrap();
+        chunks.push((pos, data));
+        end = pos;
+    }
+    assert_eq!(
+        chunks,
+        [
+            (19, b"three".to_vec()),
+            (11, b"".to_vec()),
+            (0, b"one".to_vec())
+        ]
+    );
+
+    let err = reader.read_ending_at(33).unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::OffsetOutOfRange {
+            offset: 33,
+            len: 32
+        }
+    ));
+    write_and_cut(&dir.join("plain"), 0);
+    let (mut reader, _) = SeqDataReaderSeek::<Plain>::open(dir.join("plain")).unwrap();
+    let err = reader.read_ending_at(7).unwrap_err();
+    assert!(matches!(err, SeqDataError::Unsupported(_)));
+}
+
+#[test]
+fn append_position_is_the_next_chunk_offset() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let (seek, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.append_position(), 23);
+    assert_eq!(seek.append_position(), 23);
+
+    let (mut writer, _) = SeqDataWriter::<Plain>::open(&path, &[]).unwrap();
+    assert_eq!(writer.position(), 23);
+    writer.append(b"four").unwrap();
+    writer.close().unwrap();
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.nth(3).unwrap().unwrap(), (23, b"four".to_vec()));
+}
+
+#[test]
+fn sentinel_mismatch_then_resync() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let chunks: [&[u8]; 4] = [b"one", b"two", b"three", b"four"];
+    SeqData::write_all::<Sentinel, _>(&path, &[], chunks).unwrap();
+    assert_eq!(common::chunks::<Sentinel>(&path), chunks);
+
+    // break the sentinel after "two"
+    let mut bytes = std::fs::read(&path).unwrap();
+    let sentinel = Sentinel::MAGIC.len() + 9 + 4 + 3;
+    assert_eq!(bytes[sentinel..sentinel + 2], *Sentinel::SENTINEL);
+    bytes[sentinel] = 0;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Sentinel>::open(&path).unwrap();
+    reader.next().unwrap().unwrap();
+    let err = reader.next().unwrap().unwrap_err();
+    assert!(
+        matches!(&err, SeqDataError::CorruptedChunk { offset: 9, error }
+            if matches!(**error, SeqDataError::SentinelMismatch)),
+        "{:?}",
+        err
+    );
+
+    // the scan restarts after the next intact sentinel, the one after "three"
+    let (read, skipped) = skipping_errors::<Sentinel>(&path);
+    assert_eq!(read, [&b"one"[..], b"four"]);
+    assert_eq!(skipped, [9]);
+}
+
+#[test]
+fn chunk_metadata_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithMeta>::create(&path, &[]).unwrap();
+    writer.append_with_meta(b"m1", b"one").unwrap();
+    writer.append_with_meta(b"m2", b"").unwrap();
+    writer.close().unwrap();
+
+    let (mut reader, _) = SeqDataReader::<WithMeta>::open(&path).unwrap();
+    let meta = |offset, meta: &[u8], data: &[u8]| (offset, meta.to_vec(), data.to_vec());
+    assert_eq!(
+        reader.next_with_meta().unwrap().unwrap(),
+        meta(0, b"m1", b"one")
+    );
+    assert_eq!(
+        reader.next_with_meta().unwrap().unwrap(),
+        meta(9, b"m2", b"")
+    );
+    assert!(reader.next_with_meta().is_none());
+    // the plain blocks start with the metadata
+    assert_eq!(common::chunks::<WithMeta>(&path), [&b"m1one"[..], b"m2"]);
+}
+
+#[test]
+fn next_shared_blocks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let (offset, block) = reader.next_shared().unwrap().unwrap();
+    let shared = block.clone();
+    assert_eq!((offset, &*shared), (0, &b"one"[..]));
+    assert!(std::sync::Arc::ptr_eq(&block, &shared));
+    let (offset, block) = reader.next_shared().unwrap().unwrap();
+    assert_eq!((offset, &*block), (7, &b"two"[..]));
+}
+
+#[test]
+fn records_span_continued_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    // 15 bits of length per chunk, so split in 3 chunks
+    let large: Vec<u8> = (0..70_000u32).map(|i| i as u8).collect();
+    let mut writer = SeqDataWriter::<Continued>::create(&path, &[]).unwrap();
+    writer.append_large(b"small").unwrap();
+    writer.append_large(&large).unwrap();
+    writer.append_large(b"").unwrap();
+    writer.close().unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Continued>::open(&path).unwrap();
+    assert_eq!(
+        reader.next_record().unwrap().unwrap(),
+        (0, b"small".to_vec())
+    );
+    assert_eq!(reader.next_record().unwrap().unwrap(), (7, large.clone()));
+    let (_, empty) = reader.next_record().unwrap().unwrap();
+    assert!(empty.is_empty());
+    assert!(reader.next_record().is_none());
+    let pieces = common::chunks::<Continued>(&path);
+    assert_eq!(pieces.len(), 5);
+    assert_eq!(pieces[1..4].concat(), large);
+
+    // a record cut after its first chunk
+    let len = 7 + 2 + pieces[1].len() as u64;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_len(Continued::MAGIC.len() as u64 + len)
+        .unwrap();
+    let (mut reader, _) = SeqDataReader::<Continued>::open(&path).unwrap();
+    reader.next_record().unwrap().unwrap();
+    let err = reader.next_record().unwrap().unwrap_err();
+    assert!(matches!(err, SeqDataError::TruncatedRecord), "{:?}", err);
+
+    let mut writer = SeqDataWriter::<Plain>::create(dir.join("plain"), &[]).unwrap();
+    let err = writer.append_large(b"record").unwrap_err();
+    assert!(matches!(err, SeqDataError::Unsupported(_)));
+}
+
+#[test]
+fn read_at_vectored_scatters_the_block() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    let (mut a, mut b, mut c) = ([0; 2], [0; 2], [0xff; 4]);
+    let mut bufs = [
+        IoSliceMut::new(&mut a),
+        IoSliceMut::new(&mut b),
+        IoSliceMut::new(&mut c),
+    ];
+    assert_eq!(reader.read_at_vectored(14, &mut bufs).unwrap(), 5);
+    assert_eq!((&a, &b, &c), (b"th", b"re", &[b'e', 0xff, 0xff, 0xff]));
+
+    let mut small = [0; 4];
+    let err = reader
+        .read_at_vectored(14, &mut [IoSliceMut::new(&mut small)])
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::BufferTooSmall {
+            capacity: 4,
+            len: 5
+        }
+    ));
+}
+
+#[test]
+fn readers_try_from_path() -> seq_data_file::Result<()> {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let mut reader = SeqDataReader::<Plain>::try_from(path.as_path())?;
+    assert_eq!(reader.next().unwrap()?, (0, b"one".to_vec()));
+    let mut seek: SeqDataReaderSeek<Plain> = path.as_path().try_into()?;
+    assert_eq!(seek.next_at(7)?, b"two");
+
+    let missing = dir.join("missing");
+    assert!(SeqDataReader::<Plain>::try_from(missing.as_path()).is_err());
+    let err = SeqDataReaderSeek::<Headered>::try_from(path.as_path())
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::MagicMismatch));
+    Ok(())
+}
+
+#[test]
+fn header_validated_on_open() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Validated, _>(&path, &[1], [b"one"]).unwrap();
+    let (_, header) = SeqDataReader::<Validated>::open(&path).unwrap();
+    assert_eq!(header, [1]);
+
+    // a header rejected by the format, written behind its back
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[Validated::MAGIC.len()] = 0;
+    std::fs::write(&path, &bytes).unwrap();
+    let err = SeqDataReader::<Validated>::open(&path).err().unwrap();
+    assert!(
+        matches!(&err, SeqDataError::InvalidHeader(e) if e == "version 0 is reserved"),
+        "{:?}",
+        err
+    );
+    assert!(SeqDataReaderSeek::<Validated>::open(&path).is_err());
+    assert!(SeqDataWriter::<Validated>::open(&path, &[1]).is_err());
+    assert!(SeqDataStreamWriter::<_, Validated>::new(Vec::new(), &[0]).is_err());
+}
+
+#[test]
+fn read_at_slice_of_a_block() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.read_at_slice(14, 1, 3).unwrap(), b"hre");
+    assert_eq!(reader.read_at_slice(14, 5, 0).unwrap(), b"");
+    assert_eq!(reader.read_at_slice(7, 0, 3).unwrap(), b"two");
+
+    let err = reader.read_at_slice(7, 1, 3).unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::OffsetOutOfRange { offset: 4, len: 3 }
+    ));
+    let err = reader.read_at_slice(23, 0, 1).unwrap_err();
+    assert!(matches!(
+        err,
+        SeqDataError::OffsetOutOfRange { offset: 23, .. }
+    ));
+}
+
+#[test]
+fn physical_offsets_in_the_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, b"v1", [&b"one"[..], b"two"]).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let (mut reader, _) = SeqDataReader::<Headered>::open(&path).unwrap();
+    reader.next().unwrap().unwrap();
+    let (offset, _) = reader.next().unwrap().unwrap();
+
+    // the length prefix of the chunk is at its physical offset
+    let physical = reader.to_physical(offset) as usize;
+    assert_eq!(physical, 6 + 2 + 7);
+    assert_eq!(bytes[physical..physical + 4], 3u32.to_le_bytes());
+    assert_eq!(&bytes[physical + 4..], b"two");
+    assert_eq!(reader.to_logical(physical as u64), Some(offset));
+    assert_eq!(reader.to_logical(8), Some(0));
+    assert_eq!(reader.to_logical(7), None);
+}
+
+#[test]
+fn readers_over_bytes_in_memory() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<WithTrailer, _>(&path, &[], [&b"one"[..], b"two"]).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+
+    let (reader, header) = SeqDataReader::<WithTrailer, _>::from_bytes(bytes.clone()).unwrap();
+    assert!(header.is_empty());
+    let chunks = reader.map(|c| c.unwrap()).collect::<Vec<_>>();
+    assert_eq!(chunks, [(0, b"one".to_vec()), (7, b"two".to_vec())]);
+
+    let cursor = std::io::Cursor::new(&bytes[..]);
+    let (mut reader, _) = SeqDataReader::<WithTrailer, _>::from_reader(cursor).unwrap();
+    assert_eq!(reader.nth(1).unwrap().unwrap(), (7, b"two".to_vec()));
+
+    let (mut seek, _) = SeqDataReaderSeek::<WithTrailer, _>::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(seek.next_at(7).unwrap(), b"two");
+
+    // the same checks as a file, here a missing trailer magic
+    let cut = bytes[..bytes.len() - 1].to_vec();
+    let err = SeqDataReader::<WithTrailer, _>::from_bytes(cut)
+        .err()
+        .unwrap();
+    assert!(matches!(err, SeqDataError::TrailerMismatch), "{:?}", err);
+}
+
+#[test]
+fn open_with_known_len() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let len = file_len(&path);
+    let (reader, _) = SeqDataReader::<Plain>::open_with_len(&path, len).unwrap();
+    assert_eq!(reader.append_position(), 23);
+    assert_eq!(reader.count(), 3);
+
+    // a length cut at a chunk boundary, e.g. the size at an earlier point
+    let (reader, _) = SeqDataReader::<Plain>::open_with_len(&path, 5 + 14).unwrap();
+    assert_eq!(reader.append_position(), 14);
+    let err = SeqDataReader::<Plain>::open_with_len(&path, 4)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, SeqDataError::FileTooShort { len: 4, min: 5 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn open_allow_empty_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    assert!(SeqDataReader::<Plain>::open_allow_empty(&path)
+        .unwrap()
+        .is_none());
+
+    std::fs::write(&path, b"").unwrap();
+    assert!(SeqDataReader::<Plain>::open_allow_empty(&path)
+        .unwrap()
+        .is_none());
+
+    // only missing or zero byte files are relaxed
+    std::fs::write(&path, b"PLA").unwrap();
+    let err = SeqDataReader::<Plain>::open_allow_empty(&path)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, SeqDataError::FileTooShort { len: 3, min: 5 }),
+        "{:?}",
+        err
+    );
+
+    std::fs::remove_file(&path).unwrap();
+    write_and_cut(&path, 0);
+    let (reader, _) = SeqDataReader::<Plain>::open_allow_empty(&path)
+        .unwrap()
+        .unwrap();
+    assert_eq!(reader.count(), 3);
+}
+
+#[test]
+fn read_chunks_piecewise() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.bytes_remaining_in_chunk(), None);
+    assert_eq!(reader.read_in_chunk(&mut [0; 4]).unwrap(), 0);
+
+    let mut buf = [0; 2];
+    assert_eq!(reader.start_chunk().unwrap().unwrap(), (0, 3));
+    assert_eq!(reader.read_in_chunk(&mut buf).unwrap(), 2);
+    assert_eq!(&buf, b"on");
+    assert_eq!(reader.bytes_remaining_in_chunk(), Some(1));
+
+    // starting the next block skips the rest of this one
+    assert_eq!(reader.start_chunk().unwrap().unwrap(), (7, 3));
+    let mut buf = [0; 8];
+    assert_eq!(reader.read_in_chunk(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"two");
+    assert_eq!(reader.bytes_remaining_in_chunk(), Some(0));
+    assert_eq!(reader.read_in_chunk(&mut buf).unwrap(), 0);
+    assert_eq!(reader.bytes_remaining_in_chunk(), None);
+
+    assert_eq!(reader.start_chunk().unwrap().unwrap(), (14, 5));
+    assert!(reader.start_chunk().is_none());
+}
+
+#[test]
+fn chunk_reader_streams_a_block() {
+    use std::io::Read;
+
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let (offset, mut chunk) = reader.chunk_reader().unwrap().unwrap();
+    assert_eq!(offset, 0);
+    let mut data = Vec::new();
+    chunk.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"one");
+    drop(chunk);
+
+    // dropping the reader part way skips the rest of the block
+    let (offset, mut chunk) = reader.chunk_reader().unwrap().unwrap();
+    assert_eq!(offset, 7);
+    let mut buf = [0; 1];
+    chunk.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"t");
+    drop(chunk);
+    assert_eq!(reader.next().unwrap().unwrap(), (14, b"three".to_vec()));
+    assert!(reader.chunk_reader().is_none());
+}
+
+#[test]
+fn resync_to_sync_marker() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Marked>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    assert_eq!(writer.append_sync_marker().unwrap(), 7);
+    writer.append(b"two").unwrap();
+    assert_eq!(writer.append_sync_marker().unwrap(), 22);
+    writer.append(b"three").unwrap();
+    writer.close().unwrap();
+
+    // break the length prefix of "one"
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[Marked::MAGIC.len() + 3] = 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+
+    let (mut reader, _) = SeqDataReader::<Marked>::open(&path).unwrap();
+    assert!(reader.next().unwrap().is_err());
+    assert_eq!(reader.resync_to_marker().unwrap(), Some(7));
+    assert_eq!(reader.next().unwrap().unwrap(), (15, b"two".to_vec()));
+    let (_, marker) = reader.next().unwrap().unwrap();
+    assert!(SeqDataReader::<Marked>::is_sync_marker(&marker));
+    assert!(!SeqDataReader::<Marked>::is_sync_marker(b"two"));
+    // no marker left past the one just read
+    assert_eq!(reader.resync_to_marker().unwrap(), None);
+    assert!(reader.next().is_none());
+
+    let mut writer = SeqDataWriter::<Plain>::create(dir.join("plain"), &[]).unwrap();
+    let err = writer.append_sync_marker().unwrap_err();
+    assert!(matches!(err, SeqDataError::Unsupported(_)), "{:?}", err);
+    assert!(!SeqDataReader::<Plain>::is_sync_marker(b"SYNC"));
+}
+
+#[test]
+fn scan_chunks_without_reading_data() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    reader.next().unwrap().unwrap();
+    // indices are counted from the current position
+    let metas = reader.scan_chunks().map(|m| m.unwrap()).collect::<Vec<_>>();
+    assert_eq!(
+        metas,
+        [
+            ChunkMeta {
+                index: 0,
+                offset: 7,
+                len: 3
+            },
+            ChunkMeta {
+                index: 1,
+                offset: 14,
+                len: 5
+            },
+        ]
+    );
+    assert!(reader.next().is_none());
+
+    // stops after the first error
+    write_and_cut(&dir.join("cut"), 1);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(dir.join("cut")).unwrap();
+    let metas = reader.scan_chunks().collect::<Vec<_>>();
+    assert_eq!(metas.len(), 3);
+    assert!(metas[2].is_err());
+}
+
+#[test]
+fn skip_chunks_and_read_index_range() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(reader.skip_chunks(1).unwrap(), 1);
+    assert_eq!(reader.next().unwrap().unwrap(), (7, b"two".to_vec()));
+    // fewer skipped at the end of file
+    assert_eq!(reader.skip_chunks(5).unwrap(), 1);
+    assert!(reader.next().is_none());
+
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(
+        reader.read_index_range(1, 5).unwrap(),
+        [(7, b"two".to_vec()), (14, b"three".to_vec())]
+    );
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    assert_eq!(
+        reader.read_index_range(0, 1).unwrap(),
+        [(0, b"one".to_vec())]
+    );
+    // indices are counted from the current position
+    assert_eq!(reader.read_index_range(1, 1).unwrap(), []);
+    assert_eq!(reader.next().unwrap().unwrap(), (14, b"three".to_vec()));
+    assert!(reader.read_index_range(4, 9).unwrap().is_empty());
+
+    let err = reader.read_index_range(2, 1).unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::InvalidRange { start: 2, end: 1 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn open_expect_header_mismatch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, b"v1", [b"one"]).unwrap();
+    let reader = SeqDataReader::<Headered>::open_expect_header(&path, b"v1").unwrap();
+    assert_eq!(reader.count(), 1);
+
+    let err = SeqDataReader::<Headered>::open_expect_header(&path, b"v2")
+        .err()
+        .unwrap();
+    assert!(
+        matches!(&err, SeqDataError::HeaderMismatch { expected, got }
+            if expected == b"v2" && got == b"v1"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn timing_summary_of_next() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    reader.next().unwrap().unwrap();
+    assert_eq!(reader.timing_summary(), None);
+
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut reader = reader.with_timing();
+    assert_eq!(reader.timing_summary(), Some(TimingSummary::default()));
+    while let Some(chunk) = reader.next() {
+        chunk.unwrap();
+    }
+    let summary = reader.timing_summary().unwrap();
+    assert_eq!(summary.count, 3);
+    assert!(summary.min <= summary.avg() && summary.avg() <= summary.max);
+    assert!(summary.max <= summary.total);
+
+    let summary = TimingSummary {
+        count: 4,
+        total: Duration::from_millis(10),
+        min: Duration::from_millis(1),
+        max: Duration::from_millis(4),
+    };
+    assert_eq!(summary.avg(), Duration::from_micros(2500));
+    assert_eq!(TimingSummary::default().avg(), Duration::ZERO);
+}
+
+#[test]
+fn read_first_chunk_only() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, b"hd", [&b"one"[..], b"two"]).unwrap();
+    let (header, first) = SeqDataReader::<Headered>::read_first(&path).unwrap();
+    assert_eq!(header, b"hd");
+    assert_eq!(first.unwrap(), b"one");
+
+    let empty = dir.join("empty");
+    SeqData::write_all::<Headered, _>(&empty, b"hd", Vec::<&[u8]>::new()).unwrap();
+    assert_eq!(
+        SeqDataReader::<Headered>::read_first(&empty).unwrap(),
+        (b"hd".to_vec(), None)
+    );
+}
+
+#[test]
+fn warm_range_checks_bounds() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_and_cut(&path, 0);
+    let (mut seek, _) = SeqDataReaderSeek::<Plain>::open(&path).unwrap();
+    seek.warm_range(7, 23).unwrap();
+    seek.warm_range(7, 7).unwrap();
+    assert_eq!(seek.next_at(7).unwrap(), b"two");
+
+    let err = seek.warm_range(7, 24).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SeqDataError::OffsetOutOfRange {
+                offset: 24,
+                len: 23
+            }
+        ),
+        "{:?}",
+        err
+    );
+    let err = seek.warm_range(14, 7).unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::InvalidRange { start: 14, end: 7 }),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "bincode")]
+mod common;
+
+use common::{Plain, TempDir};
+use seq_data_file::{SeqData, SeqDataError, SeqDataReader, SeqDataWriter};
+
+#[test]
+fn serialize_roundtrip() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let values = [
+        (1u32, "one".to_string()),
+        (2, String::new()),
+        (3, "three".into()),
+    ];
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    for value in &values {
+        writer.append_serialize(value).unwrap();
+    }
+    drop(writer);
+
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let read = reader
+        .iter_deserialize::<(u32, String)>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(read, values);
+}
+
+#[test]
+fn deserialize_failure_is_encoding_error() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [&8u64.to_le_bytes()[..], b"ab"]).unwrap();
+
+    let (reader, _) = SeqDataReader::<Plain>::open(&path).unwrap();
+    let mut values = reader.iter_deserialize::<u64>();
+    assert_eq!(values.next().unwrap().unwrap(), 8);
+    let err = values.next().unwrap().unwrap_err();
+    assert!(matches!(err, SeqDataError::Encoding(_)));
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(values.next().is_none());
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use common::{chunks, file_len, Headered, Other, Plain, TempDir, WithManifest, WithMeta};
+use seq_data_file::{
+    ChunkMeta, DynSeqDataFormat, NoMagicNoHeader, SeqData, SeqDataError, SeqDataReader,
+    SeqDataWriter, VerifyResult,
+};
+
+#[test]
+fn swap_exchanges_files() {
+    let dir = TempDir::new();
+    let (a, b) = (dir.join("a"), dir.join("b"));
+    SeqData::write_all::<Plain, _>(&a, &[], [b"first"]).unwrap();
+    SeqData::write_all::<Plain, _>(&b, &[], [b"second"]).unwrap();
+
+    SeqData::swap::<Plain>(&a, &b).unwrap();
+    assert_eq!(chunks::<Plain>(&a), [b"second"]);
+    assert_eq!(chunks::<Plain>(&b), [b"first"]);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn remove_keeps_file_of_other_format() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Other, _>(&path, &[], [b"chunk"]).unwrap();
+    let err = SeqData::remove::<Plain>(&path).unwrap_err();
+    assert!(matches!(err, SeqDataError::MagicMismatch));
+    assert!(path.exists());
+
+    SeqData::remove::<Other>(&path).unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn verify_against_hashes_data_only() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithMeta>::create(&path, &[]).unwrap();
+    writer.append_with_meta(b"m1", b"first").unwrap();
+    let offset = writer.position();
+    writer.append_with_meta(b"m2", b"second").unwrap();
+    writer.append_with_meta(b"m3", b"third").unwrap();
+    writer.close().unwrap();
+
+    let expected = [
+        crc32fast::hash(b"first"),
+        crc32fast::hash(b"second"),
+        crc32fast::hash(b"third"),
+    ];
+    assert_eq!(
+        SeqData::verify_against::<WithMeta>(&path, &expected).unwrap(),
+        VerifyResult::Valid
+    );
+
+    let wrong = [expected[0], expected[2], expected[2]];
+    assert_eq!(
+        SeqData::verify_against::<WithMeta>(&path, &wrong).unwrap(),
+        VerifyResult::Mismatch {
+            index: 1,
+            offset,
+            expected: expected[2],
+            actual: expected[1],
+        }
+    );
+}
+
+#[test]
+fn count_chunks_of_truncated_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [b"chunk"; 5]).unwrap();
+    assert_eq!(SeqData::count_chunks::<Plain>(&path).unwrap(), 5);
+
+    // cut in the middle of the data of the last chunk
+    let len = common::file_len(&path);
+    let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - 2).unwrap();
+    let err = SeqData::count_chunks::<Plain>(&path).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            SeqDataError::TruncatedChunk {
+                len: Some(5),
+                available: 3
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn merge_by_keeps_input_order_of_equal_keys() {
+    let dir = TempDir::new();
+    let (a, b, out) = (dir.join("a"), dir.join("b"), dir.join("out"));
+    SeqData::write_all::<Plain, _>(&a, &[], [&b"a1"[..], b"c1", b"c1'"]).unwrap();
+    SeqData::write_all::<Plain, _>(&b, &[], [&b"a2"[..], b"b2", b"c2"]).unwrap();
+
+    let count = SeqData::merge_by::<Plain, _, _, _>(&[&a, &b], &out, &[], |c| c[0]).unwrap();
+    assert_eq!(count, 6);
+    assert_eq!(
+        chunks::<Plain>(&out),
+        [&b"a1"[..], b"a2", b"b2", b"c1", b"c1'", b"c2"]
+    );
+}
+
+#[test]
+fn describe_lists_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, b"v1", [&b"one"[..], b"", b"three"]).unwrap();
+
+    let description = SeqData::describe::<Headered>(&path, None).unwrap();
+    assert_eq!(description.magic, b"HEADER");
+    assert_eq!(description.header, b"v1");
+    assert_eq!(description.data_len, 20);
+    assert!(description.complete);
+    let meta = |index, offset, len| ChunkMeta { index, offset, len };
+    assert_eq!(
+        description.chunks,
+        [meta(0, 0, 3), meta(1, 7, 0), meta(2, 11, 5)]
+    );
+    assert_eq!(
+        description.to_string(),
+        concat!(
+            "magic: 484541444552\n",
+            "header: 7631\n",
+            "data length: 20\n",
+            "   index       offset       length\n",
+            "       0            0            3\n",
+            "       1            7            0\n",
+            "       2           11            5\n",
+        )
+    );
+
+    let description = SeqData::describe::<Headered>(&path, Some(2)).unwrap();
+    assert_eq!(description.chunks.len(), 2);
+    assert!(!description.complete);
+    assert!(description.to_string().ends_with("...\n"));
+    assert!(
+        SeqData::describe::<Headered>(&path, Some(3))
+            .unwrap()
+            .complete
+    );
+}
+
+#[test]
+fn write_all_from_iterator() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let count = SeqData::write_all::<Plain, _>(&path, &[], (1..=3).map(|n| vec![n; n as usize]));
+    assert_eq!(count.unwrap(), 3);
+    assert_eq!(chunks::<Plain>(&path), [vec![1], vec![2, 2], vec![3, 3, 3]]);
+    assert!(SeqData::write_all::<Plain, _>(&path, &[], [b"again"]).is_err());
+    assert_eq!(chunks::<Plain>(&path).len(), 3);
+
+    // the first chunk is the manifest
+    let path = dir.join("manifest");
+    let count = SeqData::write_all::<WithManifest, _>(&path, &[], [&b"manifest"[..], b"one"]);
+    assert_eq!(count.unwrap(), 2);
+    let (reader, _) = SeqDataReader::<WithManifest>::open(&path).unwrap();
+    assert_eq!(reader.manifest(), Some(&b"manifest"[..]));
+    assert_eq!(chunks::<WithManifest>(&path), [b"one"]);
+}
+
+#[test]
+fn detect_format_among_candidates() {
+    let dir = TempDir::new();
+    let (plain, other) = (dir.join("plain"), dir.join("other"));
+    SeqData::write_all::<Plain, _>(&plain, &[], [b"one"]).unwrap();
+    SeqData::write_all::<Other, _>(&other, &[], [b"one"]).unwrap();
+
+    let candidates: &[&dyn DynSeqDataFormat] = &[&Headered, &Other, &Plain];
+    assert_eq!(SeqData::detect_format(&plain, candidates).unwrap(), Some(2));
+    assert_eq!(SeqData::detect_format(&other, candidates).unwrap(), Some(1));
+    assert_eq!(
+        SeqData::detect_format(&plain, &[&Headered, &Other]).unwrap(),
+        None
+    );
+    // an empty magic matches anything
+    let candidates: &[&dyn DynSeqDataFormat] = &[&NoMagicNoHeader, &Plain];
+    assert_eq!(SeqData::detect_format(&plain, candidates).unwrap(), Some(0));
+
+    // a file too short for the header of the format
+    let short = dir.join("short");
+    std::fs::write(&short, b"HEADER\x01").unwrap();
+    assert_eq!(SeqData::detect_format(&short, &[&Headered]).unwrap(), None);
+}
+
+#[test]
+fn is_valid_checks_magic_and_size() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Headered, _>(&path, b"v1", [b"one"]).unwrap();
+    assert!(SeqData::is_valid::<Headered>(&path).unwrap());
+    assert!(!SeqData::is_valid::<Plain>(&path).unwrap());
+
+    // the magic alone, without room for the header
+    std::fs::write(&path, b"HEADER\x01").unwrap();
+    assert!(!SeqData::is_valid::<Headered>(&path).unwrap());
+    assert!(SeqData::is_valid::<Headered>(dir.join("missing")).is_err());
+}
+
+#[test]
+fn verify_reports_chunks_or_first_error() {
+    let dir = TempDir::new();
+    let (good, cut) = (dir.join("good"), dir.join("cut"));
+    SeqData::write_all::<Headered, _>(&good, b"hd", [&b"one"[..], b"two"]).unwrap();
+    let report = SeqData::verify::<Headered>(&good).unwrap();
+    assert_eq!(report.header, b"hd");
+    assert_eq!(report.chunks, 2);
+    assert_eq!(report.data_len, 14);
+
+    std::fs::copy(&good, &cut).unwrap();
+    let file = std::fs::OpenOptions::new().write(true).open(&cut).unwrap();
+    file.set_len(file_len(&good) - 1).unwrap();
+    let err = SeqData::verify::<Headered>(&cut).unwrap_err();
+    assert!(
+        matches!(err, SeqDataError::TruncatedChunk { .. }),
+        "{:?}",
+        err
+    );
+
+    let paths = [good.clone(), cut.clone(), dir.join("missing")];
+    let results = SeqData::verify_many::<Headered>(&paths);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].0, good);
+    assert_eq!(results[0].1.as_ref().unwrap().chunks, 2);
+    assert_eq!(results[1].0, cut);
+    assert!(results[1].1.is_err());
+    assert!(results[2].1.is_err());
+}
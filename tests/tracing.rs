@@ -0,0 +1,87 @@
+#![cfg(feature = "tracing")]
+mod common;
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use common::{Other, Plain, TempDir};
+use seq_data_file::{SeqData, SeqDataReader};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Subscriber keeping the fields of every event, formatted as `name=value`
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        write!(self.0, " {}={:?}", field.name(), value).unwrap();
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(fields.0);
+    }
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+}
+
+fn recorded(f: impl FnOnce()) -> Vec<String> {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), f);
+    let events = recorder.0.lock().unwrap().clone();
+    events
+}
+
+#[test]
+fn open_emits_events() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [b"one"]).unwrap();
+
+    let events = recorded(|| assert!(SeqDataReader::<Plain>::open(&path).is_ok()));
+    assert!(
+        events.iter().any(|e| e.contains("open for reading")),
+        "{:?}",
+        events
+    );
+    assert!(!events.iter().any(|e| e.contains("seqdata error")));
+}
+
+#[test]
+fn errors_emit_their_operation() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let events = recorded(|| assert!(SeqDataReader::<Plain>::open(&path).is_err()));
+    assert!(
+        events
+            .iter()
+            .any(|e| e.contains("operation=\"open\"") && e.contains("seqdata error")),
+        "{:?}",
+        events
+    );
+
+    SeqData::write_all::<Other, _>(&path, &[], [b"one"]).unwrap();
+    let events = recorded(|| assert!(SeqDataReader::<Plain>::open(&path).is_err()));
+    assert!(
+        events
+            .iter()
+            .any(|e| e.contains("operation=\"header\"") && e.contains("magic")),
+        "{:?}",
+        events
+    );
+}
@@ -0,0 +1,808 @@
+mod common;
+
+use std::io::Write;
+
+use common::{
+    chunks, file_len, read_all, Checksummed, Narrow, Plain, TempDir, Validated, WithMeta,
+};
+use seq_data_file::{
+    NoMagicNoHeader, SeqData, SeqDataError, SeqDataFormat, SeqDataReader, SeqDataStreamWriter,
+    SeqDataWriter, DEDUP_MAX_LEN, REPEAT_WRITE_SIZE,
+};
+
+struct Framed;
+impl SeqDataFormat for Framed {
+    const MAGIC: &'static [u8] = b"FRAMED";
+    const HEADER_SIZE: usize = 2;
+    const TRAILING_LENGTH: bool = true;
+}
+
+/// Sink accepting `budget` bytes, then failing every write
+struct FailingSink {
+    data: Vec<u8>,
+    budget: usize,
+}
+
+impl Write for FailingSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.budget == 0 {
+            return Err(std::io::Error::other("sink full"));
+        }
+        let n = buf.len().min(self.budget);
+        self.data.extend_from_slice(&buf[..n]);
+        self.budget -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// magic, header and a first chunk of 5 bytes with its trailing length
+const FIRST_CHUNK_END: usize = 6 + 2 + 4 + 5 + 4;
+
+fn failing_writer(extra: usize) -> SeqDataStreamWriter<FailingSink, Framed> {
+    let sink = FailingSink {
+        data: Vec::new(),
+        budget: FIRST_CHUNK_END + extra,
+    };
+    let mut writer = SeqDataStreamWriter::<_, Framed>::new(sink, b"hd").unwrap();
+    writer.append(b"first").unwrap();
+    writer
+}
+
+#[test]
+fn try_append_rolls_back_partial_write() {
+    let mut writer = failing_writer(7).with_truncate(|sink, len| {
+        sink.data.truncate(len as usize);
+        Ok(())
+    });
+    let offset = writer.position();
+    let err = writer.try_append(b"second").unwrap_err();
+    assert_eq!(err.offset, offset);
+    assert_eq!(err.written, 7);
+    assert!(err.rolled_back);
+    assert_eq!(writer.get_ref().data.len(), FIRST_CHUNK_END);
+    assert_eq!(writer.position(), offset);
+}
+
+#[test]
+fn try_append_without_truncate_reports_no_rollback() {
+    let mut writer = failing_writer(3);
+    let err = writer.try_append(b"second").unwrap_err();
+    assert_eq!(err.written, 3);
+    assert!(!err.rolled_back);
+    assert_eq!(writer.get_ref().data.len(), FIRST_CHUNK_END + 3);
+}
+
+#[test]
+fn try_append_nothing_written() {
+    let mut writer = failing_writer(0);
+    let err = writer.try_append(b"second").unwrap_err();
+    assert_eq!(err.written, 0);
+    assert!(err.rolled_back);
+}
+
+#[test]
+fn try_append_with_meta() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<WithMeta>::create(&path, &[]).unwrap();
+    let len = file_len(&path);
+    let err = writer.try_append(b"data").unwrap_err();
+    assert_eq!(err.written, 0);
+    assert!(err.rolled_back);
+    assert_eq!(file_len(&path), len);
+
+    assert_eq!(writer.try_append_with_meta(&[1, 2], b"data").unwrap(), 0);
+    writer.close().unwrap();
+    assert_eq!(
+        read_all::<WithMeta>(&path),
+        vec![(0, b"\x01\x02data".to_vec())]
+    );
+}
+
+#[test]
+fn try_append_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<NoMagicNoHeader>::create(&path, &[]).unwrap();
+    assert_eq!(writer.try_append(b"one").unwrap(), 0);
+    assert_eq!(writer.try_append(b"two").unwrap(), 7);
+    writer.close().unwrap();
+    assert_eq!(
+        read_all::<NoMagicNoHeader>(&path),
+        vec![(0, b"one".to_vec()), (7, b"two".to_vec())]
+    );
+}
+
+fn write_chunks(path: &std::path::Path, chunks: &[&[u8]]) {
+    let mut writer = SeqDataWriter::<Framed>::create(path, b"hd").unwrap();
+    for chunk in chunks {
+        writer.append(chunk).unwrap();
+    }
+    writer.close().unwrap();
+}
+
+// cut `cut` bytes off the end of the file, then recover it
+fn recover_after_cut(cut: u64) -> (u64, Vec<Vec<u8>>) {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_chunks(&path, &[b"one", b"two", b"three"]);
+    let len = file_len(&path);
+    seq_data_file::truncate_at(&path, len - cut).unwrap();
+
+    let (mut writer, header, removed) = SeqDataWriter::<Framed>::open_recover(&path).unwrap();
+    assert_eq!(header, b"hd");
+    writer.append(b"after").unwrap();
+    writer.close().unwrap();
+    let chunks = read_all::<Framed>(&path)
+        .into_iter()
+        .map(|(_, data)| data)
+        .collect();
+    (removed, chunks)
+}
+
+#[test]
+fn open_recover_intact_file() {
+    let (removed, chunks) = recover_after_cut(0);
+    assert_eq!(removed, 0);
+    assert_eq!(chunks, [&b"one"[..], b"two", b"three", b"after"]);
+}
+
+#[test]
+fn open_recover_cut_in_payload() {
+    // the last chunk takes 4 + 5 + 4 bytes, cut within its data
+    let (removed, chunks) = recover_after_cut(6);
+    assert_eq!(removed, 13 - 6);
+    assert_eq!(chunks, [&b"one"[..], b"two", b"after"]);
+}
+
+#[test]
+fn open_recover_cut_in_length_prefix() {
+    // leave 2 bytes of the length prefix of the last chunk
+    let (removed, chunks) = recover_after_cut(11);
+    assert_eq!(removed, 2);
+    assert_eq!(chunks, [&b"one"[..], b"two", b"after"]);
+}
+
+#[test]
+fn open_recover_keeps_corrupted_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_chunks(&path, &[b"one", b"two"]);
+    let mut bytes = std::fs::read(&path).unwrap();
+    // trailing length of the last chunk
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+
+    assert!(SeqDataWriter::<Framed>::open_recover(&path).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+}
+
+#[test]
+fn append_many_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd").unwrap();
+    writer.append(b"first").unwrap();
+    writer.append_many(&[b"one", b"", b"three"]).unwrap();
+    writer.append(b"last").unwrap();
+    writer.close().unwrap();
+
+    // each chunk takes its data, length prefix and trailing length
+    let chunks = read_all::<Framed>(&path);
+    let expected: &[(u64, &[u8])] = &[
+        (0, b"first"),
+        (13, b"one"),
+        (24, b""),
+        (32, b"three"),
+        (45, b"last"),
+    ];
+    assert_eq!(chunks.len(), expected.len());
+    for ((offset, data), (expected_offset, expected_data)) in chunks.iter().zip(expected) {
+        assert_eq!(offset, expected_offset);
+        assert_eq!(data, expected_data);
+    }
+}
+
+#[test]
+fn append_many_chunk_too_large_writes_nothing() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Narrow>::create(&path, &[]).unwrap();
+    writer.append(b"first").unwrap();
+    let large = vec![0; 70_000];
+    assert!(writer.append_many(&[b"one", &large, b"three"]).is_err());
+    writer.close().unwrap();
+
+    let chunks = read_all::<Narrow>(&path);
+    assert_eq!(chunks, [(0, b"first".to_vec())]);
+    assert_eq!(file_len(&path), 6 + 2 + 5);
+}
+
+#[test]
+fn append_many_over_size_limit_writes_nothing() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .with_size_limit(40);
+    writer.append(b"first").unwrap();
+    assert!(writer.append_many(&[b"one", b"two", b"three"]).is_err());
+    writer.close().unwrap();
+
+    assert_eq!(read_all::<Framed>(&path), [(0, b"first".to_vec())]);
+    assert_eq!(file_len(&path) as usize, FIRST_CHUNK_END);
+}
+
+#[test]
+fn buffered_chunks_visible_after_flush() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .with_buffering(1024)
+        .unwrap();
+    writer.append(b"first").unwrap();
+    writer.append(b"second").unwrap();
+    // still in the buffer
+    assert!(read_all::<Framed>(&path).is_empty());
+    assert_eq!(writer.flushed_len(), 0);
+
+    writer.flush().unwrap();
+    assert_eq!(writer.flushed_len(), writer.position());
+    let chunks = read_all::<Framed>(&path);
+    assert_eq!(chunks, [(0, b"first".to_vec()), (13, b"second".to_vec())]);
+
+    writer.append(b"third").unwrap();
+    writer.sync().unwrap();
+    let chunks = read_all::<Framed>(&path);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[2], (27, b"third".to_vec()));
+}
+
+#[test]
+fn failed_create_leaves_no_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let err = SeqDataWriter::<Validated>::create(&path, &[0])
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(!path.exists());
+    assert!(SeqDataWriter::<Validated>::create(&path, &[0, 1]).is_err());
+    assert!(!path.exists());
+
+    SeqDataWriter::<Validated>::create(&path, &[1]).unwrap();
+    assert!(path.exists());
+}
+
+#[test]
+fn create_initializes_empty_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"").unwrap();
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd").unwrap();
+    writer.append(b"first").unwrap();
+    writer.close().unwrap();
+    assert_eq!(read_all::<Framed>(&path), [(0, b"first".to_vec())]);
+}
+
+#[test]
+fn create_rejects_nonempty_file() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    std::fs::write(&path, b"not a seqdata file").unwrap();
+    let err = SeqDataWriter::<Framed>::create(&path, b"hd").err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read(&path).unwrap(), b"not a seqdata file");
+}
+
+#[test]
+fn from_file_rejects_read_only_handle() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .close()
+        .unwrap();
+
+    let file = std::fs::File::open(&path).unwrap();
+    let err = SeqDataWriter::<Framed>::from_file(file).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert_eq!(file_len(&path), 8);
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    let (mut writer, header) = SeqDataWriter::<Framed>::from_file(file).unwrap();
+    assert_eq!(header, b"hd");
+    writer.append(b"first").unwrap();
+    writer.close().unwrap();
+    assert_eq!(read_all::<Framed>(&path), [(0, b"first".to_vec())]);
+}
+
+fn streaming_writer(path: &std::path::Path) -> SeqDataWriter<Framed> {
+    let mut writer = SeqDataWriter::<Framed>::create(path, b"hd").unwrap();
+    writer.append(b"first").unwrap();
+    writer
+}
+
+#[test]
+fn append_streaming() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = streaming_writer(&path);
+    let mut sink = writer.append_streaming(6).unwrap();
+    sink.write_all(b"sec").unwrap();
+    sink.write_all(b"ond").unwrap();
+    assert_eq!(sink.finish().unwrap(), 13);
+    writer.close().unwrap();
+    assert_eq!(
+        read_all::<Framed>(&path),
+        [(0, b"first".to_vec()), (13, b"second".to_vec())]
+    );
+}
+
+#[test]
+fn append_streaming_under_write_rolls_back() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = streaming_writer(&path);
+    let mut sink = writer.append_streaming(6).unwrap();
+    sink.write_all(b"sec").unwrap();
+    let err = sink.finish().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(file_len(&path) as usize, FIRST_CHUNK_END);
+
+    // the writer is still usable
+    writer.append(b"third").unwrap();
+    writer.close().unwrap();
+    assert_eq!(
+        read_all::<Framed>(&path),
+        [(0, b"first".to_vec()), (13, b"third".to_vec())]
+    );
+}
+
+#[test]
+fn append_streaming_over_write_rolls_back() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = streaming_writer(&path);
+    let mut sink = writer.append_streaming(2).unwrap();
+    let err = sink.write_all(b"sec").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(
+        sink.finish().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    writer.close().unwrap();
+    assert_eq!(file_len(&path) as usize, FIRST_CHUNK_END);
+    assert_eq!(read_all::<Framed>(&path), [(0, b"first".to_vec())]);
+}
+
+#[test]
+fn append_streaming_dropped_rolls_back() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = streaming_writer(&path);
+    let mut sink = writer.append_streaming(6).unwrap();
+    sink.write_all(b"second").unwrap();
+    drop(sink);
+    assert_eq!(file_len(&path) as usize, FIRST_CHUNK_END);
+    writer.close().unwrap();
+    assert_eq!(read_all::<Framed>(&path), [(0, b"first".to_vec())]);
+}
+
+#[test]
+fn buffered_writes_match_unbuffered() {
+    let dir = TempDir::new();
+    let chunks = (0..1000u32)
+        .map(|i| i.to_le_bytes()[..(i % 4) as usize].to_vec())
+        .collect::<Vec<_>>();
+    let write = |name: &str, buffering: Option<usize>| {
+        let path = dir.join(name);
+        let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd").unwrap();
+        if let Some(capacity) = buffering {
+            writer = writer.with_buffering(capacity).unwrap();
+        }
+        let offsets = chunks
+            .iter()
+            .map(|chunk| {
+                let offset = writer.position();
+                writer.append(chunk).unwrap();
+                offset
+            })
+            .collect::<Vec<_>>();
+        writer.close().unwrap();
+        (path, offsets)
+    };
+    let (unbuffered, unbuffered_offsets) = write("unbuffered", None);
+    let (buffered, buffered_offsets) = write("buffered", Some(100));
+    assert_eq!(
+        std::fs::read(&unbuffered).unwrap(),
+        std::fs::read(&buffered).unwrap()
+    );
+    assert_eq!(unbuffered_offsets, buffered_offsets);
+    let read = read_all::<Framed>(&buffered);
+    assert_eq!(
+        read.iter().map(|(o, _)| *o).collect::<Vec<_>>(),
+        buffered_offsets
+    );
+    assert_eq!(read.into_iter().map(|(_, d)| d).collect::<Vec<_>>(), chunks);
+}
+
+#[test]
+fn overwrite_at_same_size() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    writer.append(b"first").unwrap();
+    let offset = writer.position();
+    writer.append(b"second").unwrap();
+    writer.append(b"third").unwrap();
+    writer.overwrite_at(offset, b"SECOND").unwrap();
+    // appending goes on at the end
+    writer.append(b"fourth").unwrap();
+    writer.close().unwrap();
+
+    // the checksum is updated along with the data
+    assert_eq!(
+        chunks::<Checksummed>(&path),
+        [&b"first"[..], b"SECOND", b"third", b"fourth"]
+    );
+}
+
+#[test]
+fn overwrite_at_size_mismatch() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    let offset = writer.position();
+    writer.append(b"first").unwrap();
+    writer.flush().unwrap();
+    let before = std::fs::read(&path).unwrap();
+    assert!(writer.overwrite_at(offset, b"longer").is_err());
+    assert!(writer.overwrite_at(offset, b"shor").is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+}
+
+#[test]
+fn overwrite_at_out_of_range() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    // a chunk whose data looks like the length prefix of a 3 bytes chunk
+    writer.append(&[3, 0, 0, 0, b'x']).unwrap();
+    let end = writer.position();
+    writer.flush().unwrap();
+    let before = std::fs::read(&path).unwrap();
+
+    assert!(writer.overwrite_at(end, b"abc").is_err());
+    assert!(writer.overwrite_at(end + 10, b"abc").is_err());
+    // the length read there fits, but the chunk would go past the end of the data
+    assert!(writer.overwrite_at(4, b"abc").is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+}
+
+// run `f` on another thread, checking that it waits for `lock` to be released
+fn blocked_until_unlocked<T: Send + 'static>(
+    lock: std::fs::File,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let handle = std::thread::spawn(move || tx.send(f()).unwrap());
+    assert!(matches!(
+        rx.recv_timeout(std::time::Duration::from_millis(100)),
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout)
+    ));
+    lock.unlock().unwrap();
+    let result = rx.recv().unwrap();
+    handle.join().unwrap();
+    result
+}
+
+#[test]
+fn append_coordinated_waits_for_locked_reader() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    writer.append(b"one").unwrap();
+    writer.flush().unwrap();
+
+    // a reader looking up the length of the file
+    let lock = std::fs::File::open(&path).unwrap();
+    lock.lock_shared().unwrap();
+    let other = std::fs::File::open(&path).unwrap();
+    assert!(other.try_lock().is_err());
+    let offset = blocked_until_unlocked(lock, move || {
+        let offset = writer.append_coordinated(b"two").unwrap();
+        writer.close().unwrap();
+        offset
+    });
+    assert_eq!(offset, 7);
+    assert_eq!(chunks::<Plain>(&path), [b"one", b"two"]);
+}
+
+#[test]
+fn open_locked_waits_for_coordinated_append() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    SeqData::write_all::<Plain, _>(&path, &[], [b"one"]).unwrap();
+
+    // a writer in the middle of a coordinated append
+    let lock = std::fs::File::open(&path).unwrap();
+    lock.lock().unwrap();
+    let reader_path = path.clone();
+    let (reader, _) = blocked_until_unlocked(lock, move || {
+        SeqDataReader::<Plain>::open_locked(reader_path).unwrap()
+    });
+    assert_eq!(reader.map(|r| r.unwrap().1).collect::<Vec<_>>(), [b"one"]);
+}
+
+#[test]
+fn estimate_remaining_chunks_under_size_limit() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    assert_eq!(writer.estimate_remaining_chunks(6), None);
+
+    // magic and room for 3 chunks of 6 bytes with their length prefix
+    let mut writer = writer.with_size_limit(5 + 3 * 10 + 9);
+    assert_eq!(writer.current_len(), 5);
+    assert_eq!(writer.estimate_remaining_chunks(6), Some(3));
+    writer.append(b"sixsix").unwrap();
+    assert_eq!(writer.current_len(), 15);
+    assert_eq!(writer.estimate_remaining_chunks(6), Some(2));
+    writer.append(b"sixsix").unwrap();
+    writer.append(b"sixsix").unwrap();
+    assert_eq!(writer.estimate_remaining_chunks(6), Some(0));
+    assert!(writer.append(b"sixsix").is_err());
+    assert_eq!(writer.estimate_remaining_chunks(0), Some(2));
+}
+
+#[test]
+fn append_slice_iter_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    writer.append(b"first").unwrap();
+    let offsets = writer
+        .append_slice_iter(&[&b"one"[..], b"", b"three"])
+        .unwrap();
+    assert_eq!(offsets, [9, 16, 20]);
+    assert!(writer.append_slice_iter(&[]).unwrap().is_empty());
+    writer.close().unwrap();
+    assert_eq!(
+        read_all::<Plain>(&path),
+        vec![
+            (0, b"first".to_vec()),
+            (9, b"one".to_vec()),
+            (16, b"".to_vec()),
+            (20, b"three".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn append_with_calls_back_written_offsets() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[])
+        .unwrap()
+        .with_size_limit(5 + 7 + 7);
+    let mut index = Vec::new();
+    writer
+        .append_with(b"one", |offset| index.push(offset))
+        .unwrap();
+    writer
+        .append_with(b"two", |offset| index.push(offset))
+        .unwrap();
+    // not written, so not indexed
+    assert!(writer
+        .append_with(b"three", |offset| index.push(offset))
+        .is_err());
+    writer.close().unwrap();
+    assert_eq!(index, [0, 7]);
+    assert_eq!(chunks::<Plain>(&path), [b"one", b"two"]);
+}
+
+#[test]
+fn open_append_creates_or_appends() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let (mut writer, header) = SeqDataWriter::<Framed>::open_append(&path, b"v1").unwrap();
+    assert_eq!(header, b"v1");
+    writer.append(b"one").unwrap();
+    writer.close().unwrap();
+
+    // the header of the existing file is kept
+    let (mut writer, header) = SeqDataWriter::<Framed>::open_append(&path, b"v2").unwrap();
+    assert_eq!(header, b"v1");
+    assert_eq!(writer.position(), 11);
+    writer.append(b"two").unwrap();
+    writer.close().unwrap();
+    assert!(SeqDataWriter::<Framed>::create(&path, b"v3").is_err());
+
+    let (_, header) = SeqDataReader::<Framed>::open(&path).unwrap();
+    assert_eq!(header, b"v1");
+    assert_eq!(chunks::<Framed>(&path), [b"one", b"two"]);
+}
+
+#[test]
+fn stream_writer_to_memory_sink() {
+    let mut writer = SeqDataStreamWriter::<_, Framed>::new(Vec::new(), b"hd").unwrap();
+    writer.append(b"one").unwrap();
+    assert_eq!(writer.position(), 11);
+    writer.append(b"two").unwrap();
+    let bytes = writer.close().unwrap();
+
+    // the same bytes as a file written with the same chunks
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    write_chunks(&path, &[b"one", b"two"]);
+    assert_eq!(bytes, std::fs::read(&path).unwrap());
+
+    let (reader, header) = SeqDataReader::<Framed, _>::from_bytes(bytes).unwrap();
+    assert_eq!(header, b"hd");
+    let chunks = reader.map(|c| c.unwrap()).collect::<Vec<_>>();
+    assert_eq!(chunks, [(0, b"one".to_vec()), (11, b"two".to_vec())]);
+}
+
+#[test]
+fn append_and_read_back() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .with_buffering(1024)
+        .unwrap();
+    writer.append(b"one").unwrap();
+    assert_eq!(
+        writer.append_and_read(b"two").unwrap(),
+        (11, b"two".to_vec())
+    );
+    // the next chunks are still appended after it
+    writer.append(b"three").unwrap();
+    assert_eq!(writer.append_and_read(b"").unwrap(), (35, Vec::new()));
+    writer.close().unwrap();
+    assert_eq!(
+        chunks::<Framed>(&path),
+        [&b"one"[..], b"two", b"three", b""]
+    );
+}
+
+#[test]
+fn append_repeated_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Checksummed>::create(&path, &[]).unwrap();
+    assert!(writer.append_repeated(b"none", 0).unwrap().is_empty());
+    // enough copies to take more than one write
+    let data = [7; 1000];
+    let n = 2 * REPEAT_WRITE_SIZE / data.len();
+    let offsets = writer.append_repeated(&data, n).unwrap();
+    let size = 4 + 1000 + 4;
+    assert_eq!(offsets, (0..n as u64).map(|i| i * size).collect::<Vec<_>>());
+    writer.append(b"after").unwrap();
+    writer.close().unwrap();
+
+    let read = read_all::<Checksummed>(&path);
+    assert_eq!(read.len(), n + 1);
+    assert!(read[..n].iter().all(|(_, chunk)| *chunk == data));
+    assert_eq!(read[n], (n as u64 * size, b"after".to_vec()));
+
+    // all or nothing against the size limit
+    let path = dir.join("limited");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[])
+        .unwrap()
+        .with_size_limit(5 + 2 * 7);
+    assert!(writer.append_repeated(b"one", 3).is_err());
+    assert_eq!(writer.append_repeated(b"one", 2).unwrap(), [0, 7]);
+    writer.close().unwrap();
+    assert_eq!(chunks::<Plain>(&path), [b"one", b"one"]);
+}
+
+#[test]
+fn append_dedup_skips_immediate_repeats() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    assert_eq!(writer.append_dedup(b"one").unwrap(), Some(0));
+    assert_eq!(writer.append_dedup(b"one").unwrap(), None);
+    assert_eq!(writer.append_dedup(b"two").unwrap(), Some(7));
+    // a chunk appended in between resets the comparison
+    writer.append(b"three").unwrap();
+    assert_eq!(writer.append_dedup(b"two").unwrap(), Some(23));
+    assert_eq!(writer.append_dedup(b"two").unwrap(), None);
+
+    // chunks too big to be remembered are always written
+    let big = vec![0; DEDUP_MAX_LEN + 1];
+    assert_eq!(writer.append_dedup(&big).unwrap(), Some(30));
+    assert!(writer.append_dedup(&big).unwrap().is_some());
+    writer.close().unwrap();
+    assert_eq!(chunks::<Plain>(&path).len(), 6);
+}
+
+#[test]
+fn flushed_len_matches_what_readers_see() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .with_buffering(16)
+        .unwrap();
+    let reader_len = || {
+        SeqDataReader::<Framed>::open(&path)
+            .unwrap()
+            .0
+            .append_position()
+    };
+    writer.append(b"one").unwrap();
+    assert_eq!(writer.flushed_len(), 0);
+    assert_eq!(reader_len(), 0);
+    // overflowing the buffer writes out some of the pending data
+    writer.append(&[0; 32]).unwrap();
+    assert!(writer.flushed_len() > 0);
+    assert!(writer.flushed_len() < writer.position());
+    assert_eq!(reader_len(), writer.flushed_len());
+    writer.flush().unwrap();
+    assert_eq!(writer.flushed_len(), writer.position());
+    assert_eq!(reader_len(), writer.position());
+}
+
+#[test]
+fn append_durable_writes_through_the_buffer() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Framed>::create(&path, b"hd")
+        .unwrap()
+        .with_buffering(1024)
+        .unwrap();
+    writer.append(b"buffered").unwrap();
+    assert_eq!(writer.append_durable(b"durable").unwrap(), 16);
+    // both chunks reached the file, without closing the writer
+    assert_eq!(chunks::<Framed>(&path), [&b"buffered"[..], b"durable"]);
+    assert_eq!(writer.flushed_len(), writer.position());
+}
+
+#[test]
+fn append_split_into_bounded_chunks() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let mut writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    assert_eq!(writer.append_split(b"0123456789", 4).unwrap(), [0, 8, 16]);
+    assert_eq!(writer.append_split(b"", 4).unwrap(), [22]);
+    let err = writer.append_split(b"x", 0).unwrap_err();
+    assert!(matches!(err, SeqDataError::InvalidInput(_)), "{:?}", err);
+    drop(writer);
+    assert_eq!(chunks::<Plain>(&path), [&b"0123"[..], b"4567", b"89", b""]);
+}
+
+#[test]
+fn spawn_consumer_appends_received_messages() {
+    let dir = TempDir::new();
+    let path = dir.join("data");
+    let writer = SeqDataWriter::<Plain>::create(&path, &[]).unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let handle = writer.spawn_consumer(rx);
+    let senders = (0..4u8)
+        .map(|i| {
+            let tx = tx.clone();
+            std::thread::spawn(move || tx.send(vec![i; 3]).unwrap())
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    assert_eq!(handle.join().unwrap().unwrap(), 4);
+    let mut read = chunks::<Plain>(&path);
+    read.sort();
+    assert_eq!(read, (0..4u8).map(|i| vec![i; 3]).collect::<Vec<_>>());
+}